@@ -1,43 +1,568 @@
 #ifndef MOTOR_INTERFACE_H_
 #define MOTOR_INTERFACE_H_
 
+// Generated by cbindgen from src/ffi.rs, do not edit by hand. Rebuild with
+// HAPPY_UPDATE_HEADER=1 set to update the copy in happy_ros2_control.
+
+#include <stdbool.h>
 #include <stdint.h>
 
-const char * LEFT_SERVO_NAME = "amy_485_port_left";
-const char * RIGHT_SERVO_NAME = "amy_485_port_right";
-const uint8_t DEFAULT_DEVICE_ADDRESS = 0x1;
+#define LEFT_SERVO_NAME "amy_485_port_left"
+#define RIGHT_SERVO_NAME "amy_485_port_right"
+
+#define DEFAULT_DEVICE_ADDRESS 1
+
+typedef enum MotorControllerStatus {
+  MOTOR_CONTROLLER_STATUS_OK = 0,
+  MOTOR_CONTROLLER_STATUS_NULL_POINTER = 1,
+  MOTOR_CONTROLLER_STATUS_INVALID_ARGUMENT = 2,
+  MOTOR_CONTROLLER_STATUS_SERIAL_ERROR = 3,
+  MOTOR_CONTROLLER_STATUS_IO_ERROR = 4,
+  MOTOR_CONTROLLER_STATUS_TIMEOUT = 5,
+  MOTOR_CONTROLLER_STATUS_BAD_RESPONSE = 6,
+  MOTOR_CONTROLLER_STATUS_MODBUS_EXCEPTION = 7,
+  MOTOR_CONTROLLER_STATUS_PANIC = 8,
+} MotorControllerStatus;
+
+typedef enum MotorAlarm {
+  MOTOR_ALARM_NONE = 0,
+  MOTOR_ALARM_HIGH_TEMPERATURE = 16,
+  MOTOR_ALARM_OVERHEAT = 17,
+  MOTOR_ALARM_SYSTEM_STALL = 18,
+  MOTOR_ALARM_UNDER_VOLTAGE = 19,
+  MOTOR_ALARM_LOAD_TOO_HEAVY = 20,
+  MOTOR_ALARM_FLASH_WRITE_FAILED = 32,
+} MotorAlarm;
+
+typedef enum WheelPolarity {
+  WHEEL_POLARITY_FORWARD,
+  WHEEL_POLARITY_REVERSED,
+} WheelPolarity;
+
+typedef enum PollItem {
+  POLL_ITEM_SPEED,
+  POLL_ITEM_POSITION,
+  POLL_ITEM_ALARM,
+  POLL_ITEM_CURRENT,
+  POLL_ITEM_VOLTAGE,
+  POLL_ITEM_TEMPERATURE,
+} PollItem;
+
+typedef struct diff_drive_base_t diff_drive_base_t;
+
+typedef struct motor_controller_t motor_controller_t;
+
+typedef struct motor_poller_t motor_poller_t;
+
+typedef struct MotorConfig {
+  uint32_t gear;
+  float wheel_length;
+  uint32_t encoder_count;
+  float wheel_separation;
+  uint32_t max_rpm;
+  uint32_t baud_rate;
+  uint32_t connection_timeout_ms;
+} MotorConfig;
+
+typedef struct MotorTelemetry {
+  enum MotorAlarm alarm;
+  float current;
+  int16_t rpm;
+  float voltage;
+  uint16_t temperature;
+  float pwm;
+  double timestamp;
+} MotorTelemetry;
+
+typedef struct RetryStats {
+  uint64_t requests;
+  uint64_t retries;
+  uint64_t failures;
+  uint32_t last_request_retries;
+  uint32_t most_retries;
+} RetryStats;
+
+typedef struct WatchdogConfig {
+  uint32_t timeout_ms;
+  uint32_t ramp_ms;
+  bool disable_motor;
+} WatchdogConfig;
+
+typedef struct WatchdogTrip {
+  uint8_t device_address;
+  int16_t speed;
+  bool stopped;
+  bool disabled;
+} WatchdogTrip;
+
+typedef struct MotionLimits {
+  float acceleration;
+  float deceleration;
+  float jerk;
+  float emergency_deceleration;
+} MotionLimits;
+
+typedef struct DiffDriveConfig {
+  struct MotorConfig motor;
+  enum WheelPolarity left_polarity;
+  enum WheelPolarity right_polarity;
+  struct MotionLimits wheel_limits;
+  struct MotionLimits linear_limits;
+  struct MotionLimits angular_limits;
+} DiffDriveConfig;
+
+typedef struct WheelSpeeds {
+  float left;
+  float right;
+} WheelSpeeds;
+
+typedef struct WheelState {
+  float velocity;
+  float position;
+  int32_t counts;
+} WheelState;
+
+typedef struct PollRate {
+  enum PollItem item;
+  uint32_t period_ms;
+} PollRate;
+
+typedef struct PolledDrive {
+  uint8_t device_address;
+  int16_t rpm;
+  double rpm_age;
+  int32_t position;
+  double position_age;
+  enum MotorAlarm alarm;
+  double alarm_age;
+  float current;
+  double current_age;
+  float voltage;
+  double voltage_age;
+  uint16_t temperature;
+  double temperature_age;
+  uint64_t failed_polls;
+  uint64_t failed_writes;
+} PolledDrive;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+// The message for the last call on this thread that didn't return `Ok`, or null if there
+// hasn't been one.
+//
+// # Safety
+// The string belongs to the library and is only valid until the next failing call on the same
+// thread. Copy it if it needs to live longer.
+const char *motor_controller_last_error(void);
+
+// # Safety
+// `port_path` must be a valid, NUL-terminated C string. `out` must point to somewhere a
+// controller pointer can be written, which is only done on success.
+enum MotorControllerStatus motor_controller_new(const char *port_path,
+                                                uint8_t device_address,
+                                                struct motor_controller_t **out_controller);
+
+// Like `motor_controller_new`, for a robot that doesn't match the defaults. `config` may be null
+// for the defaults.
+//
+// # Safety
+// `port_path` must be a valid, NUL-terminated C string. `config` must be null or point to a
+// `MotorConfig`. `out_controller` must point to somewhere a controller pointer can be written,
+// which is only done on success.
+enum MotorControllerStatus motor_controller_new_with_config(const char *port_path,
+                                                            uint8_t device_address,
+                                                            const struct MotorConfig *config,
+                                                            struct motor_controller_t **out_controller);
+
+// The geometry this library was built for, to start from when only some settings need changing.
+struct MotorConfig motor_config_default(void);
+
+// Reads a `.toml`, `.yaml` or `.yml` file. Anything the file leaves out keeps its default.
+//
+// # Safety
+// `path` must be a valid, NUL-terminated C string. `out_config` must be valid to write a
+// `MotorConfig` to, which is only done on success.
+enum MotorControllerStatus motor_config_load(const char *path, struct MotorConfig *out_config);
+
+// Sets one setting from its name and value as text, e.g. a ros2_control hardware parameter.
+// The config is left alone if the name or value is no good.
+//
+// # Safety
+// `config` must point to a `MotorConfig`. `key` and `value` must be valid, NUL-terminated C
+// strings.
+enum MotorControllerStatus motor_config_set(struct MotorConfig *config,
+                                            const char *key,
+                                            const char *value);
+
+// Checks the settings make sense together. Controllers check this themselves, this is for
+// finding out early.
+//
+// # Safety
+// `config` must point to a `MotorConfig`.
+enum MotorControllerStatus motor_config_validate(const struct MotorConfig *config);
+
+// A motor that was last told to move is stopped first, following the watchdog's ramp if there
+// is one.
+//
+// # Safety
+// `ptr` must be null or a pointer returned by `motor_controller_new`. It must not be used again afterwards.
+void motor_controller_free(struct motor_controller_t *ptr);
+
+// # Safety
+// `ptr` must be a pointer returned by `motor_controller_new` that has not yet been freed.
+enum MotorControllerStatus motor_controller_enable_modbus(struct motor_controller_t *ptr);
+
+// # Safety
+// `ptr` must be a pointer returned by `motor_controller_new` that has not yet been freed.
+enum MotorControllerStatus motor_controller_set_motor_enabled(struct motor_controller_t *ptr);
+
+// # Safety
+// `ptr` must be a pointer returned by `motor_controller_new` that has not yet been freed.
+enum MotorControllerStatus motor_controller_set_motor_disabled(struct motor_controller_t *ptr);
+
+// # Safety
+// `ptr` must be a pointer returned by `motor_controller_new` that has not yet been freed.
+// `out_position` must be valid to write an `int32_t` to.
+enum MotorControllerStatus motor_controller_get_position(struct motor_controller_t *ptr,
+                                                         int32_t *out_position);
+
+// # Safety
+// `ptr` must be a pointer returned by `motor_controller_new` that has not yet been freed.
+// `out_velocity` must be valid to write a `float` to.
+enum MotorControllerStatus motor_controller_get_velocity(struct motor_controller_t *ptr,
+                                                         float *out_velocity);
+
+// The speed the drive settled on is written to `out_velocity`, unless it is null.
+//
+// # Safety
+// `ptr` must be a pointer returned by `motor_controller_new` that has not yet been freed.
+// `out_velocity` must be null or valid to write a `float` to.
+enum MotorControllerStatus motor_controller_set_velocity(struct motor_controller_t *ptr,
+                                                         float speed,
+                                                         float *out_velocity);
+
+// # Safety
+// `ptr` must be a pointer returned by `motor_controller_new` that has not yet been freed.
+enum MotorControllerStatus motor_controller_set_position_feedforward(struct motor_controller_t *ptr,
+                                                                     int16_t ff);
+
+// # Safety
+// `ptr` must be a pointer returned by `motor_controller_new` that has not yet been freed.
+enum MotorControllerStatus motor_controller_set_position_gain(struct motor_controller_t *ptr,
+                                                              int16_t gain);
+
+// Whether the drive stops for this alarm, rather than just warning about it.
+bool motor_alarm_is_fatal(enum MotorAlarm alarm);
+
+// # Safety
+// `ptr` must be a pointer returned by `motor_controller_new` that has not yet been freed.
+// `out_alarm` must be valid to write a `MotorAlarm` to.
+enum MotorControllerStatus motor_controller_get_status(struct motor_controller_t *ptr,
+                                                       enum MotorAlarm *out_alarm);
+
+// # Safety
+// `ptr` must be a pointer returned by `motor_controller_new` that has not yet been freed.
+// `out_rpm` must be valid to write an `int16_t` to.
+enum MotorControllerStatus motor_controller_get_rpm(struct motor_controller_t *ptr,
+                                                    int16_t *out_rpm);
+
+// The speed the drive settled on is written to `out_rpm`, unless it is null.
+//
+// # Safety
+// `ptr` must be a pointer returned by `motor_controller_new` that has not yet been freed.
+// `out_rpm` must be null or valid to write an `int16_t` to.
+enum MotorControllerStatus motor_controller_set_rpm(struct motor_controller_t *ptr,
+                                                    int16_t rpm,
+                                                    int16_t *out_rpm);
+
+// Reads any register by number. Numbers the drive doesn't have give `INVALID_ARGUMENT`
+// without anything being sent.
+//
+// # Safety
+// `ptr` must be a pointer returned by `motor_controller_new` that has not yet been freed.
+// `out_value` must be valid to write a `uint16_t` to.
+enum MotorControllerStatus motor_controller_read_register(struct motor_controller_t *ptr,
+                                                          uint16_t register_number,
+                                                          uint16_t *out_value);
+
+// `count` registers in a row from `first_register`, in one transaction. They all have to be
+// registers the drive has, or it's `INVALID_ARGUMENT` without anything being sent. Nothing is
+// written to `out_values` unless the read succeeds.
+//
+// # Safety
+// `ptr` must be a pointer returned by `motor_controller_new` that has not yet been freed.
+// `out_values` must be valid to write `count` `uint16_t`s to.
+enum MotorControllerStatus motor_controller_read_registers(struct motor_controller_t *ptr,
+                                                           uint16_t first_register,
+                                                           uint16_t count,
+                                                           uint16_t *out_values);
+
+// Writes any register by number. Numbers the drive doesn't have give `INVALID_ARGUMENT`
+// without anything being sent.
+//
+// # Safety
+// `ptr` must be a pointer returned by `motor_controller_new` that has not yet been freed.
+enum MotorControllerStatus motor_controller_write_register(struct motor_controller_t *ptr,
+                                                           uint16_t register_number,
+                                                           uint16_t value);
+
+// Alarm, current, speed, voltage, temperature and PWM in a single read, which is quicker than
+// asking for them one at a time.
+//
+// # Safety
+// `ptr` must be a pointer returned by `motor_controller_new` that has not yet been freed.
+// `out_telemetry` must be valid to write a `MotorTelemetry` to.
+enum MotorControllerStatus motor_controller_get_telemetry(struct motor_controller_t *ptr,
+                                                          struct MotorTelemetry *out_telemetry);
+
+// Motor current, in amps.
+//
+// # Safety
+// `ptr` must be a pointer returned by `motor_controller_new` that has not yet been freed.
+// `out_current` must be valid to write a `float` to.
+enum MotorControllerStatus motor_controller_get_current(struct motor_controller_t *ptr,
+                                                        float *out_current);
+
+// Supply voltage, in volts.
+//
+// # Safety
+// `ptr` must be a pointer returned by `motor_controller_new` that has not yet been freed.
+// `out_voltage` must be valid to write a `float` to.
+enum MotorControllerStatus motor_controller_get_voltage(struct motor_controller_t *ptr,
+                                                        float *out_voltage);
+
+// Drive temperature, in degrees C.
+//
+// # Safety
+// `ptr` must be a pointer returned by `motor_controller_new` that has not yet been freed.
+// `out_temperature` must be valid to write a `uint16_t` to.
+enum MotorControllerStatus motor_controller_get_temperature(struct motor_controller_t *ptr,
+                                                            uint16_t *out_temperature);
+
+// Output duty cycle, from -100 to 100 percent.
+//
+// # Safety
+// `ptr` must be a pointer returned by `motor_controller_new` that has not yet been freed.
+// `out_pwm` must be valid to write a `float` to.
+enum MotorControllerStatus motor_controller_get_output_pwm(struct motor_controller_t *ptr,
+                                                           float *out_pwm);
+
+// # Safety
+// `ptr` must be a pointer returned by `motor_controller_new` that has not yet been freed.
+// `out_stats` must be valid to write a `RetryStats` to.
+enum MotorControllerStatus motor_controller_get_retry_stats(struct motor_controller_t *ptr,
+                                                            struct RetryStats *out_stats);
+
+// A starting point for `motor_controller_set_watchdog`.
+struct WatchdogConfig watchdog_config_default(void);
+
+// Stops the motor if no speed is set for `config->timeout_ms`. Null turns the watchdog off.
+// Freeing the controller always stops a moving motor, watchdog or not.
+//
+// # Safety
+// `ptr` must be a pointer returned by `motor_controller_new` that has not yet been freed.
+// `config` must be null or point to a `WatchdogConfig`.
+enum MotorControllerStatus motor_controller_set_watchdog(struct motor_controller_t *ptr,
+                                                         const struct WatchdogConfig *config);
+
+// Takes the oldest watchdog trip not yet collected. `out_tripped` says whether there was one,
+// and `out_trip` is only written if so.
+//
+// # Safety
+// `ptr` must be a pointer returned by `motor_controller_new` that has not yet been freed.
+// `out_trip` must be valid to write a `WatchdogTrip` to, and `out_tripped` a `bool`.
+enum MotorControllerStatus motor_controller_take_watchdog_trip(struct motor_controller_t *ptr,
+                                                               struct WatchdogTrip *out_trip,
+                                                               bool *out_tripped);
+
+// Reads every register the drive saves to flash and writes them to a `.toml` or `.json` file,
+// to be put back later with `motor_controller_load_profile`.
+//
+// # Safety
+// `ptr` must be a pointer returned by `motor_controller_new` that has not yet been freed.
+// `path` must be a valid, NUL-terminated C string.
+enum MotorControllerStatus motor_controller_save_profile(struct motor_controller_t *ptr,
+                                                         const char *path);
+
+// Writes a profile file to the drive and reads it back to check it took. With `persist` it's
+// then saved to the drive's flash, failing if the drive says the flash write didn't work.
+//
+// # Safety
+// `ptr` must be a pointer returned by `motor_controller_new` that has not yet been freed.
+// `path` must be a valid, NUL-terminated C string.
+enum MotorControllerStatus motor_controller_load_profile(struct motor_controller_t *ptr,
+                                                         const char *path,
+                                                         bool persist);
+
+// The defaults for the robot, to start from when only some settings need changing.
+struct DiffDriveConfig diff_drive_config_default(void);
+
+// `motor_config_set`, plus `left_polarity` and `right_polarity` (`forward` or `reversed`), and
+// the limits as `wheel_`, `linear_` or `angular_` followed by `acceleration`, `deceleration`,
+// `jerk` or `emergency_deceleration`.
+//
+// # Safety
+// `config` must point to a `DiffDriveConfig`. `key` and `value` must be valid, NUL-terminated C
+// strings.
+enum MotorControllerStatus diff_drive_config_set(struct DiffDriveConfig *config,
+                                                 const char *key,
+                                                 const char *value);
+
+// Opens both drives. `config` may be null for the defaults.
+//
+// # Safety
+// `left_port` and `right_port` must be valid, NUL-terminated C strings. `config` must be null or
+// point to a `DiffDriveConfig`. `out_base` must point to somewhere a base pointer can be
+// written, which is only done on success.
+enum MotorControllerStatus diff_drive_base_new(const char *left_port,
+                                               uint8_t left_address,
+                                               const char *right_port,
+                                               uint8_t right_address,
+                                               const struct DiffDriveConfig *config,
+                                               struct diff_drive_base_t **out_base);
+
+// Stops both wheels first, as `motor_controller_free` does.
+//
+// # Safety
+// `ptr` must be null or a pointer returned by `diff_drive_base_new`. It must not be used again afterwards.
+void diff_drive_base_free(struct diff_drive_base_t *ptr);
+
+// Turns on Modbus control and the motors on both sides.
+//
+// # Safety
+// `ptr` must be a pointer returned by `diff_drive_base_new` that has not yet been freed.
+enum MotorControllerStatus diff_drive_base_enable(struct diff_drive_base_t *ptr);
+
+// Both sides are tried even if the first fails.
+//
+// # Safety
+// `ptr` must be a pointer returned by `diff_drive_base_new` that has not yet been freed.
+enum MotorControllerStatus diff_drive_base_disable(struct diff_drive_base_t *ptr);
+
+// # Safety
+// `ptr` must be a pointer returned by `diff_drive_base_new` that has not yet been freed.
+enum MotorControllerStatus diff_drive_base_stop(struct diff_drive_base_t *ptr);
+
+// `linear` in m/s and `angular` in rad/s, anticlockwise positive. The wheel speeds the drives
+// settled on are written to `out_speeds`, unless it is null.
+//
+// # Safety
+// `ptr` must be a pointer returned by `diff_drive_base_new` that has not yet been freed.
+// `out_speeds` must be null or valid to write a `WheelSpeeds` to.
+enum MotorControllerStatus diff_drive_base_set_twist(struct diff_drive_base_t *ptr,
+                                                     float linear,
+                                                     float angular,
+                                                     struct WheelSpeeds *out_speeds);
+
+// What `diff_drive_base_tick` heads for. Nothing is sent until then.
+//
+// # Safety
+// `ptr` must be a pointer returned by `diff_drive_base_new` that has not yet been freed.
+enum MotorControllerStatus diff_drive_base_set_twist_target(struct diff_drive_base_t *ptr,
+                                                            float linear,
+                                                            float angular);
 
+// Call once per control loop with the seconds since the last call. Moves the twist towards its
+// target within the config's limits and sends it as `diff_drive_base_set_twist` does.
+//
+// # Safety
+// `ptr` must be a pointer returned by `diff_drive_base_new` that has not yet been freed.
+// `out_speeds` must be null or valid to write a `WheelSpeeds` to.
+enum MotorControllerStatus diff_drive_base_tick(struct diff_drive_base_t *ptr,
+                                                double period,
+                                                struct WheelSpeeds *out_speeds);
 
-typedef struct motor_controller motor_controller_t;
+// Brakes at the emergency limits on the following ticks, until `diff_drive_base_resume`.
+//
+// # Safety
+// `ptr` must be a pointer returned by `diff_drive_base_new` that has not yet been freed.
+enum MotorControllerStatus diff_drive_base_emergency_stop(struct diff_drive_base_t *ptr);
 
-extern motor_controller_t *
-motor_controller_new(const char *port_path, const uint8_t device_address);
+// # Safety
+// `ptr` must be a pointer returned by `diff_drive_base_new` that has not yet been freed.
+enum MotorControllerStatus diff_drive_base_resume(struct diff_drive_base_t *ptr);
 
-extern void
-motor_controller_free(motor_controller_t *);
+// Reads both wheels back to back. Nothing is written unless both reads succeed.
+//
+// # Safety
+// `ptr` must be a pointer returned by `diff_drive_base_new` that has not yet been freed.
+// `out_left` and `out_right` must be valid to write a `WheelState` to.
+enum MotorControllerStatus diff_drive_base_read(struct diff_drive_base_t *ptr,
+                                                struct WheelState *out_left,
+                                                struct WheelState *out_right);
 
-extern void
-motor_controller_enable_modbus(motor_controller_t *);
+// The same watchdog on both wheels, fed by `diff_drive_base_set_twist`. Null turns it off.
+//
+// # Safety
+// `ptr` must be a pointer returned by `diff_drive_base_new` that has not yet been freed.
+// `config` must be null or point to a `WatchdogConfig`.
+enum MotorControllerStatus diff_drive_base_set_watchdog(struct diff_drive_base_t *ptr,
+                                                        const struct WatchdogConfig *config);
 
-extern void
-motor_controller_set_motor_enabled(motor_controller_t *);
+// As `motor_controller_take_watchdog_trip`, for either wheel.
+//
+// # Safety
+// `ptr` must be a pointer returned by `diff_drive_base_new` that has not yet been freed.
+// `out_trip` must be valid to write a `WatchdogTrip` to, and `out_tripped` a `bool`.
+enum MotorControllerStatus diff_drive_base_take_watchdog_trip(struct diff_drive_base_t *ptr,
+                                                              struct WatchdogTrip *out_trip,
+                                                              bool *out_tripped);
 
-extern void
-motor_controller_set_position_feedforward(motor_controller_t *, uint16_t);
+// Opens `port_path` and starts reading the drives at `addresses` in the background. `rates` may
+// be null for the defaults: speed and position every 50ms, current every 200ms, alarm every
+// 500ms and voltage and temperature every second. `config` may be null for the defaults.
+//
+// # Safety
+// `port_path` must be a valid, NUL-terminated C string. `addresses` must point to
+// `address_count` addresses, and `rates` be null or point to `rate_count` `PollRate`s. `config`
+// must be null or point to a `MotorConfig`. `out_poller` must point to somewhere a poller pointer
+// can be written, which is only done on success.
+enum MotorControllerStatus motor_poller_new(const char *port_path,
+                                            const struct MotorConfig *config,
+                                            const uint8_t *addresses,
+                                            uintptr_t address_count,
+                                            const struct PollRate *rates,
+                                            uintptr_t rate_count,
+                                            struct motor_poller_t **out_poller);
 
-extern void
-motor_controller_set_position_gain(motor_controller_t *, uint16_t);
+// Stops the polling thread, dropping any writes not yet sent. Moving motors are stopped, as with
+// `motor_controller_free`.
+//
+// # Safety
+// `ptr` must be null or a pointer returned by `motor_poller_new`. It must not be used again afterwards.
+void motor_poller_free(struct motor_poller_t *ptr);
 
-extern void
-motor_controller_set_motor_disabled(motor_controller_t *);
+// The latest values for one drive. Never waits on the bus.
+//
+// # Safety
+// `ptr` must be a pointer returned by `motor_poller_new` that has not yet been freed.
+// `out_drive` must be valid to write a `PolledDrive` to.
+enum MotorControllerStatus motor_poller_read(struct motor_poller_t *ptr,
+                                             uint8_t device_address,
+                                             struct PolledDrive *out_drive);
 
-extern int32_t
-motor_controller_get_position(motor_controller_t *);
+// Queues a speed to go out ahead of the next poll, replacing any not yet sent. Failures only
+// show up in `failed_writes`.
+//
+// # Safety
+// `ptr` must be a pointer returned by `motor_poller_new` that has not yet been freed.
+enum MotorControllerStatus motor_poller_set_rpm(struct motor_poller_t *ptr,
+                                                uint8_t device_address,
+                                                int16_t speed);
 
-extern float
-motor_controller_get_velocity(motor_controller_t *);
+// As `motor_poller_set_rpm`, for any register.
+//
+// # Safety
+// `ptr` must be a pointer returned by `motor_poller_new` that has not yet been freed.
+enum MotorControllerStatus motor_poller_write_register(struct motor_poller_t *ptr,
+                                                       uint8_t device_address,
+                                                       uint16_t register_,
+                                                       uint16_t value);
 
-extern float
-motor_controller_set_velocity(motor_controller_t *, float);
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
 
-#endif // MOTOR_INTERFACE_H_
\ No newline at end of file
+#endif  /* MOTOR_INTERFACE_H_ */
@@ -0,0 +1,31 @@
+use std::env;
+use std::path::PathBuf;
+
+// Where the ros2_control side picks the header up from
+const CHECKED_IN_HEADER: &str = "../happy_ros2_control/include/motor_interface/motor_interface.h";
+
+fn main() {
+    let crate_dir = PathBuf::from(env::var("CARGO_MANIFEST_DIR").unwrap());
+    let out_dir = PathBuf::from(env::var("OUT_DIR").unwrap());
+
+    // Types exported to C are spread across the crate, not just ffi.rs
+    println!("cargo:rerun-if-changed=src");
+    println!("cargo:rerun-if-changed=cbindgen.toml");
+    println!("cargo:rerun-if-env-changed=HAPPY_UPDATE_HEADER");
+
+    let config = cbindgen::Config::from_file(crate_dir.join("cbindgen.toml"))
+        .expect("Failed to read cbindgen.toml!");
+
+    let bindings = cbindgen::Builder::new()
+        .with_crate(&crate_dir)
+        .with_config(config)
+        .generate()
+        .expect("Failed to generate the C header!");
+
+    // The drift test compares the checked-in header against this one
+    bindings.write_to_file(out_dir.join("motor_interface.h"));
+
+    if env::var_os("HAPPY_UPDATE_HEADER").is_some() {
+        bindings.write_to_file(crate_dir.join(CHECKED_IN_HEADER));
+    }
+}
@@ -0,0 +1,526 @@
+use crate::message::ModbusRegister;
+use crate::motor_controller::config::{ConfigError, MotorConfig};
+use crate::motor_controller::discovery::{self, DiscoveredDrive, DiscoveryOptions};
+use crate::motor_controller::error::MotorControllerError;
+use crate::motor_controller::profile::{DriveProfile, ProfileError};
+use crate::motor_controller::MotorController;
+use crate::transport::SerialTransport;
+use std::io::Write;
+use std::ops::RangeInclusive;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+use thiserror::Error;
+
+// The guts of the motor-bench binary. Everything goes through MotorController,
+// same as on the robot, so what works on the bench works there too.
+
+pub const USAGE: &str = "\
+Usage: motor-bench [--port PATH] [--address N] [--config FILE] COMMAND
+
+The port can also come from HAPPY_MOTOR_PORT. The address defaults to 1.
+
+Commands:
+  ports                           List serial ports
+  scan [--baud N]... [--addresses A-B]
+                                  Look for drives, on --port or every port
+  read [REGISTER]                 Read one register, or all of them
+  write REGISTER VALUE            Write one register
+  enable                          Enable modbus control and the motor output
+  disable                         Disable the motor output
+  rpm RPM [--for SECS]            Set the motor speed, stopping after SECS or on Ctrl-C
+  velocity M/S [--for SECS]       Set the wheel speed, stopping after SECS or on Ctrl-C
+  watch [--period MS] [--count N] Print telemetry as it changes, until Ctrl-C
+  dump FILE                       Save the drive's parameters, - for stdout
+  load FILE [--persist]           Write parameters, --persist saves them to flash
+  diff FILE                       Compare the drive's parameters with a file
+  clear-alarm                     Clear the drive's alarm by turning the output off,
+                                  and back on if it was on
+
+Registers go by name, e.g. MotorAcceleration, or address, e.g. 0x03.
+Profiles are .toml or .json.";
+
+pub const PORT_VARIABLE: &str = "HAPPY_MOTOR_PORT";
+
+#[derive(Debug, Error)]
+pub enum BenchError {
+    #[error("{0}\n\n{USAGE}")]
+    Usage(String),
+    #[error("No port given, use --port or set {PORT_VARIABLE}")]
+    NoPort,
+    #[error(transparent)]
+    Controller(#[from] MotorControllerError),
+    #[error(transparent)]
+    Config(#[from] ConfigError),
+    #[error(transparent)]
+    Profile(#[from] ProfileError),
+    #[error("Couldn't list serial ports! {0}")]
+    SerialError(#[from] serialport::Error),
+    #[error("Couldn't write output! {0}")]
+    IOError(#[from] std::io::Error),
+    #[error("Couldn't catch Ctrl-C! {0}")]
+    CtrlC(#[from] ctrlc::Error),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    Ports,
+    Scan {
+        baud_rates: Vec<u32>,
+        addresses: RangeInclusive<u8>,
+    },
+    // None for all of them
+    Read(Option<ModbusRegister>),
+    Write(ModbusRegister, u16),
+    Enable,
+    Disable,
+    Rpm {
+        rpm: f32,
+        hold: Option<Duration>,
+    },
+    Velocity {
+        speed: f32,
+        hold: Option<Duration>,
+    },
+    Watch {
+        period: Duration,
+        count: Option<u64>,
+    },
+    Dump(PathBuf),
+    Load {
+        path: PathBuf,
+        persist: bool,
+    },
+    Diff(PathBuf),
+    ClearAlarm,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Invocation {
+    pub port: Option<String>,
+    pub address: u8,
+    pub config: MotorConfig,
+    pub command: Command,
+}
+
+fn usage(message: impl Into<String>) -> BenchError {
+    BenchError::Usage(message.into())
+}
+
+fn number<T: std::str::FromStr>(what: &str, text: &str) -> Result<T, BenchError> {
+    let parsed = match text.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16)
+            .ok()
+            .and_then(|value| value.to_string().parse().ok()),
+        None => text.parse().ok(),
+    };
+
+    parsed.ok_or_else(|| usage(format!("Bad {}: {}", what, text)))
+}
+
+fn seconds(text: &str) -> Result<Duration, BenchError> {
+    Duration::try_from_secs_f32(number("time", text)?)
+        .map_err(|_| usage(format!("Bad time: {}", text)))
+}
+
+// By name or by address
+pub fn parse_register(text: &str) -> Result<ModbusRegister, BenchError> {
+    text.parse()
+        .ok()
+        .or_else(|| ModbusRegister::try_from(number::<u16>("register", text).ok()?).ok())
+        .ok_or_else(|| usage(format!("No register {}", text)))
+}
+
+// Signed registers take negative values too
+fn register_value(register: ModbusRegister, text: &str) -> Result<u16, BenchError> {
+    let value: i32 = number("value", text)?;
+    let lowest = if register.info().signed {
+        i16::MIN as i32
+    } else {
+        0
+    };
+    if !(lowest..=u16::MAX as i32).contains(&value) {
+        return Err(usage(format!("{} doesn't fit in {:?}", value, register)));
+    }
+
+    Ok(value as u16)
+}
+
+// Pulls `--name VALUE` out of `args`, as many times as it's given
+fn take_flag(args: &mut Vec<String>, name: &str) -> Result<Vec<String>, BenchError> {
+    let mut values = Vec::new();
+    while let Some(index) = args.iter().position(|arg| arg == name) {
+        if index + 1 >= args.len() {
+            return Err(usage(format!("{} needs a value", name)));
+        }
+        values.push(args.remove(index + 1));
+        args.remove(index);
+    }
+
+    Ok(values)
+}
+
+fn take_one(args: &mut Vec<String>, name: &str) -> Result<Option<String>, BenchError> {
+    Ok(take_flag(args, name)?.pop())
+}
+
+fn take_switch(args: &mut Vec<String>, name: &str) -> bool {
+    let before = args.len();
+    args.retain(|arg| arg != name);
+
+    args.len() != before
+}
+
+// `args` without the program name. `default_port` is what to use if there's
+// no --port, normally from the environment
+pub fn parse_args(args: &[String], default_port: Option<String>) -> Result<Invocation, BenchError> {
+    let mut args = args.to_vec();
+
+    let port = take_one(&mut args, "--port")?.or(default_port);
+    let address = match take_one(&mut args, "--address")? {
+        Some(address) => number("address", &address)?,
+        None => 0x01,
+    };
+    let config = match take_one(&mut args, "--config")? {
+        Some(path) => MotorConfig::load(path)?,
+        None => MotorConfig::default(),
+    };
+
+    if args.is_empty() {
+        return Err(usage("No command given"));
+    }
+    let name = args.remove(0);
+
+    let command = match name.as_str() {
+        "ports" => Command::Ports,
+        "scan" => {
+            let defaults = DiscoveryOptions::default();
+            let baud_rates = take_flag(&mut args, "--baud")?
+                .iter()
+                .map(|rate| number("baud rate", rate))
+                .collect::<Result<Vec<u32>, _>>()?;
+            let addresses = match take_one(&mut args, "--addresses")? {
+                Some(range) => {
+                    let (first, last) = range.split_once('-').unwrap_or((&range, &range));
+                    number("address", first)?..=number("address", last)?
+                }
+                None => defaults.addresses,
+            };
+
+            Command::Scan {
+                baud_rates: if baud_rates.is_empty() {
+                    defaults.baud_rates
+                } else {
+                    baud_rates
+                },
+                addresses,
+            }
+        }
+        "read" => Command::Read(match args.pop() {
+            Some(register) => Some(parse_register(&register)?),
+            None => None,
+        }),
+        "write" => {
+            let [register, value] = &args[..] else {
+                return Err(usage("write takes a register and a value"));
+            };
+            let register = parse_register(register)?;
+            let value = register_value(register, value)?;
+            args.clear();
+
+            Command::Write(register, value)
+        }
+        "enable" => Command::Enable,
+        "disable" => Command::Disable,
+        "rpm" | "velocity" => {
+            let hold = take_one(&mut args, "--for")?
+                .map(|time| seconds(&time))
+                .transpose()?;
+            let Some(speed) = args.pop() else {
+                return Err(usage(format!("{} needs a speed", name)));
+            };
+            let speed: f32 = number("speed", &speed)?;
+
+            if name == "rpm" {
+                Command::Rpm { rpm: speed, hold }
+            } else {
+                Command::Velocity { speed, hold }
+            }
+        }
+        "watch" => Command::Watch {
+            period: match take_one(&mut args, "--period")? {
+                Some(ms) => Duration::from_millis(number("period", &ms)?),
+                None => Duration::from_millis(200),
+            },
+            count: take_one(&mut args, "--count")?
+                .map(|count| number("count", &count))
+                .transpose()?,
+        },
+        "dump" | "diff" | "load" => {
+            let persist = take_switch(&mut args, "--persist");
+            let Some(path) = args.pop() else {
+                return Err(usage(format!("{} needs a file", name)));
+            };
+            let path = PathBuf::from(path);
+
+            match name.as_str() {
+                "dump" => Command::Dump(path),
+                "diff" => Command::Diff(path),
+                _ => Command::Load { path, persist },
+            }
+        }
+        "clear-alarm" => Command::ClearAlarm,
+        _ => return Err(usage(format!("No command {}", name))),
+    };
+
+    if let Some(extra) = args.first() {
+        return Err(usage(format!("Didn't expect {}", extra)));
+    }
+
+    Ok(Invocation {
+        port,
+        address,
+        config,
+        command,
+    })
+}
+
+// Opens whatever the command needs and runs it
+pub fn run(invocation: &Invocation, out: &mut impl Write) -> Result<(), BenchError> {
+    match &invocation.command {
+        Command::Ports => {
+            for port in serialport::available_ports()? {
+                writeln!(out, "{}", port.port_name)?;
+            }
+
+            Ok(())
+        }
+        Command::Scan {
+            baud_rates,
+            addresses,
+        } => {
+            let options = DiscoveryOptions {
+                addresses: addresses.clone(),
+                baud_rates: baud_rates.clone(),
+                ..DiscoveryOptions::default()
+            };
+
+            match &invocation.port {
+                Some(port) => {
+                    let transport =
+                        SerialTransport::open(port, invocation.config.baud_rate, options.timeout)?;
+                    let drives = discovery::discover(transport, &options)?;
+                    print_drives(out, port, &drives)
+                }
+                None => {
+                    for report in discovery::discover_ports(&options)? {
+                        match report.drives {
+                            Ok(drives) => print_drives(out, &report.port_name, &drives)?,
+                            Err(e) => writeln!(out, "{}: {}", report.port_name, e)?,
+                        }
+                    }
+
+                    Ok(())
+                }
+            }
+        }
+        command => {
+            let port = invocation.port.as_deref().ok_or(BenchError::NoPort)?;
+            let mut controller =
+                MotorController::with_config(port, invocation.address, invocation.config)?;
+
+            // Ctrl-C ends the command instead of the program, so a spinning motor
+            // gets told to stop
+            static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+            ctrlc::set_handler(|| INTERRUPTED.store(true, Ordering::SeqCst))?;
+
+            execute(&mut controller, command, out, &INTERRUPTED)
+        }
+    }
+}
+
+fn print_drives(
+    out: &mut impl Write,
+    port: &str,
+    drives: &[DiscoveredDrive],
+) -> Result<(), BenchError> {
+    if drives.is_empty() {
+        writeln!(out, "{}: no drives", port)?;
+    }
+    for drive in drives {
+        writeln!(
+            out,
+            "{}: drive {} at {} baud, status {:?}, parameters saved {:?}",
+            port, drive.device_address, drive.baud_rate, drive.status, drive.parameters_saved
+        )?;
+    }
+
+    Ok(())
+}
+
+// Runs a command that talks to one drive. Anything that runs until it's
+// stopped finishes once `interrupted` is set
+pub fn execute(
+    controller: &mut MotorController,
+    command: &Command,
+    out: &mut impl Write,
+    interrupted: &AtomicBool,
+) -> Result<(), BenchError> {
+    match command {
+        Command::Ports | Command::Scan { .. } => {
+            return Err(usage("Scanning needs the port to itself"))
+        }
+        Command::Read(Some(register)) => {
+            let value = controller.read_register(*register)?;
+            writeln!(out, "{}", register.value(value))?;
+        }
+        Command::Read(None) => {
+            let bank = controller
+                .read_registers(ModbusRegister::EnableModbus, ModbusRegister::ALL.len())?;
+            write!(out, "{}", bank)?;
+        }
+        Command::Write(register, value) => {
+            controller.write_register(*register, *value)?;
+            let value = controller.read_register(*register)?;
+            writeln!(out, "{}", register.value(value))?;
+        }
+        Command::Enable => {
+            controller.enable_modbus()?;
+            controller.set_motor_enabled()?;
+            writeln!(out, "Motor enabled")?;
+        }
+        Command::Disable => {
+            controller.set_motor_disabled()?;
+            writeln!(out, "Motor disabled")?;
+        }
+        Command::Rpm { rpm, hold } => {
+            let speed = rpm * 10.0;
+            if !(i16::MIN as f32..=i16::MAX as f32).contains(&speed) {
+                return Err(usage(format!("{} rpm is out of range", rpm)));
+            }
+            let speed = controller.set_rpm(speed.round() as i16)?;
+            writeln!(
+                out,
+                "{}",
+                ModbusRegister::MotorTargetSpeed.value(speed as u16)
+            )?;
+            stop_after(controller, *hold, out, interrupted)?;
+        }
+        Command::Velocity { speed, hold } => {
+            let speed = controller.set_velocity(*speed)?;
+            writeln!(out, "Wheel speed {:.3} m/s", speed)?;
+            stop_after(controller, *hold, out, interrupted)?;
+        }
+        Command::Watch { period, count } => {
+            // Until Ctrl-C, without a count
+            for seen in 1.. {
+                let telemetry = controller.get_telemetry()?;
+                let position = controller.get_position()?;
+                writeln!(
+                    out,
+                    "{:?}  {:.1} rpm  {:.2} A  {:.1} V  {} °C  {:.1}% pwm  position {}",
+                    telemetry.status,
+                    telemetry.rpm as f32 / 10.0,
+                    telemetry.current,
+                    telemetry.voltage,
+                    telemetry.temperature,
+                    telemetry.pwm,
+                    position
+                )?;
+                out.flush()?;
+
+                if count.is_some_and(|count| seen >= count) || interrupted.load(Ordering::SeqCst) {
+                    break;
+                }
+                thread::sleep(*period);
+            }
+        }
+        Command::Dump(path) => {
+            let profile = controller.read_profile()?;
+            if path.as_os_str() == "-" {
+                write!(out, "{}", profile.to_toml()?)?;
+            } else {
+                profile.save(path)?;
+                writeln!(
+                    out,
+                    "Saved {} parameters to {}",
+                    profile.len(),
+                    path.display()
+                )?;
+            }
+        }
+        Command::Load { path, persist } => {
+            let profile = DriveProfile::load(path)?;
+            controller.apply_profile(&profile)?;
+            writeln!(out, "Wrote {} parameters", profile.len())?;
+
+            if *persist {
+                controller.save_parameters()?;
+                writeln!(out, "Saved to flash")?;
+            }
+        }
+        Command::Diff(path) => {
+            let differences = controller.diff_profile(&DriveProfile::load(path)?)?;
+            if differences.is_empty() {
+                writeln!(out, "Drive matches {}", path.display())?;
+            }
+            for difference in differences {
+                writeln!(
+                    out,
+                    "{:?}: drive {}, file {}",
+                    difference.register, difference.drive, difference.profile
+                )?;
+            }
+        }
+        Command::ClearAlarm => {
+            let before = controller.get_status()?;
+            let was_enabled = controller.read_register(ModbusRegister::EnableMotor)? == 1;
+
+            // Turning the output off then on again is what clears it. The old
+            // speed is dropped first so the motor doesn't pick it back up
+            controller.set_motor_disabled()?;
+            if was_enabled {
+                controller.set_rpm(0)?;
+                controller.set_motor_enabled()?;
+            }
+
+            let after = controller.get_status()?;
+            writeln!(out, "Alarm was {:?}, now {:?}", before, after)?;
+        }
+    }
+
+    Ok(())
+}
+
+// Holds the speed for `hold`, or until interrupted without one. Just returning
+// would stop it anyway, as dropping the controller does, but only after a blip
+fn stop_after(
+    controller: &mut MotorController,
+    hold: Option<Duration>,
+    out: &mut impl Write,
+    interrupted: &AtomicBool,
+) -> Result<(), BenchError> {
+    let deadline = hold.map(|hold| Instant::now() + hold);
+    if deadline.is_none() {
+        writeln!(out, "Running, Ctrl-C to stop")?;
+        out.flush()?;
+    }
+
+    // In short naps either way, so Ctrl-C cuts a hold short too
+    while !interrupted.load(Ordering::SeqCst) {
+        let nap = Duration::from_millis(50);
+        match deadline {
+            Some(deadline) => match deadline.checked_duration_since(Instant::now()) {
+                Some(left) if !left.is_zero() => thread::sleep(left.min(nap)),
+                _ => break,
+            },
+            None => thread::sleep(nap),
+        }
+    }
+
+    controller.set_rpm(0)?;
+    writeln!(out, "Stopped")?;
+
+    Ok(())
+}
@@ -0,0 +1,27 @@
+// Bench testing a drive from the command line. See `motor-bench --help`. Needs
+// the bench feature, e.g. `cargo run --features bench --bin motor-bench`
+use happy_hardware_interface::bench::{self, BenchError, PORT_VARIABLE, USAGE};
+use std::process::ExitCode;
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.iter().any(|arg| arg == "--help" || arg == "-h") {
+        println!("{}", USAGE);
+        return ExitCode::SUCCESS;
+    }
+
+    let result = bench::parse_args(&args, std::env::var(PORT_VARIABLE).ok())
+        .and_then(|invocation| bench::run(&invocation, &mut std::io::stdout()));
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e @ BenchError::Usage(_)) => {
+            eprintln!("{}", e);
+            ExitCode::from(2)
+        }
+        Err(e) => {
+            eprintln!("{}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,1288 @@
+use crate::message::{ModbusRegister, ModbusResponseError};
+use crate::motor_controller::bus::MotorBus;
+use crate::motor_controller::config::{ConfigError, MotorConfig};
+use crate::motor_controller::diff_drive::{
+    DiffDriveBase, DiffDriveConfig, DiffDriveError, WheelSpeeds, WheelState,
+};
+use crate::motor_controller::error::MotorControllerError;
+use crate::motor_controller::motor_status::{MotorStatus, MotorStatusFatal, MotorStatusWarning};
+use crate::motor_controller::poller::{
+    DriveSnapshot, PollRate, Poller, Reading, DEFAULT_POLL_RATES,
+};
+use crate::motor_controller::profile::{DriveProfile, ProfileError};
+use crate::motor_controller::retry::RetryStats;
+use crate::motor_controller::telemetry::Telemetry;
+use crate::motor_controller::watchdog::{WatchdogConfig, WatchdogTrip};
+use crate::motor_controller::MotorController;
+use std::cell::RefCell;
+use std::ffi::{c_char, CStr, CString};
+use std::panic::{self, AssertUnwindSafe};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use thiserror::Error;
+
+// Public FFI Shims
+// Nothing here is allowed to panic or unwind into the C++ side. Every call
+// returns a status, with any values coming back through out-parameters, and on
+// failure `motor_controller_last_error` says what went wrong.
+
+pub const DEFAULT_DEVICE_ADDRESS: u8 = 0x1;
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MotorControllerStatus {
+    Ok = 0,
+    // A pointer argument was null
+    NullPointer = 1,
+    // Not a valid path, address, ...
+    InvalidArgument = 2,
+    // The serial port couldn't be opened or configured
+    SerialError = 3,
+    // Reading or writing the port failed
+    IoError = 4,
+    // The drive didn't answer in time
+    Timeout = 5,
+    // The drive answered, but not with anything that made sense
+    BadResponse = 6,
+    // The drive understood and said no
+    ModbusException = 7,
+    // A bug on our side. The controller may be in a bad state
+    Panic = 8,
+}
+
+// `MotorStatus` flattened for C, numbered the same as the drive's alarm codes
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MotorAlarm {
+    None = 0x00,
+    HighTemperature = 0x10,
+    Overheat = 0x11,
+    SystemStall = 0x12,
+    UnderVoltage = 0x13,
+    LoadTooHeavy = 0x14,
+    FlashWriteFailed = 0x20,
+}
+
+impl From<MotorStatus> for MotorAlarm {
+    fn from(value: MotorStatus) -> Self {
+        match value {
+            MotorStatus::None => Self::None,
+            MotorStatus::Warning(MotorStatusWarning::HighTemperature) => Self::HighTemperature,
+            MotorStatus::Warning(MotorStatusWarning::FlashWriteFailed) => Self::FlashWriteFailed,
+            MotorStatus::Fatal(MotorStatusFatal::Overheat) => Self::Overheat,
+            MotorStatus::Fatal(MotorStatusFatal::SystemStall) => Self::SystemStall,
+            MotorStatus::Fatal(MotorStatusFatal::UnderVoltage) => Self::UnderVoltage,
+            MotorStatus::Fatal(MotorStatusFatal::LoadTooHeavy) => Self::LoadTooHeavy,
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+enum FfiError {
+    #[error("{0} is null")]
+    NullPointer(&'static str),
+    #[error("{0}")]
+    InvalidArgument(String),
+    #[error(transparent)]
+    Controller(#[from] MotorControllerError),
+    #[error(transparent)]
+    DiffDrive(#[from] DiffDriveError),
+    #[error(transparent)]
+    Config(#[from] ConfigError),
+    #[error(transparent)]
+    Profile(#[from] ProfileError),
+}
+
+impl From<&MotorControllerError> for MotorControllerStatus {
+    fn from(value: &MotorControllerError) -> Self {
+        match value {
+            MotorControllerError::SerialError(_) => Self::SerialError,
+            MotorControllerError::IOError(_) => Self::IoError,
+            MotorControllerError::ResponseError(ModbusResponseError::IOError(e))
+                if e.kind() == std::io::ErrorKind::TimedOut =>
+            {
+                Self::Timeout
+            }
+            MotorControllerError::FlashWriteTimedOut(_) => Self::Timeout,
+            MotorControllerError::ResponseError(ModbusResponseError::IOError(_)) => Self::IoError,
+            MotorControllerError::InvalidDeviceAddress(_)
+            | MotorControllerError::InvalidRegisterRange(..)
+            | MotorControllerError::InvalidWrite(_) => Self::InvalidArgument,
+            MotorControllerError::InvalidConfig(e) => e.into(),
+            MotorControllerError::ModbusException(..) => Self::ModbusException,
+            _ => Self::BadResponse,
+        }
+    }
+}
+
+impl From<&FfiError> for MotorControllerStatus {
+    fn from(value: &FfiError) -> Self {
+        match value {
+            FfiError::NullPointer(_) => Self::NullPointer,
+            FfiError::InvalidArgument(_) => Self::InvalidArgument,
+            FfiError::Controller(e) => e.into(),
+            FfiError::DiffDrive(e) => e.into(),
+            FfiError::Config(e) => e.into(),
+            FfiError::Profile(e) => e.into(),
+        }
+    }
+}
+
+impl From<&DiffDriveError> for MotorControllerStatus {
+    fn from(value: &DiffDriveError) -> Self {
+        match value {
+            DiffDriveError::Left(e) | DiffDriveError::Right(e) => e.into(),
+            DiffDriveError::InvalidConfig(e) => e.into(),
+        }
+    }
+}
+
+impl From<&ConfigError> for MotorControllerStatus {
+    fn from(value: &ConfigError) -> Self {
+        match value {
+            ConfigError::IOError(_) => Self::IoError,
+            _ => Self::InvalidArgument,
+        }
+    }
+}
+
+impl From<&ProfileError> for MotorControllerStatus {
+    fn from(value: &ProfileError) -> Self {
+        match value {
+            ProfileError::IOError(_) => Self::IoError,
+            _ => Self::InvalidArgument,
+        }
+    }
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn set_last_error(message: String) {
+    // A message with a NUL in it would be cut short anyway
+    let message = CString::new(message.replace('\0', " ")).unwrap_or_default();
+    LAST_ERROR.with(|last_error| *last_error.borrow_mut() = Some(message));
+}
+
+// Run `f`, turning errors and panics alike into a status
+fn ffi_call(f: impl FnOnce() -> Result<(), FfiError>) -> MotorControllerStatus {
+    match panic::catch_unwind(AssertUnwindSafe(f)) {
+        Ok(Ok(())) => MotorControllerStatus::Ok,
+        Ok(Err(e)) => {
+            set_last_error(e.to_string());
+            (&e).into()
+        }
+        Err(panic) => {
+            let message = panic
+                .downcast_ref::<&str>()
+                .map(|s| s.to_string())
+                .or_else(|| panic.downcast_ref::<String>().cloned())
+                .unwrap_or_else(|| "unknown panic".to_string());
+            set_last_error(format!("Panicked: {}", message));
+            MotorControllerStatus::Panic
+        }
+    }
+}
+
+unsafe fn controller<'a>(ptr: *mut MotorController) -> Result<&'a mut MotorController, FfiError> {
+    unsafe { ptr.as_mut() }.ok_or(FfiError::NullPointer("motor controller"))
+}
+
+fn register(register: u16) -> Result<ModbusRegister, FfiError> {
+    register
+        .try_into()
+        .map_err(|_| FfiError::InvalidArgument(format!("No register {:#06x}", register)))
+}
+
+unsafe fn base<'a>(ptr: *mut DiffDriveBase) -> Result<&'a mut DiffDriveBase, FfiError> {
+    unsafe { ptr.as_mut() }.ok_or(FfiError::NullPointer("diff drive base"))
+}
+
+unsafe fn c_str<'a>(ptr: *const c_char, name: &'static str) -> Result<&'a str, FfiError> {
+    if ptr.is_null() {
+        return Err(FfiError::NullPointer(name));
+    }
+
+    unsafe { CStr::from_ptr(ptr) }
+        .to_str()
+        .map_err(|e| FfiError::InvalidArgument(format!("{} is not UTF-8: {}", name, e)))
+}
+
+unsafe fn motor_config<'a>(ptr: *mut MotorConfig) -> Result<&'a mut MotorConfig, FfiError> {
+    unsafe { ptr.as_mut() }.ok_or(FfiError::NullPointer("config"))
+}
+
+unsafe fn out<'a, T>(ptr: *mut T, name: &'static str) -> Result<&'a mut T, FfiError> {
+    unsafe { ptr.as_mut() }.ok_or(FfiError::NullPointer(name))
+}
+
+/// The message for the last call on this thread that didn't return `Ok`, or null if there
+/// hasn't been one.
+///
+/// # Safety
+/// The string belongs to the library and is only valid until the next failing call on the same
+/// thread. Copy it if it needs to live longer.
+#[no_mangle]
+pub unsafe extern "C" fn motor_controller_last_error() -> *const c_char {
+    LAST_ERROR.with(|last_error| {
+        last_error
+            .borrow()
+            .as_ref()
+            .map_or(std::ptr::null(), |message| message.as_ptr())
+    })
+}
+
+/// # Safety
+/// `port_path` must be a valid, NUL-terminated C string. `out` must point to somewhere a
+/// controller pointer can be written, which is only done on success.
+#[no_mangle]
+pub unsafe extern "C" fn motor_controller_new(
+    port_path: *const c_char,
+    device_address: u8,
+    out_controller: *mut *mut MotorController,
+) -> MotorControllerStatus {
+    ffi_call(|| {
+        let port_path = unsafe { c_str(port_path, "port_path") }?;
+        let out_controller = unsafe { out(out_controller, "out_controller") }?;
+
+        let mc = MotorController::new(port_path, device_address)?;
+        *out_controller = Box::into_raw(Box::new(mc));
+
+        Ok(())
+    })
+}
+
+/// Like `motor_controller_new`, for a robot that doesn't match the defaults. `config` may be null
+/// for the defaults.
+///
+/// # Safety
+/// `port_path` must be a valid, NUL-terminated C string. `config` must be null or point to a
+/// `MotorConfig`. `out_controller` must point to somewhere a controller pointer can be written,
+/// which is only done on success.
+#[no_mangle]
+pub unsafe extern "C" fn motor_controller_new_with_config(
+    port_path: *const c_char,
+    device_address: u8,
+    config: *const MotorConfig,
+    out_controller: *mut *mut MotorController,
+) -> MotorControllerStatus {
+    ffi_call(|| {
+        let port_path = unsafe { c_str(port_path, "port_path") }?;
+        let config = unsafe { config.as_ref() }.copied().unwrap_or_default();
+        let out_controller = unsafe { out(out_controller, "out_controller") }?;
+
+        let mc = MotorController::with_config(port_path, device_address, config)?;
+        *out_controller = Box::into_raw(Box::new(mc));
+
+        Ok(())
+    })
+}
+
+/// The geometry this library was built for, to start from when only some settings need changing.
+#[no_mangle]
+pub extern "C" fn motor_config_default() -> MotorConfig {
+    MotorConfig::default()
+}
+
+/// Reads a `.toml`, `.yaml` or `.yml` file. Anything the file leaves out keeps its default.
+///
+/// # Safety
+/// `path` must be a valid, NUL-terminated C string. `out_config` must be valid to write a
+/// `MotorConfig` to, which is only done on success.
+#[no_mangle]
+pub unsafe extern "C" fn motor_config_load(
+    path: *const c_char,
+    out_config: *mut MotorConfig,
+) -> MotorControllerStatus {
+    ffi_call(|| {
+        let path = unsafe { c_str(path, "path") }?;
+        let out_config = unsafe { out(out_config, "out_config") }?;
+
+        *out_config = MotorConfig::load(path)?;
+
+        Ok(())
+    })
+}
+
+/// Sets one setting from its name and value as text, e.g. a ros2_control hardware parameter.
+/// The config is left alone if the name or value is no good.
+///
+/// # Safety
+/// `config` must point to a `MotorConfig`. `key` and `value` must be valid, NUL-terminated C
+/// strings.
+#[no_mangle]
+pub unsafe extern "C" fn motor_config_set(
+    config: *mut MotorConfig,
+    key: *const c_char,
+    value: *const c_char,
+) -> MotorControllerStatus {
+    ffi_call(|| {
+        let config = unsafe { motor_config(config) }?;
+        let key = unsafe { c_str(key, "key") }?;
+        let value = unsafe { c_str(value, "value") }?;
+
+        Ok(config.set(key, value)?)
+    })
+}
+
+/// Checks the settings make sense together. Controllers check this themselves, this is for
+/// finding out early.
+///
+/// # Safety
+/// `config` must point to a `MotorConfig`.
+#[no_mangle]
+pub unsafe extern "C" fn motor_config_validate(
+    config: *const MotorConfig,
+) -> MotorControllerStatus {
+    ffi_call(|| Ok(unsafe { motor_config(config.cast_mut()) }?.validate()?))
+}
+
+/// A motor that was last told to move is stopped first, following the watchdog's ramp if there
+/// is one.
+///
+/// # Safety
+/// `ptr` must be null or a pointer returned by `motor_controller_new`. It must not be used again afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn motor_controller_free(ptr: *mut MotorController) {
+    if ptr.is_null() {
+        return;
+    }
+
+    // Nothing to report to, but still mustn't unwind
+    let _ = panic::catch_unwind(AssertUnwindSafe(|| drop(unsafe { Box::from_raw(ptr) })));
+}
+
+/// # Safety
+/// `ptr` must be a pointer returned by `motor_controller_new` that has not yet been freed.
+#[no_mangle]
+pub unsafe extern "C" fn motor_controller_enable_modbus(
+    ptr: *mut MotorController,
+) -> MotorControllerStatus {
+    ffi_call(|| Ok(unsafe { controller(ptr) }?.enable_modbus()?))
+}
+
+/// # Safety
+/// `ptr` must be a pointer returned by `motor_controller_new` that has not yet been freed.
+#[no_mangle]
+pub unsafe extern "C" fn motor_controller_set_motor_enabled(
+    ptr: *mut MotorController,
+) -> MotorControllerStatus {
+    ffi_call(|| Ok(unsafe { controller(ptr) }?.set_motor_enabled()?))
+}
+
+/// # Safety
+/// `ptr` must be a pointer returned by `motor_controller_new` that has not yet been freed.
+#[no_mangle]
+pub unsafe extern "C" fn motor_controller_set_motor_disabled(
+    ptr: *mut MotorController,
+) -> MotorControllerStatus {
+    ffi_call(|| Ok(unsafe { controller(ptr) }?.set_motor_disabled()?))
+}
+
+/// # Safety
+/// `ptr` must be a pointer returned by `motor_controller_new` that has not yet been freed.
+/// `out_position` must be valid to write an `int32_t` to.
+#[no_mangle]
+pub unsafe extern "C" fn motor_controller_get_position(
+    ptr: *mut MotorController,
+    out_position: *mut i32,
+) -> MotorControllerStatus {
+    ffi_call(|| {
+        let motor_controller = unsafe { controller(ptr) }?;
+        let out_position = unsafe { out(out_position, "out_position") }?;
+
+        *out_position = motor_controller.get_position()?;
+
+        Ok(())
+    })
+}
+
+/// # Safety
+/// `ptr` must be a pointer returned by `motor_controller_new` that has not yet been freed.
+/// `out_velocity` must be valid to write a `float` to.
+#[no_mangle]
+pub unsafe extern "C" fn motor_controller_get_velocity(
+    ptr: *mut MotorController,
+    out_velocity: *mut f32,
+) -> MotorControllerStatus {
+    ffi_call(|| {
+        let motor_controller = unsafe { controller(ptr) }?;
+        let out_velocity = unsafe { out(out_velocity, "out_velocity") }?;
+
+        *out_velocity = motor_controller.get_velocity()?;
+
+        Ok(())
+    })
+}
+
+/// The speed the drive settled on is written to `out_velocity`, unless it is null.
+///
+/// # Safety
+/// `ptr` must be a pointer returned by `motor_controller_new` that has not yet been freed.
+/// `out_velocity` must be null or valid to write a `float` to.
+#[no_mangle]
+pub unsafe extern "C" fn motor_controller_set_velocity(
+    ptr: *mut MotorController,
+    speed: f32,
+    out_velocity: *mut f32,
+) -> MotorControllerStatus {
+    ffi_call(|| {
+        let motor_controller = unsafe { controller(ptr) }?;
+
+        let velocity = motor_controller.set_velocity(speed)?;
+        if let Some(out_velocity) = unsafe { out_velocity.as_mut() } {
+            *out_velocity = velocity;
+        }
+
+        Ok(())
+    })
+}
+
+/// # Safety
+/// `ptr` must be a pointer returned by `motor_controller_new` that has not yet been freed.
+#[no_mangle]
+pub unsafe extern "C" fn motor_controller_set_position_feedforward(
+    ptr: *mut MotorController,
+    ff: i16,
+) -> MotorControllerStatus {
+    ffi_call(|| Ok(unsafe { controller(ptr) }?.set_position_feedforward(ff)?))
+}
+
+/// # Safety
+/// `ptr` must be a pointer returned by `motor_controller_new` that has not yet been freed.
+#[no_mangle]
+pub unsafe extern "C" fn motor_controller_set_position_gain(
+    ptr: *mut MotorController,
+    gain: i16,
+) -> MotorControllerStatus {
+    ffi_call(|| Ok(unsafe { controller(ptr) }?.set_position_gain(gain)?))
+}
+
+/// Whether the drive stops for this alarm, rather than just warning about it.
+#[no_mangle]
+pub extern "C" fn motor_alarm_is_fatal(alarm: MotorAlarm) -> bool {
+    matches!(
+        alarm,
+        MotorAlarm::Overheat
+            | MotorAlarm::SystemStall
+            | MotorAlarm::UnderVoltage
+            | MotorAlarm::LoadTooHeavy
+    )
+}
+
+/// # Safety
+/// `ptr` must be a pointer returned by `motor_controller_new` that has not yet been freed.
+/// `out_alarm` must be valid to write a `MotorAlarm` to.
+#[no_mangle]
+pub unsafe extern "C" fn motor_controller_get_status(
+    ptr: *mut MotorController,
+    out_alarm: *mut MotorAlarm,
+) -> MotorControllerStatus {
+    ffi_call(|| {
+        let motor_controller = unsafe { controller(ptr) }?;
+        let out_alarm = unsafe { out(out_alarm, "out_alarm") }?;
+
+        *out_alarm = motor_controller.get_status()?.into();
+
+        Ok(())
+    })
+}
+
+/// # Safety
+/// `ptr` must be a pointer returned by `motor_controller_new` that has not yet been freed.
+/// `out_rpm` must be valid to write an `int16_t` to.
+#[no_mangle]
+pub unsafe extern "C" fn motor_controller_get_rpm(
+    ptr: *mut MotorController,
+    out_rpm: *mut i16,
+) -> MotorControllerStatus {
+    ffi_call(|| {
+        let motor_controller = unsafe { controller(ptr) }?;
+        let out_rpm = unsafe { out(out_rpm, "out_rpm") }?;
+
+        *out_rpm = motor_controller.get_rpm()?;
+
+        Ok(())
+    })
+}
+
+/// The speed the drive settled on is written to `out_rpm`, unless it is null.
+///
+/// # Safety
+/// `ptr` must be a pointer returned by `motor_controller_new` that has not yet been freed.
+/// `out_rpm` must be null or valid to write an `int16_t` to.
+#[no_mangle]
+pub unsafe extern "C" fn motor_controller_set_rpm(
+    ptr: *mut MotorController,
+    rpm: i16,
+    out_rpm: *mut i16,
+) -> MotorControllerStatus {
+    ffi_call(|| {
+        let motor_controller = unsafe { controller(ptr) }?;
+
+        let rpm = motor_controller.set_rpm(rpm)?;
+        if let Some(out_rpm) = unsafe { out_rpm.as_mut() } {
+            *out_rpm = rpm;
+        }
+
+        Ok(())
+    })
+}
+
+/// Reads any register by number. Numbers the drive doesn't have give `INVALID_ARGUMENT`
+/// without anything being sent.
+///
+/// # Safety
+/// `ptr` must be a pointer returned by `motor_controller_new` that has not yet been freed.
+/// `out_value` must be valid to write a `uint16_t` to.
+#[no_mangle]
+pub unsafe extern "C" fn motor_controller_read_register(
+    ptr: *mut MotorController,
+    register_number: u16,
+    out_value: *mut u16,
+) -> MotorControllerStatus {
+    ffi_call(|| {
+        let motor_controller = unsafe { controller(ptr) }?;
+        let out_value = unsafe { out(out_value, "out_value") }?;
+
+        *out_value = motor_controller.read_register(register(register_number)?)?;
+
+        Ok(())
+    })
+}
+
+/// `count` registers in a row from `first_register`, in one transaction. They all have to be
+/// registers the drive has, or it's `INVALID_ARGUMENT` without anything being sent. Nothing is
+/// written to `out_values` unless the read succeeds.
+///
+/// # Safety
+/// `ptr` must be a pointer returned by `motor_controller_new` that has not yet been freed.
+/// `out_values` must be valid to write `count` `uint16_t`s to.
+#[no_mangle]
+pub unsafe extern "C" fn motor_controller_read_registers(
+    ptr: *mut MotorController,
+    first_register: u16,
+    count: u16,
+    out_values: *mut u16,
+) -> MotorControllerStatus {
+    ffi_call(|| {
+        let motor_controller = unsafe { controller(ptr) }?;
+        if out_values.is_null() {
+            return Err(FfiError::NullPointer("out_values"));
+        }
+
+        let registers =
+            motor_controller.read_registers(register(first_register)?, count as usize)?;
+        let out_values = unsafe { std::slice::from_raw_parts_mut(out_values, registers.len()) };
+        out_values.copy_from_slice(registers.values());
+
+        Ok(())
+    })
+}
+
+/// Writes any register by number. Numbers the drive doesn't have give `INVALID_ARGUMENT`
+/// without anything being sent.
+///
+/// # Safety
+/// `ptr` must be a pointer returned by `motor_controller_new` that has not yet been freed.
+#[no_mangle]
+pub unsafe extern "C" fn motor_controller_write_register(
+    ptr: *mut MotorController,
+    register_number: u16,
+    value: u16,
+) -> MotorControllerStatus {
+    ffi_call(|| {
+        let motor_controller = unsafe { controller(ptr) }?;
+
+        Ok(motor_controller.write_register(register(register_number)?, value)?)
+    })
+}
+
+// `Telemetry` for C. `timestamp` is wall clock time in seconds since the Unix
+// epoch, the same clock ROS stamps messages with
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MotorTelemetry {
+    pub alarm: MotorAlarm,
+    pub current: f32,
+    pub rpm: i16,
+    pub voltage: f32,
+    pub temperature: u16,
+    pub pwm: f32,
+    pub timestamp: f64,
+}
+
+impl From<&Telemetry> for MotorTelemetry {
+    fn from(telemetry: &Telemetry) -> Self {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+
+        MotorTelemetry {
+            alarm: telemetry.status.into(),
+            current: telemetry.current,
+            rpm: telemetry.rpm,
+            voltage: telemetry.voltage,
+            temperature: telemetry.temperature,
+            pwm: telemetry.pwm,
+            timestamp: now
+                .saturating_sub(telemetry.taken_at.elapsed())
+                .as_secs_f64(),
+        }
+    }
+}
+
+/// Alarm, current, speed, voltage, temperature and PWM in a single read, which is quicker than
+/// asking for them one at a time.
+///
+/// # Safety
+/// `ptr` must be a pointer returned by `motor_controller_new` that has not yet been freed.
+/// `out_telemetry` must be valid to write a `MotorTelemetry` to.
+#[no_mangle]
+pub unsafe extern "C" fn motor_controller_get_telemetry(
+    ptr: *mut MotorController,
+    out_telemetry: *mut MotorTelemetry,
+) -> MotorControllerStatus {
+    ffi_call(|| {
+        let motor_controller = unsafe { controller(ptr) }?;
+        let out_telemetry = unsafe { out(out_telemetry, "out_telemetry") }?;
+
+        *out_telemetry = (&motor_controller.get_telemetry()?).into();
+
+        Ok(())
+    })
+}
+
+/// Motor current, in amps.
+///
+/// # Safety
+/// `ptr` must be a pointer returned by `motor_controller_new` that has not yet been freed.
+/// `out_current` must be valid to write a `float` to.
+#[no_mangle]
+pub unsafe extern "C" fn motor_controller_get_current(
+    ptr: *mut MotorController,
+    out_current: *mut f32,
+) -> MotorControllerStatus {
+    ffi_call(|| {
+        let motor_controller = unsafe { controller(ptr) }?;
+        let out_current = unsafe { out(out_current, "out_current") }?;
+
+        *out_current = motor_controller.get_current()?;
+
+        Ok(())
+    })
+}
+
+/// Supply voltage, in volts.
+///
+/// # Safety
+/// `ptr` must be a pointer returned by `motor_controller_new` that has not yet been freed.
+/// `out_voltage` must be valid to write a `float` to.
+#[no_mangle]
+pub unsafe extern "C" fn motor_controller_get_voltage(
+    ptr: *mut MotorController,
+    out_voltage: *mut f32,
+) -> MotorControllerStatus {
+    ffi_call(|| {
+        let motor_controller = unsafe { controller(ptr) }?;
+        let out_voltage = unsafe { out(out_voltage, "out_voltage") }?;
+
+        *out_voltage = motor_controller.get_voltage()?;
+
+        Ok(())
+    })
+}
+
+/// Drive temperature, in degrees C.
+///
+/// # Safety
+/// `ptr` must be a pointer returned by `motor_controller_new` that has not yet been freed.
+/// `out_temperature` must be valid to write a `uint16_t` to.
+#[no_mangle]
+pub unsafe extern "C" fn motor_controller_get_temperature(
+    ptr: *mut MotorController,
+    out_temperature: *mut u16,
+) -> MotorControllerStatus {
+    ffi_call(|| {
+        let motor_controller = unsafe { controller(ptr) }?;
+        let out_temperature = unsafe { out(out_temperature, "out_temperature") }?;
+
+        *out_temperature = motor_controller.get_temperature()?;
+
+        Ok(())
+    })
+}
+
+/// Output duty cycle, from -100 to 100 percent.
+///
+/// # Safety
+/// `ptr` must be a pointer returned by `motor_controller_new` that has not yet been freed.
+/// `out_pwm` must be valid to write a `float` to.
+#[no_mangle]
+pub unsafe extern "C" fn motor_controller_get_output_pwm(
+    ptr: *mut MotorController,
+    out_pwm: *mut f32,
+) -> MotorControllerStatus {
+    ffi_call(|| {
+        let motor_controller = unsafe { controller(ptr) }?;
+        let out_pwm = unsafe { out(out_pwm, "out_pwm") }?;
+
+        *out_pwm = motor_controller.get_output_pwm()?;
+
+        Ok(())
+    })
+}
+
+/// # Safety
+/// `ptr` must be a pointer returned by `motor_controller_new` that has not yet been freed.
+/// `out_stats` must be valid to write a `RetryStats` to.
+#[no_mangle]
+pub unsafe extern "C" fn motor_controller_get_retry_stats(
+    ptr: *mut MotorController,
+    out_stats: *mut RetryStats,
+) -> MotorControllerStatus {
+    ffi_call(|| {
+        let motor_controller = unsafe { controller(ptr) }?;
+        let out_stats = unsafe { out(out_stats, "out_stats") }?;
+
+        *out_stats = motor_controller.retry_stats();
+
+        Ok(())
+    })
+}
+
+/// A starting point for `motor_controller_set_watchdog`.
+#[no_mangle]
+pub extern "C" fn watchdog_config_default() -> WatchdogConfig {
+    WatchdogConfig::default()
+}
+
+/// Stops the motor if no speed is set for `config->timeout_ms`. Null turns the watchdog off.
+/// Freeing the controller always stops a moving motor, watchdog or not.
+///
+/// # Safety
+/// `ptr` must be a pointer returned by `motor_controller_new` that has not yet been freed.
+/// `config` must be null or point to a `WatchdogConfig`.
+#[no_mangle]
+pub unsafe extern "C" fn motor_controller_set_watchdog(
+    ptr: *mut MotorController,
+    config: *const WatchdogConfig,
+) -> MotorControllerStatus {
+    ffi_call(|| {
+        let motor_controller = unsafe { controller(ptr) }?;
+
+        motor_controller.set_watchdog(unsafe { config.as_ref() }.copied());
+
+        Ok(())
+    })
+}
+
+/// Takes the oldest watchdog trip not yet collected. `out_tripped` says whether there was one,
+/// and `out_trip` is only written if so.
+///
+/// # Safety
+/// `ptr` must be a pointer returned by `motor_controller_new` that has not yet been freed.
+/// `out_trip` must be valid to write a `WatchdogTrip` to, and `out_tripped` a `bool`.
+#[no_mangle]
+pub unsafe extern "C" fn motor_controller_take_watchdog_trip(
+    ptr: *mut MotorController,
+    out_trip: *mut WatchdogTrip,
+    out_tripped: *mut bool,
+) -> MotorControllerStatus {
+    ffi_call(|| {
+        let motor_controller = unsafe { controller(ptr) }?;
+        let out_trip = unsafe { out(out_trip, "out_trip") }?;
+        let out_tripped = unsafe { out(out_tripped, "out_tripped") }?;
+
+        let trip = motor_controller.take_watchdog_trip();
+        *out_tripped = trip.is_some();
+        if let Some(trip) = trip {
+            *out_trip = trip;
+        }
+
+        Ok(())
+    })
+}
+
+/// Reads every register the drive saves to flash and writes them to a `.toml` or `.json` file,
+/// to be put back later with `motor_controller_load_profile`.
+///
+/// # Safety
+/// `ptr` must be a pointer returned by `motor_controller_new` that has not yet been freed.
+/// `path` must be a valid, NUL-terminated C string.
+#[no_mangle]
+pub unsafe extern "C" fn motor_controller_save_profile(
+    ptr: *mut MotorController,
+    path: *const c_char,
+) -> MotorControllerStatus {
+    ffi_call(|| {
+        let motor_controller = unsafe { controller(ptr) }?;
+        let path = unsafe { c_str(path, "path") }?;
+
+        motor_controller.read_profile()?.save(path)?;
+
+        Ok(())
+    })
+}
+
+/// Writes a profile file to the drive and reads it back to check it took. With `persist` it's
+/// then saved to the drive's flash, failing if the drive says the flash write didn't work.
+///
+/// # Safety
+/// `ptr` must be a pointer returned by `motor_controller_new` that has not yet been freed.
+/// `path` must be a valid, NUL-terminated C string.
+#[no_mangle]
+pub unsafe extern "C" fn motor_controller_load_profile(
+    ptr: *mut MotorController,
+    path: *const c_char,
+    persist: bool,
+) -> MotorControllerStatus {
+    ffi_call(|| {
+        let motor_controller = unsafe { controller(ptr) }?;
+        let path = unsafe { c_str(path, "path") }?;
+
+        let profile = DriveProfile::load(path)?;
+        motor_controller.apply_profile(&profile)?;
+        if persist {
+            motor_controller.save_parameters()?;
+        }
+
+        Ok(())
+    })
+}
+
+/// The defaults for the robot, to start from when only some settings need changing.
+#[no_mangle]
+pub extern "C" fn diff_drive_config_default() -> DiffDriveConfig {
+    DiffDriveConfig::default()
+}
+
+/// `motor_config_set`, plus `left_polarity` and `right_polarity` (`forward` or `reversed`), and
+/// the limits as `wheel_`, `linear_` or `angular_` followed by `acceleration`, `deceleration`,
+/// `jerk` or `emergency_deceleration`.
+///
+/// # Safety
+/// `config` must point to a `DiffDriveConfig`. `key` and `value` must be valid, NUL-terminated C
+/// strings.
+#[no_mangle]
+pub unsafe extern "C" fn diff_drive_config_set(
+    config: *mut DiffDriveConfig,
+    key: *const c_char,
+    value: *const c_char,
+) -> MotorControllerStatus {
+    ffi_call(|| {
+        let config = unsafe { out(config, "config") }?;
+        let key = unsafe { c_str(key, "key") }?;
+        let value = unsafe { c_str(value, "value") }?;
+
+        Ok(config.set(key, value)?)
+    })
+}
+
+/// Opens both drives. `config` may be null for the defaults.
+///
+/// # Safety
+/// `left_port` and `right_port` must be valid, NUL-terminated C strings. `config` must be null or
+/// point to a `DiffDriveConfig`. `out_base` must point to somewhere a base pointer can be
+/// written, which is only done on success.
+#[no_mangle]
+pub unsafe extern "C" fn diff_drive_base_new(
+    left_port: *const c_char,
+    left_address: u8,
+    right_port: *const c_char,
+    right_address: u8,
+    config: *const DiffDriveConfig,
+    out_base: *mut *mut DiffDriveBase,
+) -> MotorControllerStatus {
+    ffi_call(|| {
+        let left_port = unsafe { c_str(left_port, "left_port") }?;
+        let right_port = unsafe { c_str(right_port, "right_port") }?;
+        let config = unsafe { config.as_ref() }.copied().unwrap_or_default();
+        let out_base = unsafe { out(out_base, "out_base") }?;
+
+        let base = DiffDriveBase::new(left_port, left_address, right_port, right_address, config)?;
+        *out_base = Box::into_raw(Box::new(base));
+
+        Ok(())
+    })
+}
+
+/// Stops both wheels first, as `motor_controller_free` does.
+///
+/// # Safety
+/// `ptr` must be null or a pointer returned by `diff_drive_base_new`. It must not be used again afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn diff_drive_base_free(ptr: *mut DiffDriveBase) {
+    if ptr.is_null() {
+        return;
+    }
+
+    let _ = panic::catch_unwind(AssertUnwindSafe(|| drop(unsafe { Box::from_raw(ptr) })));
+}
+
+/// Turns on Modbus control and the motors on both sides.
+///
+/// # Safety
+/// `ptr` must be a pointer returned by `diff_drive_base_new` that has not yet been freed.
+#[no_mangle]
+pub unsafe extern "C" fn diff_drive_base_enable(ptr: *mut DiffDriveBase) -> MotorControllerStatus {
+    ffi_call(|| Ok(unsafe { base(ptr) }?.enable()?))
+}
+
+/// Both sides are tried even if the first fails.
+///
+/// # Safety
+/// `ptr` must be a pointer returned by `diff_drive_base_new` that has not yet been freed.
+#[no_mangle]
+pub unsafe extern "C" fn diff_drive_base_disable(ptr: *mut DiffDriveBase) -> MotorControllerStatus {
+    ffi_call(|| Ok(unsafe { base(ptr) }?.disable()?))
+}
+
+/// # Safety
+/// `ptr` must be a pointer returned by `diff_drive_base_new` that has not yet been freed.
+#[no_mangle]
+pub unsafe extern "C" fn diff_drive_base_stop(ptr: *mut DiffDriveBase) -> MotorControllerStatus {
+    ffi_call(|| Ok(unsafe { base(ptr) }?.stop()?))
+}
+
+/// `linear` in m/s and `angular` in rad/s, anticlockwise positive. The wheel speeds the drives
+/// settled on are written to `out_speeds`, unless it is null.
+///
+/// # Safety
+/// `ptr` must be a pointer returned by `diff_drive_base_new` that has not yet been freed.
+/// `out_speeds` must be null or valid to write a `WheelSpeeds` to.
+#[no_mangle]
+pub unsafe extern "C" fn diff_drive_base_set_twist(
+    ptr: *mut DiffDriveBase,
+    linear: f32,
+    angular: f32,
+    out_speeds: *mut WheelSpeeds,
+) -> MotorControllerStatus {
+    ffi_call(|| {
+        let base = unsafe { base(ptr) }?;
+
+        let speeds = base.set_twist(linear, angular)?;
+        if let Some(out_speeds) = unsafe { out_speeds.as_mut() } {
+            *out_speeds = speeds;
+        }
+
+        Ok(())
+    })
+}
+
+/// What `diff_drive_base_tick` heads for. Nothing is sent until then.
+///
+/// # Safety
+/// `ptr` must be a pointer returned by `diff_drive_base_new` that has not yet been freed.
+#[no_mangle]
+pub unsafe extern "C" fn diff_drive_base_set_twist_target(
+    ptr: *mut DiffDriveBase,
+    linear: f32,
+    angular: f32,
+) -> MotorControllerStatus {
+    ffi_call(|| {
+        unsafe { base(ptr) }?.set_twist_target(linear, angular);
+
+        Ok(())
+    })
+}
+
+/// Call once per control loop with the seconds since the last call. Moves the twist towards its
+/// target within the config's limits and sends it as `diff_drive_base_set_twist` does.
+///
+/// # Safety
+/// `ptr` must be a pointer returned by `diff_drive_base_new` that has not yet been freed.
+/// `out_speeds` must be null or valid to write a `WheelSpeeds` to.
+#[no_mangle]
+pub unsafe extern "C" fn diff_drive_base_tick(
+    ptr: *mut DiffDriveBase,
+    period: f64,
+    out_speeds: *mut WheelSpeeds,
+) -> MotorControllerStatus {
+    ffi_call(|| {
+        let base = unsafe { base(ptr) }?;
+        let period = Duration::try_from_secs_f64(period)
+            .map_err(|e| FfiError::InvalidArgument(format!("Bad period {}: {}", period, e)))?;
+
+        let speeds = base.tick(period)?;
+        if let Some(out_speeds) = unsafe { out_speeds.as_mut() } {
+            *out_speeds = speeds;
+        }
+
+        Ok(())
+    })
+}
+
+/// Brakes at the emergency limits on the following ticks, until `diff_drive_base_resume`.
+///
+/// # Safety
+/// `ptr` must be a pointer returned by `diff_drive_base_new` that has not yet been freed.
+#[no_mangle]
+pub unsafe extern "C" fn diff_drive_base_emergency_stop(
+    ptr: *mut DiffDriveBase,
+) -> MotorControllerStatus {
+    ffi_call(|| {
+        unsafe { base(ptr) }?.emergency_stop();
+
+        Ok(())
+    })
+}
+
+/// # Safety
+/// `ptr` must be a pointer returned by `diff_drive_base_new` that has not yet been freed.
+#[no_mangle]
+pub unsafe extern "C" fn diff_drive_base_resume(ptr: *mut DiffDriveBase) -> MotorControllerStatus {
+    ffi_call(|| {
+        unsafe { base(ptr) }?.resume();
+
+        Ok(())
+    })
+}
+
+/// Reads both wheels back to back. Nothing is written unless both reads succeed.
+///
+/// # Safety
+/// `ptr` must be a pointer returned by `diff_drive_base_new` that has not yet been freed.
+/// `out_left` and `out_right` must be valid to write a `WheelState` to.
+#[no_mangle]
+pub unsafe extern "C" fn diff_drive_base_read(
+    ptr: *mut DiffDriveBase,
+    out_left: *mut WheelState,
+    out_right: *mut WheelState,
+) -> MotorControllerStatus {
+    ffi_call(|| {
+        let base = unsafe { base(ptr) }?;
+        let out_left = unsafe { out(out_left, "out_left") }?;
+        let out_right = unsafe { out(out_right, "out_right") }?;
+
+        let sample = base.sample()?;
+        *out_left = sample.left;
+        *out_right = sample.right;
+
+        Ok(())
+    })
+}
+
+/// The same watchdog on both wheels, fed by `diff_drive_base_set_twist`. Null turns it off.
+///
+/// # Safety
+/// `ptr` must be a pointer returned by `diff_drive_base_new` that has not yet been freed.
+/// `config` must be null or point to a `WatchdogConfig`.
+#[no_mangle]
+pub unsafe extern "C" fn diff_drive_base_set_watchdog(
+    ptr: *mut DiffDriveBase,
+    config: *const WatchdogConfig,
+) -> MotorControllerStatus {
+    ffi_call(|| {
+        let base = unsafe { base(ptr) }?;
+
+        base.set_watchdog(unsafe { config.as_ref() }.copied());
+
+        Ok(())
+    })
+}
+
+/// As `motor_controller_take_watchdog_trip`, for either wheel.
+///
+/// # Safety
+/// `ptr` must be a pointer returned by `diff_drive_base_new` that has not yet been freed.
+/// `out_trip` must be valid to write a `WatchdogTrip` to, and `out_tripped` a `bool`.
+#[no_mangle]
+pub unsafe extern "C" fn diff_drive_base_take_watchdog_trip(
+    ptr: *mut DiffDriveBase,
+    out_trip: *mut WatchdogTrip,
+    out_tripped: *mut bool,
+) -> MotorControllerStatus {
+    ffi_call(|| {
+        let base = unsafe { base(ptr) }?;
+        let out_trip = unsafe { out(out_trip, "out_trip") }?;
+        let out_tripped = unsafe { out(out_tripped, "out_tripped") }?;
+
+        let trip = base.take_watchdog_trip();
+        *out_tripped = trip.is_some();
+        if let Some(trip) = trip {
+            *out_trip = trip;
+        }
+
+        Ok(())
+    })
+}
+
+// `DriveSnapshot` flattened for C. Each `_age` is the seconds since that value
+// was read, or negative if it hasn't been yet, in which case the value is 0
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PolledDrive {
+    pub device_address: u8,
+    pub rpm: i16,
+    pub rpm_age: f64,
+    pub position: i32,
+    pub position_age: f64,
+    pub alarm: MotorAlarm,
+    pub alarm_age: f64,
+    pub current: f32,
+    pub current_age: f64,
+    pub voltage: f32,
+    pub voltage_age: f64,
+    pub temperature: u16,
+    pub temperature_age: f64,
+    pub failed_polls: u64,
+    pub failed_writes: u64,
+}
+
+impl From<&DriveSnapshot> for PolledDrive {
+    fn from(drive: &DriveSnapshot) -> Self {
+        fn flatten<T: Default>(reading: Option<Reading<T>>) -> (T, f64) {
+            match reading {
+                Some(reading) => {
+                    let age = reading.age().as_secs_f64();
+                    (reading.value, age)
+                }
+                None => (T::default(), -1.0),
+            }
+        }
+
+        let (rpm, rpm_age) = flatten(drive.speed);
+        let (position, position_age) = flatten(drive.position);
+        let (alarm, alarm_age) = match drive.status {
+            Some(reading) => (reading.value.into(), reading.age().as_secs_f64()),
+            None => (MotorAlarm::None, -1.0),
+        };
+        let (current, current_age) = flatten(drive.current);
+        let (voltage, voltage_age) = flatten(drive.voltage);
+        let (temperature, temperature_age) = flatten(drive.temperature);
+
+        PolledDrive {
+            device_address: drive.device_address,
+            rpm,
+            rpm_age,
+            position,
+            position_age,
+            alarm,
+            alarm_age,
+            current,
+            current_age,
+            voltage,
+            voltage_age,
+            temperature,
+            temperature_age,
+            failed_polls: drive.failed_polls,
+            failed_writes: drive.failed_writes,
+        }
+    }
+}
+
+unsafe fn poller<'a>(ptr: *mut Poller) -> Result<&'a mut Poller, FfiError> {
+    unsafe { ptr.as_mut() }.ok_or(FfiError::NullPointer("poller"))
+}
+
+/// Opens `port_path` and starts reading the drives at `addresses` in the background. `rates` may
+/// be null for the defaults: speed and position every 50ms, current every 200ms, alarm every
+/// 500ms and voltage and temperature every second. `config` may be null for the defaults.
+///
+/// # Safety
+/// `port_path` must be a valid, NUL-terminated C string. `addresses` must point to
+/// `address_count` addresses, and `rates` be null or point to `rate_count` `PollRate`s. `config`
+/// must be null or point to a `MotorConfig`. `out_poller` must point to somewhere a poller pointer
+/// can be written, which is only done on success.
+#[no_mangle]
+pub unsafe extern "C" fn motor_poller_new(
+    port_path: *const c_char,
+    config: *const MotorConfig,
+    addresses: *const u8,
+    address_count: usize,
+    rates: *const PollRate,
+    rate_count: usize,
+    out_poller: *mut *mut Poller,
+) -> MotorControllerStatus {
+    ffi_call(|| {
+        let port_path = unsafe { c_str(port_path, "port_path") }?;
+        let config = unsafe { config.as_ref() }.copied().unwrap_or_default();
+        if addresses.is_null() {
+            return Err(FfiError::NullPointer("addresses"));
+        }
+        let addresses = unsafe { std::slice::from_raw_parts(addresses, address_count) };
+        let rates = if rates.is_null() {
+            &DEFAULT_POLL_RATES[..]
+        } else {
+            unsafe { std::slice::from_raw_parts(rates, rate_count) }
+        };
+        let out_poller = unsafe { out(out_poller, "out_poller") }?;
+
+        let bus = MotorBus::with_config(port_path, config)?;
+        let poller = Poller::start(&bus, addresses, rates)?;
+        *out_poller = Box::into_raw(Box::new(poller));
+
+        Ok(())
+    })
+}
+
+/// Stops the polling thread, dropping any writes not yet sent. Moving motors are stopped, as with
+/// `motor_controller_free`.
+///
+/// # Safety
+/// `ptr` must be null or a pointer returned by `motor_poller_new`. It must not be used again afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn motor_poller_free(ptr: *mut Poller) {
+    if ptr.is_null() {
+        return;
+    }
+
+    let _ = panic::catch_unwind(AssertUnwindSafe(|| drop(unsafe { Box::from_raw(ptr) })));
+}
+
+/// The latest values for one drive. Never waits on the bus.
+///
+/// # Safety
+/// `ptr` must be a pointer returned by `motor_poller_new` that has not yet been freed.
+/// `out_drive` must be valid to write a `PolledDrive` to.
+#[no_mangle]
+pub unsafe extern "C" fn motor_poller_read(
+    ptr: *mut Poller,
+    device_address: u8,
+    out_drive: *mut PolledDrive,
+) -> MotorControllerStatus {
+    ffi_call(|| {
+        let poller = unsafe { poller(ptr) }?;
+        let out_drive = unsafe { out(out_drive, "out_drive") }?;
+
+        let drive = poller.drive(device_address).ok_or_else(|| {
+            FfiError::InvalidArgument(format!("Drive {} isn't being polled", device_address))
+        })?;
+        *out_drive = (&drive).into();
+
+        Ok(())
+    })
+}
+
+/// Queues a speed to go out ahead of the next poll, replacing any not yet sent. Failures only
+/// show up in `failed_writes`.
+///
+/// # Safety
+/// `ptr` must be a pointer returned by `motor_poller_new` that has not yet been freed.
+#[no_mangle]
+pub unsafe extern "C" fn motor_poller_set_rpm(
+    ptr: *mut Poller,
+    device_address: u8,
+    speed: i16,
+) -> MotorControllerStatus {
+    ffi_call(|| Ok(unsafe { poller(ptr) }?.set_rpm(device_address, speed)?))
+}
+
+/// As `motor_poller_set_rpm`, for any register.
+///
+/// # Safety
+/// `ptr` must be a pointer returned by `motor_poller_new` that has not yet been freed.
+#[no_mangle]
+pub unsafe extern "C" fn motor_poller_write_register(
+    ptr: *mut Poller,
+    device_address: u8,
+    register: u16,
+    value: u16,
+) -> MotorControllerStatus {
+    ffi_call(|| {
+        let poller = unsafe { poller(ptr) }?;
+
+        Ok(poller.write_register(device_address, self::register(register)?, value)?)
+    })
+}
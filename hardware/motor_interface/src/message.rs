@@ -1,9 +1,21 @@
 mod modbus_command;
+mod modbus_exception;
+mod modbus_frame_decoder;
 mod modbus_register;
 mod modbus_request;
 mod modbus_response;
+mod modbus_write_location_request;
+mod modbus_write_multiple_request;
+mod register_bank;
+mod register_info;
 
 pub use modbus_command::*;
+pub use modbus_exception::*;
+pub use modbus_frame_decoder::*;
 pub use modbus_register::*;
 pub use modbus_request::*;
 pub use modbus_response::*;
+pub use modbus_write_location_request::*;
+pub use modbus_write_multiple_request::*;
+pub use register_bank::*;
+pub use register_info::*;
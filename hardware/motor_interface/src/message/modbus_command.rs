@@ -8,6 +8,7 @@ use std::fmt::Display;
 pub enum ModbusCommand {
     ReadRegister = 0x3,
     WriteRegister = 0x6,
+    // Write multiple registers. The manual only uses it to write pulse counts
     WritePulse = 0x10,
     WriteLocation = 0x78,
     ChangeDeviceAddress = 0x7a,
@@ -31,6 +32,7 @@ impl TryFrom<u8> for ModbusCommand {
         match value {
             0x3 => Ok(Self::ReadRegister),
             0x6 => Ok(Self::WriteRegister),
+            0x10 => Ok(Self::WritePulse),
             0x78 => Ok(Self::WriteLocation),
             0x7a => Ok(Self::ChangeDeviceAddress),
             _ => Err(Self::Error {}),
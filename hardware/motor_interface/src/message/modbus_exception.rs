@@ -0,0 +1,79 @@
+use std::fmt::Display;
+
+// When a drive understands a request but won't carry it out, it answers with the
+// function code's top bit set and one of these instead of the usual reply
+// | Device Address | Command + 0x80 | Exception Code | CRC High | CRC LOW |
+// The codes are the standard Modbus ones. The manual doesn't list which the
+// drive actually uses.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ModbusException {
+    // The function code isn't one the drive knows
+    IllegalFunction,
+    // The register (or some of a block of them) doesn't exist
+    IllegalDataAddress,
+    // The value, or the shape of the request, isn't allowed
+    IllegalDataValue,
+    // The drive tried and failed
+    ServerDeviceFailure,
+    // Accepted, but it'll take a while
+    Acknowledge,
+    ServerDeviceBusy,
+    MemoryParityError,
+    GatewayPathUnavailable,
+    GatewayTargetDeviceFailedToRespond,
+    // Not a code the spec defines
+    Other(u8),
+}
+
+impl From<u8> for ModbusException {
+    fn from(value: u8) -> Self {
+        match value {
+            0x01 => Self::IllegalFunction,
+            0x02 => Self::IllegalDataAddress,
+            0x03 => Self::IllegalDataValue,
+            0x04 => Self::ServerDeviceFailure,
+            0x05 => Self::Acknowledge,
+            0x06 => Self::ServerDeviceBusy,
+            0x08 => Self::MemoryParityError,
+            0x0A => Self::GatewayPathUnavailable,
+            0x0B => Self::GatewayTargetDeviceFailedToRespond,
+            a => Self::Other(a),
+        }
+    }
+}
+
+impl From<ModbusException> for u8 {
+    fn from(value: ModbusException) -> Self {
+        match value {
+            ModbusException::IllegalFunction => 0x01,
+            ModbusException::IllegalDataAddress => 0x02,
+            ModbusException::IllegalDataValue => 0x03,
+            ModbusException::ServerDeviceFailure => 0x04,
+            ModbusException::Acknowledge => 0x05,
+            ModbusException::ServerDeviceBusy => 0x06,
+            ModbusException::MemoryParityError => 0x08,
+            ModbusException::GatewayPathUnavailable => 0x0A,
+            ModbusException::GatewayTargetDeviceFailedToRespond => 0x0B,
+            ModbusException::Other(a) => a,
+        }
+    }
+}
+
+impl Display for ModbusException {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::IllegalFunction => write!(f, "illegal function"),
+            Self::IllegalDataAddress => write!(f, "illegal data address"),
+            Self::IllegalDataValue => write!(f, "illegal data value"),
+            Self::ServerDeviceFailure => write!(f, "device failure"),
+            Self::Acknowledge => write!(f, "acknowledge"),
+            Self::ServerDeviceBusy => write!(f, "device busy"),
+            Self::MemoryParityError => write!(f, "memory parity error"),
+            Self::GatewayPathUnavailable => write!(f, "gateway path unavailable"),
+            Self::GatewayTargetDeviceFailedToRespond => {
+                write!(f, "gateway target device failed to respond")
+            }
+            Self::Other(a) => write!(f, "unknown exception {:#04x}", a),
+        }
+    }
+}
@@ -0,0 +1,115 @@
+use crate::crc::crc16;
+
+// Longest response there is: a read of 125 registers
+const MAX_FRAME_LENGTH: usize = 3 + 250 + 2;
+// No point hanging on to more than a couple of frames worth of input
+const MAX_BUFFERED: usize = 2 * MAX_FRAME_LENGTH;
+
+// Picks response frames out of whatever comes off the wire. Bytes go in with
+// `push` as they arrive, and `next_frame` hands back every complete frame with a
+// good checksum. Anything that can't be part of a frame (line noise, the tail
+// end of a reply we gave up on, ...) is thrown away and counted, so one stray
+// byte doesn't leave every response after it misaligned.
+#[derive(Default)]
+pub struct ModbusFrameDecoder {
+    buffer: Vec<u8>,
+    discarded: u64,
+}
+
+enum Candidate {
+    // Can't be the start of a response
+    Junk,
+    // Might be, once more bytes turn up
+    NeedMore,
+    // Would be a frame this long, if the checksum agrees
+    Frame(usize),
+}
+
+impl ModbusFrameDecoder {
+    pub fn new() -> ModbusFrameDecoder {
+        ModbusFrameDecoder::default()
+    }
+
+    pub fn push(&mut self, bytes: &[u8]) {
+        self.buffer.extend_from_slice(bytes);
+
+        if self.buffer.len() > MAX_BUFFERED {
+            self.discard(self.buffer.len() - MAX_BUFFERED);
+        }
+    }
+
+    // The line went quiet for 3.5 characters or more, which in RTU is the end of
+    // a frame. Anything still waiting to be completed never will be.
+    pub fn silence(&mut self) {
+        self.discard(self.buffer.len());
+    }
+
+    // How many bytes have been thrown away as junk so far
+    pub fn discarded(&self) -> u64 {
+        self.discarded
+    }
+
+    // The next whole frame, CRC included. Checks every offset in the buffer so a
+    // frame is found even if junk in front of it looks like the start of a
+    // longer one. Junk is only dropped once nothing can come of it.
+    pub fn next_frame(&mut self) -> Option<Vec<u8>> {
+        let mut keep_from = self.buffer.len();
+
+        for start in 0..self.buffer.len() {
+            match candidate(&self.buffer[start..]) {
+                Candidate::Junk => {}
+                Candidate::NeedMore => keep_from = keep_from.min(start),
+                Candidate::Frame(len) if start + len > self.buffer.len() => {
+                    keep_from = keep_from.min(start)
+                }
+                Candidate::Frame(len) => {
+                    let frame = &self.buffer[start..start + len];
+                    let crc = ((frame[len - 2] as u16) << 8) | frame[len - 1] as u16;
+
+                    if crc16(&frame[..len - 2]) == crc {
+                        self.discard(start);
+                        return Some(self.buffer.drain(..len).collect());
+                    }
+                }
+            }
+        }
+
+        self.discard(keep_from);
+        None
+    }
+
+    fn discard(&mut self, count: usize) {
+        self.buffer.drain(..count);
+        self.discarded += count as u64;
+    }
+}
+
+// What the bytes at the front of `buf` could be the start of
+fn candidate(buf: &[u8]) -> Candidate {
+    // Drives can only be at 1~247, and never answer a broadcast
+    match buf.first() {
+        Some(1..=247) => {}
+        Some(_) => return Candidate::Junk,
+        None => return Candidate::NeedMore,
+    }
+
+    let function = match buf.get(1) {
+        Some(&function) => function,
+        None => return Candidate::NeedMore,
+    };
+
+    match function {
+        // An exception for any function but 0, which doesn't exist
+        0x81..=0xff => Candidate::Frame(5),
+        // Read replies say how much data follows, a whole number of registers
+        0x03 => match buf.get(2) {
+            Some(&data_len) if data_len > 0 && data_len % 2 == 0 && data_len <= 250 => {
+                Candidate::Frame(3 + data_len as usize + 2)
+            }
+            Some(_) => Candidate::Junk,
+            None => Candidate::NeedMore,
+        },
+        0x06 | 0x10 | 0x78 | 0x7a => Candidate::Frame(8),
+        _ => Candidate::Junk,
+    }
+}
@@ -4,6 +4,8 @@ use crate::message::modbus_register::ModbusRegister;
 
 // Commands sent to the Device is structured as such
 // | Device Address | Command | Register Address High | Register Address Low | Register Value High | Register Value Low | CRC High | CRC LOW |
+// The manual lists ChangeDeviceAddress (0x7a) but never shows its frame. We send it shaped like a
+// register write to DeviceAddress, with the new address as the value.
 pub struct ModbusRequest {
     pub device_address: u8, // Normally 0x1
     pub command: ModbusCommand,
@@ -1,5 +1,6 @@
 use crate::crc::crc16;
 use crate::message::modbus_command::{ModbusCommand, ModbusCommandParseError};
+use crate::message::modbus_exception::ModbusException;
 use crate::message::modbus_register::{ModbusRegister, ModbusRegisterParseError};
 use std::io::Read;
 use thiserror::Error;
@@ -7,8 +8,17 @@ use thiserror::Error;
 // Response from the Devices
 // For read commands
 // | Device Address | Command | Data Length | Data Response High | ... | Data Response Low | CRC High | CRC LOW |
-// For write commands the device should echo back to the master the command that was sent as confirmation
+// For write commands the device should echo back to the master the command that was sent as confirmation.
+// Changing the device address is assumed to be answered the same way, the manual doesn't say
 // | Device Address | Command | Register Address 1 | Register Address 2 | Register Value High | Register Value Low | CRC High | CRC LOW |
+// For write multiple commands the device echoes back where it wrote to and how much
+// | Device Address | Command | Register Address High | Register Address Low | Register Count High | Register Count Low | CRC High | CRC LOW |
+// For write location commands the device replies with a pulse count, low word first. This is not
+// an echo: the manual's example answers a request for 10000 with 9998
+// | Device Address | Command | Pulses 8~15 | Pulses 0~7 | Pulses 24~31 | Pulses 16~23 | CRC High | CRC LOW |
+// When the device refuses a request, it sets the top bit of the command and says why
+// | Device Address | Command + 0x80 | Exception Code | CRC High | CRC LOW |
+#[allow(clippy::enum_variant_names)]
 pub enum ModbusResponse {
     WriteMessage {
         device_address: u8,
@@ -21,6 +31,23 @@ pub enum ModbusResponse {
         command: ModbusCommand,
         data: Vec<u8>,
     },
+    WriteMultipleMessage {
+        device_address: u8,
+        command: ModbusCommand,
+        register: ModbusRegister, // First Modbus Register Address
+        count: u16,
+    },
+    WriteLocationMessage {
+        device_address: u8,
+        command: ModbusCommand,
+        position: i32,
+    },
+    // Kept as the raw function code, the drive may be refusing one we don't know
+    ExceptionMessage {
+        device_address: u8,
+        function: u8,
+        exception: ModbusException,
+    },
 }
 
 #[derive(Debug, Error)]
@@ -44,12 +71,35 @@ impl ModbusResponse {
 
         let device_address = message_start[0];
 
+        if message_start[1] & 0x80 != 0 {
+            let mut message_end: [u8; 3] = [0; 3];
+
+            buf.read_exact(&mut message_end)
+                .map_err(ModbusResponseError::IOError)?;
+
+            let mut message_data: [u8; 5] = [0; 5];
+            message_data[..2].copy_from_slice(&message_start);
+            message_data[2..].copy_from_slice(&message_end);
+
+            return if crc16(&message_data[0..3])
+                != (((message_data[3] as u16) << 8) + message_data[4] as u16)
+            {
+                Err(ModbusResponseError::CheckSumFail)
+            } else {
+                Ok(ModbusResponse::ExceptionMessage {
+                    device_address,
+                    function: message_start[1] & 0x7f,
+                    exception: message_data[2].into(),
+                })
+            };
+        }
+
         let command: ModbusCommand = message_start[1]
             .try_into()
             .map_err(ModbusResponseError::CommandParseError)?;
 
         match command {
-            ModbusCommand::WriteRegister => {
+            ModbusCommand::WriteRegister | ModbusCommand::ChangeDeviceAddress => {
                 let mut message_end: [u8; 6] = [0; 6];
 
                 buf.read_exact(&mut message_end)
@@ -64,11 +114,11 @@ impl ModbusResponse {
                 message_data[..2].copy_from_slice(&message_start);
                 message_data[2..].copy_from_slice(&message_end);
 
-                if crc16(&message_data[0..6]) != (((message_data[6] as u16) << 8) + message_data[7] as u16)
+                if crc16(&message_data[0..6])
+                    != (((message_data[6] as u16) << 8) + message_data[7] as u16)
                 {
                     Err(ModbusResponseError::CheckSumFail)
-                } else
-                {
+                } else {
                     Ok(ModbusResponse::WriteMessage {
                         device_address,
                         command,
@@ -105,9 +155,59 @@ impl ModbusResponse {
                     })
                 }
             }
-            ModbusCommand::WriteLocation => todo!(),
-            ModbusCommand::ChangeDeviceAddress => todo!(),
-            ModbusCommand::WritePulse => unimplemented!()
+            ModbusCommand::WriteLocation => {
+                let mut message_end: [u8; 6] = [0; 6];
+
+                buf.read_exact(&mut message_end)
+                    .map_err(ModbusResponseError::IOError)?;
+
+                let mut message_data: [u8; 8] = [0; 8];
+                message_data[..2].copy_from_slice(&message_start);
+                message_data[2..].copy_from_slice(&message_end);
+
+                if crc16(&message_data[0..6])
+                    != (((message_data[6] as u16) << 8) + message_data[7] as u16)
+                {
+                    Err(ModbusResponseError::CheckSumFail)
+                } else {
+                    let low = ((message_data[2] as u32) << 8) | (message_data[3] as u32);
+                    let high = ((message_data[4] as u32) << 8) | (message_data[5] as u32);
+
+                    Ok(ModbusResponse::WriteLocationMessage {
+                        device_address,
+                        command,
+                        position: ((high << 16) | low) as i32,
+                    })
+                }
+            }
+            ModbusCommand::WritePulse => {
+                let mut message_end: [u8; 6] = [0; 6];
+
+                buf.read_exact(&mut message_end)
+                    .map_err(ModbusResponseError::IOError)?;
+
+                let register: ModbusRegister = (((message_end[0] as u16) << 8)
+                    + (message_end[1] as u16))
+                    .try_into()
+                    .map_err(ModbusResponseError::RegisterParseError)?;
+
+                let mut message_data: [u8; 8] = [0; 8];
+                message_data[..2].copy_from_slice(&message_start);
+                message_data[2..].copy_from_slice(&message_end);
+
+                if crc16(&message_data[0..6])
+                    != (((message_data[6] as u16) << 8) + message_data[7] as u16)
+                {
+                    Err(ModbusResponseError::CheckSumFail)
+                } else {
+                    Ok(ModbusResponse::WriteMultipleMessage {
+                        device_address,
+                        command,
+                        register,
+                        count: ((message_data[4] as u16) << 8) | (message_data[5] as u16),
+                    })
+                }
+            }
         }
     }
 }
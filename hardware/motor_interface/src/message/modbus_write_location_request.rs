@@ -0,0 +1,31 @@
+use crate::crc::crc16;
+use crate::message::modbus_command::ModbusCommand;
+
+// The vendor's own shortcut (function 0x78) for sending a pulse count without going through the registers
+// | Device Address | Command | Pulses 24~31 | Pulses 16~23 | Pulses 8~15 | Pulses 0~7 | CRC High | CRC LOW |
+pub struct ModbusWriteLocationRequest {
+    pub device_address: u8,
+    pub position: i32,
+}
+
+impl ModbusWriteLocationRequest {
+    pub fn to_message_bytes(&self) -> [u8; 8] {
+        let position = self.position as u32;
+        let mut message_bytes: [u8; 8] = [
+            self.device_address,
+            ModbusCommand::WriteLocation.into(),
+            (position >> 24) as u8,
+            (position >> 16) as u8,
+            (position >> 8) as u8,
+            position as u8,
+            0x0,
+            0x0,
+        ];
+
+        let crc = crc16(&message_bytes[0..6]);
+        message_bytes[6] = (crc >> 8) as u8;
+        message_bytes[7] = crc as u8;
+
+        message_bytes
+    }
+}
@@ -0,0 +1,42 @@
+use crate::crc::crc16;
+use crate::message::modbus_command::ModbusCommand;
+use crate::message::modbus_register::ModbusRegister;
+
+// Writing a block of consecutive registers (function 0x10) is structured as such
+// | Device Address | Command | Register Address High | Register Address Low | Register Count High | Register Count Low | Byte Count | Value 1 High | Value 1 Low | ... | CRC High | CRC LOW |
+// 32-bit values (e.g. target position) go low word first, so they should be
+// written starting from the *Low register
+pub struct ModbusWriteMultipleRequest {
+    pub device_address: u8,
+    pub register: ModbusRegister, // First Modbus Register Address
+    pub values: Vec<u16>,
+}
+
+impl ModbusWriteMultipleRequest {
+    pub fn to_message_bytes(&self) -> Vec<u8> {
+        let register_code: u16 = self.register.into();
+        let count = self.values.len() as u16;
+
+        let mut message_bytes: Vec<u8> = Vec::with_capacity(7 + self.values.len() * 2 + 2);
+        message_bytes.extend_from_slice(&[
+            self.device_address,
+            ModbusCommand::WritePulse.into(),
+            (register_code >> 8) as u8,
+            register_code as u8,
+            (count >> 8) as u8,
+            count as u8,
+            (count * 2) as u8,
+        ]);
+
+        for value in &self.values {
+            message_bytes.push((value >> 8) as u8);
+            message_bytes.push(*value as u8);
+        }
+
+        let crc = crc16(&message_bytes);
+        message_bytes.push((crc >> 8) as u8);
+        message_bytes.push(crc as u8);
+
+        message_bytes
+    }
+}
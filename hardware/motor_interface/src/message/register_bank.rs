@@ -0,0 +1,59 @@
+use super::ModbusRegister;
+
+// A run of registers read in one go, so they all come from the same moment
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RegisterBank {
+    start: ModbusRegister,
+    values: Vec<u16>,
+}
+
+impl RegisterBank {
+    pub fn new(start: ModbusRegister, values: Vec<u16>) -> RegisterBank {
+        RegisterBank { start, values }
+    }
+
+    pub fn start(&self) -> ModbusRegister {
+        self.start
+    }
+
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    pub fn values(&self) -> &[u16] {
+        &self.values
+    }
+
+    // None if `register` wasn't part of the read
+    pub fn get(&self, register: ModbusRegister) -> Option<u16> {
+        let offset = (register as usize).checked_sub(self.start as usize)?;
+
+        self.values.get(offset).copied()
+    }
+
+    pub fn get_i16(&self, register: ModbusRegister) -> Option<i16> {
+        self.get(register).map(|value| value as i16)
+    }
+
+    // A 32-bit value split over `low` and the register after it, low word first
+    // as the drive does it
+    pub fn get_i32(&self, low: ModbusRegister) -> Option<i32> {
+        let high: ModbusRegister = (low as u16 + 1).try_into().ok()?;
+
+        Some((((self.get(high)? as u32) << 16) | self.get(low)? as u32) as i32)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (ModbusRegister, u16)> + '_ {
+        self.values
+            .iter()
+            .enumerate()
+            .filter_map(|(offset, &value)| {
+                let register = (self.start as u16 + offset as u16).try_into().ok()?;
+                Some((register, value))
+            })
+    }
+}
@@ -0,0 +1,278 @@
+use super::{ModbusRegister, ModbusRegisterParseError, RegisterBank};
+use std::fmt::Display;
+use std::str::FromStr;
+use thiserror::Error;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum RegisterAccess {
+    ReadOnly,
+    ReadWrite,
+    // Only takes effect when written together with the rest of its value, in
+    // one write-multiple
+    BlockWrite,
+}
+
+// What the drive's manual says about a register, as far as it says anything
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct RegisterInfo {
+    pub register: ModbusRegister,
+    pub access: RegisterAccess,
+    // Read as an i16 rather than a u16
+    pub signed: bool,
+    // What can be written, in register units. Reads can go outside this, e.g.
+    // the parameter saving flag reads back 2
+    pub min: i32,
+    pub max: i32,
+    // Real value = register value * scale, in `unit`. An empty unit is a plain
+    // number or a flag
+    pub unit: &'static str,
+    pub scale: f32,
+    // What the drive ships with, None if it's not a setting
+    pub default: Option<u16>,
+    // Kept over a power cycle once saved with the parameter saving flag
+    pub persisted: bool,
+}
+
+#[derive(Debug, PartialEq, Eq, Error)]
+pub enum RegisterWriteError {
+    #[error("{0:?} is read-only")]
+    ReadOnly(ModbusRegister),
+    #[error("{0:?} can only be written together with the rest of its value")]
+    BlockWriteOnly(ModbusRegister),
+    #[error("{1} is out of range for {0:?}, expected {2}~{3}")]
+    OutOfRange(ModbusRegister, i32, i32, i32),
+}
+
+const fn info(
+    register: ModbusRegister,
+    access: RegisterAccess,
+    min: i32,
+    max: i32,
+    unit: &'static str,
+    scale: f32,
+) -> RegisterInfo {
+    RegisterInfo {
+        register,
+        access,
+        signed: min < 0,
+        min,
+        max,
+        unit,
+        scale,
+        default: None,
+        persisted: false,
+    }
+}
+
+// A setting the drive can save, starting off at `default`
+const fn setting(
+    register: ModbusRegister,
+    min: i32,
+    max: i32,
+    unit: &'static str,
+    scale: f32,
+    default: u16,
+) -> RegisterInfo {
+    RegisterInfo {
+        default: Some(default),
+        persisted: true,
+        ..info(register, RegisterAccess::ReadWrite, min, max, unit, scale)
+    }
+}
+
+const fn read_only(
+    register: ModbusRegister,
+    min: i32,
+    max: i32,
+    unit: &'static str,
+    scale: f32,
+) -> RegisterInfo {
+    info(register, RegisterAccess::ReadOnly, min, max, unit, scale)
+}
+
+const fn flag(register: ModbusRegister) -> RegisterInfo {
+    RegisterInfo {
+        default: Some(0),
+        ..info(register, RegisterAccess::ReadWrite, 0, 1, "", 1.0)
+    }
+}
+
+const fn word(register: ModbusRegister, access: RegisterAccess) -> RegisterInfo {
+    info(register, access, 0, u16::MAX as i32, "", 1.0)
+}
+
+// In register order, so REGISTERS[register as usize] is that register's
+const REGISTERS: [RegisterInfo; 26] = {
+    use ModbusRegister::*;
+    use RegisterAccess::*;
+
+    [
+        flag(EnableModbus),
+        flag(EnableMotor),
+        RegisterInfo {
+            default: Some(0),
+            ..info(MotorTargetSpeed, ReadWrite, -30_000, 30_000, "rpm", 0.1)
+        },
+        setting(MotorAcceleration, 0, 60_000, "rpm/s", 1.0, 20_000),
+        setting(MotorInitialSpeed, 0, 500, "rpm", 1.0, 0),
+        setting(
+            MotorSpeedLoopProportionalCoefficient,
+            0,
+            10_000,
+            "",
+            0.001,
+            1_000,
+        ),
+        setting(MotorSpeedLoopIntegrationTime, 2, 2_000, "ms", 1.0, 100),
+        setting(
+            MotorPositionLoopProportionalCoefficient,
+            60,
+            5_000,
+            "",
+            1.0,
+            500,
+        ),
+        // Nobody knows the scale of this one, so it's left raw
+        setting(MotorSpeedFeedForwardVoltage, 0, u16::MAX as i32, "", 1.0, 0),
+        setting(MotorDirectionPolarity, 0, 1, "", 1.0, 0),
+        // A numerator of 0 is allowed, see the absolute position below
+        setting(MotorElectronicGearHigh, 0, u16::MAX as i32, "", 1.0, 1),
+        setting(MotorElectronicGearLow, 1, u16::MAX as i32, "", 1.0, 1),
+        // Written as one relative move by set_position
+        word(MotorTargetPositionLow, BlockWrite),
+        word(MotorTargetPositionHigh, BlockWrite),
+        read_only(MotorAlarmCode, 0, u16::MAX as i32, "", 1.0),
+        read_only(MotorI, 0, 32_767, "A", 1.0 / 2000.0),
+        read_only(MotorCurrentSpeed, -30_000, 30_000, "rpm", 0.1),
+        read_only(MotorV, 0, 32_767, "V", 1.0 / 327.0),
+        read_only(SystemTemperature, 0, 100, "°C", 1.0),
+        read_only(SystemOutputPWM, -32_768, 32_767, "%", 100.0 / 32_768.0),
+        RegisterInfo {
+            default: Some(0),
+            ..info(ParameterSavingFlag, ReadWrite, 0, 1, "", 1.0)
+        },
+        // Moved with the change device address command, not by writing it
+        read_only(DeviceAddress, 0, 255, "", 1.0),
+        // With the electronic gear numerator at 0, writing 0 here clears the
+        // position. Any other value is an absolute move, so like the target
+        // they're only written together, by move_to_counts
+        word(MotorAbsolutePositionLow, BlockWrite),
+        word(MotorAbsolutePositionHigh, BlockWrite),
+        setting(MotorSpeedFilterFrequency, 1, 2_000, "Hz", 1.0, 100),
+        setting(MotorSpecialFunction, 0, 3, "", 1.0, 0),
+    ]
+};
+
+impl ModbusRegister {
+    pub const ALL: [ModbusRegister; 26] = {
+        let mut all = [ModbusRegister::EnableModbus; 26];
+        let mut i = 0;
+        while i < all.len() {
+            all[i] = REGISTERS[i].register;
+            i += 1;
+        }
+        all
+    };
+
+    pub fn info(self) -> &'static RegisterInfo {
+        &REGISTERS[self as usize]
+    }
+
+    // Whether `value` can go out on its own with a single register write
+    pub fn check_write(self, value: u16) -> Result<(), RegisterWriteError> {
+        match self.info().access {
+            RegisterAccess::ReadOnly => Err(RegisterWriteError::ReadOnly(self)),
+            RegisterAccess::BlockWrite => Err(RegisterWriteError::BlockWriteOnly(self)),
+            RegisterAccess::ReadWrite => self.check_range(value),
+        }
+    }
+
+    // As `check_write`, for a value going out as part of a write-multiple
+    pub fn check_block_write(self, value: u16) -> Result<(), RegisterWriteError> {
+        match self.info().access {
+            RegisterAccess::ReadOnly => Err(RegisterWriteError::ReadOnly(self)),
+            RegisterAccess::ReadWrite | RegisterAccess::BlockWrite => self.check_range(value),
+        }
+    }
+
+    fn check_range(self, value: u16) -> Result<(), RegisterWriteError> {
+        let info = self.info();
+        let value = info.decode(value);
+        if (info.min..=info.max).contains(&value) {
+            Ok(())
+        } else {
+            Err(RegisterWriteError::OutOfRange(
+                self, value, info.min, info.max,
+            ))
+        }
+    }
+
+    // For printing a value read from this register
+    pub fn value(self, raw: u16) -> RegisterValue {
+        RegisterValue {
+            register: self,
+            raw,
+        }
+    }
+}
+
+// By the variant's name, as it's printed
+impl FromStr for ModbusRegister {
+    type Err = ModbusRegisterParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        ModbusRegister::ALL
+            .into_iter()
+            .find(|register| format!("{:?}", register) == s)
+            .ok_or(ModbusRegisterParseError)
+    }
+}
+
+impl RegisterInfo {
+    // The register value as a number, minding the sign
+    pub fn decode(&self, raw: u16) -> i32 {
+        if self.signed {
+            raw as i16 as i32
+        } else {
+            raw as i32
+        }
+    }
+
+    // In `unit`
+    pub fn real(&self, raw: u16) -> f32 {
+        self.decode(raw) as f32 * self.scale
+    }
+}
+
+// e.g. "MotorTargetSpeed = 1500 (150.0 rpm)"
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct RegisterValue {
+    pub register: ModbusRegister,
+    pub raw: u16,
+}
+
+impl Display for RegisterValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let info = self.register.info();
+        write!(f, "{:?} = {}", self.register, info.decode(self.raw))?;
+
+        if !info.unit.is_empty() {
+            write!(f, " ({:.1} {})", info.real(self.raw), info.unit)?;
+        } else if info.scale != 1.0 {
+            write!(f, " ({:.3})", info.real(self.raw))?;
+        }
+
+        Ok(())
+    }
+}
+
+// One register to a line
+impl Display for RegisterBank {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (register, raw) in self.iter() {
+            writeln!(f, "{}", register.value(raw))?;
+        }
+
+        Ok(())
+    }
+}
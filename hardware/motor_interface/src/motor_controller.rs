@@ -1,17 +1,45 @@
 use crate::message::*;
+use crate::motor_controller::bus::BusLine;
+use crate::motor_controller::config::MotorConfig;
+use crate::motor_controller::constants::{MOTOR_FLASH_WRITE_POLL, MOTOR_FLASH_WRITE_TIMEOUT};
 use crate::motor_controller::error::MotorControllerError;
-use crate::motor_controller::motor_status::MotorStatus;
-use constants::{MOTOR_BAUD_RATE, MOTOR_CONNECTION_TIMEOUT, MOTOR_GEAR, MOTOR_WHEEL_LENGTH};
-use serialport::SerialPort;
-use std::io::Write;
-
-mod constants;
+use crate::motor_controller::motor_status::{MotorStatus, MotorStatusWarning};
+use crate::motor_controller::profile::{DriveProfile, ProfileDifference};
+use crate::motor_controller::retry::{RequestKind, RetryPolicy, RetryStats};
+use crate::motor_controller::telemetry::{
+    current_amps, pwm_percent, voltage_volts, Telemetry, TELEMETRY_LEN, TELEMETRY_START,
+};
+use crate::motor_controller::watchdog::{Watchdog, WatchdogConfig, WatchdogTrip};
+use crate::transport::{SerialTransport, Transport};
+use log::debug;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Instant;
+
+pub mod bus;
+pub mod config;
+pub(crate) mod constants;
+pub mod diff_drive;
+pub mod discovery;
 pub mod error;
 pub mod motor_status;
-
+pub mod odometry;
+pub mod poller;
+pub mod profile;
+pub mod retry;
+pub mod shaper;
+pub mod telemetry;
+pub mod watchdog;
+
+// A single drive. The port underneath may be shared with other drives through a
+// MotorBus, otherwise the controller has it to itself.
 pub struct MotorController {
     device_address: u8,
-    port: Box<dyn SerialPort>,
+    line: Arc<Mutex<BusLine>>,
+    retry_policy: RetryPolicy,
+    retry_stats: RetryStats,
+    config: MotorConfig,
+    watchdog: Watchdog,
 }
 
 impl MotorController {
@@ -19,46 +47,239 @@ impl MotorController {
         port_path: &str,
         device_address: u8,
     ) -> Result<MotorController, MotorControllerError> {
+        MotorController::with_config(port_path, device_address, MotorConfig::default())
+    }
+
+    pub fn with_config(
+        port_path: &str,
+        device_address: u8,
+        config: MotorConfig,
+    ) -> Result<MotorController, MotorControllerError> {
+        config.validate()?;
+
         // Establish a connection to the motor port
-        let port = serialport::new(port_path, MOTOR_BAUD_RATE)
-            // Char size 8
-            .data_bits(serialport::DataBits::Eight)
-            // No parity bits
-            .parity(serialport::Parity::None)
-            // Stop bits
-            .stop_bits(serialport::StopBits::One)
-            // No hardware flow control
-            .flow_control(serialport::FlowControl::None)
-            // ---
-            .timeout(MOTOR_CONNECTION_TIMEOUT)
-            .open()
-            .map_err(|e: serialport::Error| MotorControllerError::SerialError(e))?;
-
-        Ok(MotorController {
-            port,
+        let port = SerialTransport::open(port_path, config.baud_rate, config.connection_timeout())
+            .map_err(MotorControllerError::SerialError)?;
+
+        let mut controller = MotorController::from_transport(port, device_address);
+        controller.config = config;
+
+        Ok(controller)
+    }
+
+    // Drive a motor over anything that can carry the bytes, e.g. a loopback
+    // or pseudo-terminal when there is no hardware to hand
+    pub fn from_transport(
+        transport: impl Transport + 'static,
+        device_address: u8,
+    ) -> MotorController {
+        MotorController {
+            line: Arc::new(Mutex::new(BusLine::new(Box::new(transport)))),
             device_address,
-        })
+            retry_policy: RetryPolicy::default(),
+            retry_stats: RetryStats::default(),
+            config: MotorConfig::default(),
+            watchdog: Watchdog::new(device_address),
+        }
+    }
+
+    // Another controller for the same drive, sharing the line
+    fn handle(&self) -> MotorController {
+        MotorController {
+            device_address: self.device_address,
+            line: self.line.clone(),
+            retry_policy: self.retry_policy,
+            retry_stats: RetryStats::default(),
+            config: self.config,
+            watchdog: Watchdog::new(self.device_address),
+        }
+    }
+
+    // Send a frame and wait for whatever comes back. The bus is held for the
+    // whole exchange so nobody else's request lands in the middle of it
+    fn transact(&mut self, frame: &[u8]) -> Result<ModbusResponse, MotorControllerError> {
+        let mut line = self.line.lock().unwrap();
+
+        let attempt_timeout = self.retry_policy.attempt_timeout;
+        let port_timeout = line.transport.timeout();
+        if let Some(timeout) = attempt_timeout {
+            line.transport
+                .set_timeout(timeout)
+                .map_err(MotorControllerError::IOError)?;
+        }
+
+        let response = line.exchange(frame);
+
+        if attempt_timeout.is_some() {
+            line.transport
+                .set_timeout(port_timeout)
+                .map_err(MotorControllerError::IOError)?;
+        }
+
+        match response? {
+            ModbusResponse::ExceptionMessage {
+                device_address,
+                function,
+                exception,
+            } => {
+                if device_address != self.device_address {
+                    Err(MotorControllerError::InvalidResponder(self.device_address, device_address))
+                } else {
+                    Err(MotorControllerError::ModbusException(function, exception))
+                }
+            }
+            response => Ok(response),
+        }
+    }
+
+    // Keep trying `attempt` for as long as the retry policy allows
+    fn with_retries<T>(
+        &mut self,
+        kind: RequestKind,
+        mut attempt: impl FnMut(&mut Self) -> Result<T, MotorControllerError>,
+    ) -> Result<T, MotorControllerError> {
+        let mut retries = 0;
+
+        loop {
+            match attempt(self) {
+                Ok(v) => {
+                    self.retry_stats.record(retries, false);
+                    return Ok(v);
+                }
+                Err(e)
+                    if retries + 1 < self.retry_policy.max_attempts
+                        && self.retry_policy.should_retry(kind, &e) =>
+                {
+                    retries += 1;
+                    debug!("Retrying request to drive {} after: {}", self.device_address, e);
+                    thread::sleep(self.retry_policy.backoff(retries));
+                }
+                Err(e) => {
+                    self.retry_stats.record(retries, true);
+                    return Err(e);
+                }
+            }
+        }
+    }
+
+    pub fn retry_policy(&self) -> RetryPolicy {
+        self.retry_policy
+    }
+
+    pub fn set_retry_policy(&mut self, retry_policy: RetryPolicy) {
+        self.retry_policy = retry_policy;
+    }
+
+    pub fn retry_stats(&self) -> RetryStats {
+        self.retry_stats
+    }
+
+    pub fn reset_retry_stats(&mut self) {
+        self.retry_stats = RetryStats::default();
+    }
+
+    pub fn config(&self) -> MotorConfig {
+        self.config
+    }
+
+    // Only the geometry is picked up here. The port is already open, so the baud
+    // rate and timeout have to be changed on it (or the bus) directly
+    pub fn set_config(&mut self, config: MotorConfig) -> Result<(), MotorControllerError> {
+        config.validate()?;
+        self.config = config;
+
+        Ok(())
+    }
+
+    pub fn watchdog(&self) -> Option<WatchdogConfig> {
+        self.watchdog.config()
+    }
+
+    // Stop the motor if `set_rpm` (or `set_velocity`) isn't called again within
+    // the timeout, e.g. because whatever was driving it has hung. None turns it
+    // off. Once tripped it stays quiet until the next speed command
+    pub fn set_watchdog(&mut self, config: Option<WatchdogConfig>) {
+        let handle = self.handle();
+        self.watchdog.configure(config, || handle);
+    }
+
+    // The oldest trip not yet collected
+    pub fn take_watchdog_trip(&mut self) -> Option<WatchdogTrip> {
+        self.watchdog.take_trip()
+    }
+
+    // The watchdog has stopped the drive and nothing's been sent since
+    pub fn is_watchdog_tripped(&self) -> bool {
+        self.watchdog.is_tripped()
+    }
+
+    pub fn device_address(&self) -> u8 {
+        self.device_address
+    }
+
+    // Bytes thrown away as junk on this controller's port, shared with any
+    // other drives on the same bus
+    pub fn discarded_bytes(&self) -> u64 {
+        self.line.lock().unwrap().decoder.discarded()
     }
 
     pub fn request(
         &mut self,
         message: &ModbusRequest,
     ) -> Result<ModbusResponse, MotorControllerError> {
-        let frame = message.to_message_bytes();
+        let kind = match message.command {
+            ModbusCommand::ReadRegister => RequestKind::Read,
+            ModbusCommand::WriteRegister => {
+                // Caught here rather than left for the drive to quietly ignore
+                message.register.check_write(message.value)?;
+                RequestKind::Write
+            }
+            // Might already have taken effect, so the next try would go to the wrong address
+            ModbusCommand::ChangeDeviceAddress => RequestKind::Once,
+            // A relative move, twice would go twice as far
+            ModbusCommand::WriteLocation | ModbusCommand::WritePulse => RequestKind::Once,
+        };
 
-        self.port
-            .write_all(&frame)
-            .map_err(MotorControllerError::IOError)?;
-        self.port
-            .flush()
-            .map_err(MotorControllerError::IOError)?;
+        self.with_retries(kind, |controller| controller.try_request(message))
+    }
 
-        let v = ModbusResponse::from_reader(&mut self.port)
-            .map_err(MotorControllerError::ResponseError)?;
+    fn try_request(
+        &mut self,
+        message: &ModbusRequest,
+    ) -> Result<ModbusResponse, MotorControllerError> {
+        let v = self.transact(&message.to_message_bytes())?;
 
         match (message.command, &v) {
-            (ModbusCommand::WriteLocation, _) => todo!(),
-            (ModbusCommand::ChangeDeviceAddress, _) => todo!(),
+            (
+                ModbusCommand::WriteLocation,
+                ModbusResponse::WriteLocationMessage { device_address, .. },
+            ) => {
+                if message.device_address != *device_address {
+                    Err(MotorControllerError::InvalidResponder(message.device_address, *device_address))
+                } else {
+                    Ok(v)
+                }
+            }
+            (
+                ModbusCommand::ChangeDeviceAddress,
+                ModbusResponse::WriteMessage {
+                    device_address,
+                    register,
+                    value,
+                    ..
+                },
+            ) => {
+                // Depending on when the drive switches over, the reply could come from either address
+                if message.device_address != *device_address && message.value != *device_address as u16 {
+                    Err(MotorControllerError::InvalidResponder(message.device_address, *device_address))
+                } else if message.register != *register {
+                    Err(MotorControllerError::IncorrectResponseRegister(message.register, *register))
+                } else if message.value != *value {
+                    Err(MotorControllerError::IncorrectResponseValue(message.value, *value))
+                } else {
+                    Ok(v)
+                }
+            }
             (ModbusCommand::ReadRegister, ModbusResponse::ReadMessage { device_address, .. }) => {
                 if message.device_address != *device_address {
                     Err(MotorControllerError::InvalidResponder(message.device_address, *device_address))
@@ -75,9 +296,9 @@ impl MotorController {
                 },
             ) => {
                 if message.device_address != *device_address {
-                    Err(MotorControllerError::InvalidResponder(self.device_address, message.device_address))
+                    Err(MotorControllerError::InvalidResponder(message.device_address, *device_address))
                 } else if message.register != *register {
-                    Err(MotorControllerError::IncorrectResponseRegister(*register, message.register))
+                    Err(MotorControllerError::IncorrectResponseRegister(message.register, *register))
                 } else {
                     Ok(v)
                 }
@@ -86,6 +307,323 @@ impl MotorController {
         }
     }
 
+    pub fn request_write_multiple(
+        &mut self,
+        message: &ModbusWriteMultipleRequest,
+    ) -> Result<ModbusResponse, MotorControllerError> {
+        // Modbus wants at least one register, and nothing would be written anyway
+        if message.values.is_empty() {
+            return Err(MotorControllerError::InvalidRegisterRange(message.register, 0));
+        }
+
+        let first = message.register as u16;
+        for (offset, &value) in message.values.iter().enumerate() {
+            let register = ModbusRegister::try_from(first + offset as u16).map_err(|_| {
+                MotorControllerError::InvalidRegisterRange(message.register, message.values.len())
+            })?;
+            register.check_block_write(value)?;
+        }
+
+        // Writing the target position is a relative move, anything else just sets registers
+        let last = first + message.values.len() as u16 - 1;
+        let kind = if (first..=last).contains(&(ModbusRegister::MotorTargetPositionLow as u16))
+            || (first..=last).contains(&(ModbusRegister::MotorTargetPositionHigh as u16))
+        {
+            RequestKind::Once
+        } else {
+            RequestKind::Write
+        };
+
+        self.with_retries(kind, |controller| controller.try_request_write_multiple(message))
+    }
+
+    fn try_request_write_multiple(
+        &mut self,
+        message: &ModbusWriteMultipleRequest,
+    ) -> Result<ModbusResponse, MotorControllerError> {
+        let v = self.transact(&message.to_message_bytes())?;
+
+        match &v {
+            ModbusResponse::WriteMultipleMessage {
+                device_address,
+                register,
+                count,
+                ..
+            } => {
+                if message.device_address != *device_address {
+                    Err(MotorControllerError::InvalidResponder(message.device_address, *device_address))
+                } else if message.register != *register {
+                    Err(MotorControllerError::IncorrectResponseRegister(message.register, *register))
+                } else if message.values.len() != *count as usize {
+                    Err(MotorControllerError::IncorrectResponseValue(message.values.len() as u16, *count))
+                } else {
+                    Ok(v)
+                }
+            }
+            _ => Err(MotorControllerError::IncorrectResponseType),
+        }
+    }
+
+    // Always a relative move, so never retried
+    pub fn request_write_location(
+        &mut self,
+        message: &ModbusWriteLocationRequest,
+    ) -> Result<ModbusResponse, MotorControllerError> {
+        self.with_retries(RequestKind::Once, |controller| {
+            controller.try_request_write_location(message)
+        })
+    }
+
+    fn try_request_write_location(
+        &mut self,
+        message: &ModbusWriteLocationRequest,
+    ) -> Result<ModbusResponse, MotorControllerError> {
+        let v = self.transact(&message.to_message_bytes())?;
+
+        match &v {
+            ModbusResponse::WriteLocationMessage { device_address, .. } => {
+                if message.device_address != *device_address {
+                    Err(MotorControllerError::InvalidResponder(message.device_address, *device_address))
+                } else {
+                    Ok(v)
+                }
+            }
+            _ => Err(MotorControllerError::IncorrectResponseType),
+        }
+    }
+
+    // Write a run of consecutive registers in one transaction, so the drive
+    // never sees half of a multi-word value
+    pub fn write_registers(
+        &mut self,
+        register: ModbusRegister,
+        values: &[u16],
+    ) -> Result<(), MotorControllerError> {
+        // A speed set this way is still one the watchdog has to stop on drop
+        let speed = (ModbusRegister::MotorTargetSpeed as usize)
+            .checked_sub(register as usize)
+            .and_then(|offset| values.get(offset));
+        if speed.is_some() {
+            self.watchdog.feed();
+        }
+
+        let write_registers_message = ModbusWriteMultipleRequest {
+            device_address: self.device_address,
+            register,
+            values: values.to_vec(),
+        };
+
+        self.request_write_multiple(&write_registers_message)?;
+
+        if let Some(&speed) = speed {
+            self.watchdog.took(speed as i16);
+        }
+
+        Ok(())
+    }
+
+    pub fn read_register(&mut self, register: ModbusRegister) -> Result<u16, MotorControllerError> {
+        let read_register_message = ModbusRequest {
+            device_address: self.device_address,
+            command: ModbusCommand::ReadRegister,
+            register,
+            value: 0x1,
+        };
+
+        let resp: ModbusResponse = self.request(&read_register_message)?;
+
+        match resp {
+            ModbusResponse::ReadMessage { data, .. } => {
+                if data.len() != 2 {
+                    Err(MotorControllerError::IncorrectDataLength(2, data.len()))
+                } else {
+                    Ok(((data[0] as u16) << 8) | (data[1] as u16))
+                }
+            }
+            _ => Err(MotorControllerError::IncorrectResponseType),
+        }
+    }
+
+    // `count` registers in a row from `start`, in one transaction. They all have
+    // to be registers the drive has
+    pub fn read_registers(
+        &mut self,
+        start: ModbusRegister,
+        count: usize,
+    ) -> Result<RegisterBank, MotorControllerError> {
+        let last = start as usize + count.max(1) - 1;
+        if count == 0 || ModbusRegister::try_from(last as u16).is_err() {
+            return Err(MotorControllerError::InvalidRegisterRange(start, count));
+        }
+
+        let read_registers_message = ModbusRequest {
+            device_address: self.device_address,
+            command: ModbusCommand::ReadRegister,
+            register: start,
+            value: count as u16,
+        };
+
+        match self.request(&read_registers_message)? {
+            ModbusResponse::ReadMessage { data, .. } => {
+                if data.len() != count * 2 {
+                    Err(MotorControllerError::IncorrectDataLength(count * 2, data.len()))
+                } else {
+                    let values = data
+                        .chunks_exact(2)
+                        .map(|word| ((word[0] as u16) << 8) | (word[1] as u16))
+                        .collect();
+                    Ok(RegisterBank::new(start, values))
+                }
+            }
+            _ => Err(MotorControllerError::IncorrectResponseType),
+        }
+    }
+
+    pub fn write_register(
+        &mut self,
+        register: ModbusRegister,
+        value: u16,
+    ) -> Result<(), MotorControllerError> {
+        // Same as set_rpm, so the watchdog knows the motor is moving
+        let speed = register == ModbusRegister::MotorTargetSpeed;
+        if speed {
+            self.watchdog.feed();
+        }
+
+        let write_register_message = ModbusRequest {
+            device_address: self.device_address,
+            command: ModbusCommand::WriteRegister,
+            register,
+            value,
+        };
+
+        self.request(&write_register_message)?;
+
+        if speed {
+            self.watchdog.took(value as i16);
+        }
+
+        Ok(())
+    }
+
+    // Move the drive to a new address on the bus, so more than one drive can share
+    // it. The change is checked by reading the address back from the new address,
+    // and only survives a power cycle if `persist` is set.
+    pub fn change_device_address(
+        &mut self,
+        new_address: u8,
+        persist: bool,
+    ) -> Result<(), MotorControllerError> {
+        // 0 is broadcast and 248 up are reserved
+        if !(1..=247).contains(&new_address) {
+            return Err(MotorControllerError::InvalidDeviceAddress(new_address));
+        }
+
+        let change_device_address_message = ModbusRequest {
+            device_address: self.device_address,
+            command: ModbusCommand::ChangeDeviceAddress,
+            register: ModbusRegister::DeviceAddress,
+            value: new_address as u16,
+        };
+
+        self.request(&change_device_address_message)?;
+
+        let old_address = self.device_address;
+        self.device_address = new_address;
+
+        match self.read_register(ModbusRegister::DeviceAddress) {
+            Ok(address) if address == new_address as u16 => {}
+            Ok(address) => {
+                self.device_address = old_address;
+                return Err(MotorControllerError::IncorrectResponseValue(new_address as u16, address));
+            }
+            Err(e) => {
+                self.device_address = old_address;
+                return Err(e);
+            }
+        }
+        self.watchdog.set_device_address(new_address);
+
+        if persist {
+            self.save_parameters()?;
+        }
+
+        Ok(())
+    }
+
+    // Keep the current parameters over a power cycle. The drive raises a warning
+    // rather than refusing the write if the flash won't take them
+    pub fn save_parameters(&mut self) -> Result<(), MotorControllerError> {
+        let save_parameters_message = ModbusRequest {
+            device_address: self.device_address,
+            command: ModbusCommand::WriteRegister,
+            register: ModbusRegister::ParameterSavingFlag,
+            value: 0x1,
+        };
+
+        self.request(&save_parameters_message)?;
+
+        // The flag reads 1 while the drive is still saving, and a failed write
+        // only shows up once it's done
+        let started = Instant::now();
+        loop {
+            // Alarm code up to the saving flag, in one go
+            let bank = self.read_registers(ModbusRegister::MotorAlarmCode, 7)?;
+            let alarm = bank.get(ModbusRegister::MotorAlarmCode).unwrap_or_default();
+            let flag = bank.get(ModbusRegister::ParameterSavingFlag).unwrap_or_default();
+
+            if let Ok(MotorStatus::Warning(MotorStatusWarning::FlashWriteFailed)) =
+                MotorStatus::try_from(alarm)
+            {
+                return Err(MotorControllerError::FlashWriteFailed);
+            }
+
+            // 2 is saved
+            match flag {
+                0 => return Err(MotorControllerError::FlashWriteFailed),
+                1 if started.elapsed() >= MOTOR_FLASH_WRITE_TIMEOUT => {
+                    return Err(MotorControllerError::FlashWriteTimedOut(MOTOR_FLASH_WRITE_TIMEOUT))
+                }
+                1 => thread::sleep(MOTOR_FLASH_WRITE_POLL),
+                _ => return Ok(()),
+            }
+        }
+    }
+
+    // Every register the drive saves, read in one transaction
+    pub fn read_profile(&mut self) -> Result<DriveProfile, MotorControllerError> {
+        let bank = self.read_registers(ModbusRegister::EnableModbus, ModbusRegister::ALL.len())?;
+
+        Ok(DriveProfile::from_bank(&bank))
+    }
+
+    // Empty if the drive already matches
+    pub fn diff_profile(
+        &mut self,
+        profile: &DriveProfile,
+    ) -> Result<Vec<ProfileDifference>, MotorControllerError> {
+        let bank = self.read_registers(ModbusRegister::EnableModbus, ModbusRegister::ALL.len())?;
+
+        Ok(profile.diff(&bank))
+    }
+
+    // Writes the profile and reads it back to check it took. It only lasts until
+    // the next power cycle unless followed by `save_parameters`
+    pub fn apply_profile(&mut self, profile: &DriveProfile) -> Result<(), MotorControllerError> {
+        for (start, values) in profile.runs() {
+            self.write_registers(start, &values)?;
+        }
+
+        match self.diff_profile(profile)?.first() {
+            Some(difference) => Err(MotorControllerError::ProfileMismatch(
+                difference.register,
+                difference.profile,
+                difference.drive,
+            )),
+            None => Ok(()),
+        }
+    }
+
     pub fn enable_modbus(&mut self) -> Result<(), MotorControllerError> {
         let device_address = self.device_address;
 
@@ -153,12 +691,22 @@ impl MotorController {
     pub fn get_velocity(&mut self) -> Result<f32, MotorControllerError> {
         let rpm = self.get_rpm()?;
 
-        let speed: f32 = (rpm as f32) / 60.0 * MOTOR_WHEEL_LENGTH / MOTOR_GEAR as f32 / 10.0;
+        Ok(self.config.speed_register_to_velocity(rpm))
+    }
+
+    // The watchdog only counts the speed once the drive has answered, so if a stop
+    // doesn't get through it still knows to stop the motor
+    pub fn set_rpm(&mut self, speed: i16) -> Result<i16, MotorControllerError> {
+        self.watchdog.feed();
+
+        let speed = self.write_speed(speed)?;
+        self.watchdog.took(speed);
 
         Ok(speed)
     }
 
-    pub fn set_rpm(&mut self, speed: i16) -> Result<i16, MotorControllerError> {
+    // Sets the speed without it counting as a command, for the watchdog's own use
+    fn write_speed(&mut self, speed: i16) -> Result<i16, MotorControllerError> {
         let set_velocity_message = ModbusRequest {
             device_address: self.device_address,
             command: ModbusCommand::WriteRegister,
@@ -178,82 +726,100 @@ impl MotorController {
                     Ok(value as i16)
                 }
             }
-            ModbusResponse::ReadMessage { .. } => {
-                Err(MotorControllerError::IncorrectResponseType)
-            }
+            _ => Err(MotorControllerError::IncorrectResponseType),
         }
     }
 
     pub fn set_velocity(&mut self, speed: f32) -> Result<f32, MotorControllerError> {
-        let rpm: i16 = (speed * 60.0 / MOTOR_WHEEL_LENGTH * MOTOR_GEAR as f32 * 10.0) as i16;
+        let rpm = self.config.velocity_to_speed_register(speed);
 
         let actual_rpm = self.set_rpm(rpm)?;
 
-        Ok(actual_rpm as f32 / 60.0 * MOTOR_WHEEL_LENGTH / MOTOR_GEAR as f32 / 10.0)
+        Ok(self.config.speed_register_to_velocity(actual_rpm))
     }
 
+    // Both halves in one read, so the low word can't roll over between them
     pub fn get_position(&mut self) -> Result<i32, MotorControllerError> {
-        // DATA_LOW
-        let get_position_low = ModbusRequest {
-            device_address: self.device_address,
-            command: ModbusCommand::ReadRegister,
-            register: ModbusRegister::MotorAbsolutePositionLow,
-            value: 0x1,
-        };
+        self.read_i32(ModbusRegister::MotorAbsolutePositionLow)
+    }
 
-        let resp_l: ModbusResponse = self.request(&get_position_low)?;
+    // Where the drive has been told to go, as steps still to go from where it
+    // was when the move was sent
+    pub fn get_target_position(&mut self) -> Result<i32, MotorControllerError> {
+        self.read_i32(ModbusRegister::MotorTargetPositionLow)
+    }
 
-        let low = match resp_l {
-            ModbusResponse::ReadMessage { data, .. } => {
-                if data.len() != 2 {
-                    Err(MotorControllerError::IncorrectDataLength(2, data.len()))
-                } else {
-                    let data_low: u16 = ((data[0] as u16) << 8) | (data[1] as u16);
-                    Ok(data_low)
-                }
-            }
-            _ => Err(MotorControllerError::IncorrectResponseType),
-        }?;
+    fn read_i32(&mut self, low: ModbusRegister) -> Result<i32, MotorControllerError> {
+        let registers = self.read_registers(low, 2)?;
 
-        // DATA_HIGH
-        let get_position_high = ModbusRequest {
-            device_address: self.device_address,
-            command: ModbusCommand::ReadRegister,
-            register: ModbusRegister::MotorAbsolutePositionHigh,
-            value: 0x1,
-        };
+        registers
+            .get_i32(low)
+            .ok_or(MotorControllerError::IncorrectDataLength(4, registers.len() * 2))
+    }
 
-        let resp_h: ModbusResponse = self.request(&get_position_high)?;
+    // Position mode only (SW1 off). The drive counts the target from wherever the
+    // motor is now, so this is a relative move. Both halves go in one transaction
+    // so the drive never acts on half a target, and the target is read back to
+    // make sure it took.
+    pub fn set_position(&mut self, position: i32) -> Result<(), MotorControllerError> {
+        self.write_registers(ModbusRegister::MotorTargetPositionLow, &split_words(position))?;
 
-        let high = match resp_h {
-            ModbusResponse::ReadMessage { data, .. } => {
-                if data.len() != 2 {
-                    Err(MotorControllerError::IncorrectDataLength(2, data.len()))
-                } else {
-                    let data_high: u16 = ((data[0] as u16) << 8) + (data[1] as u16);
-                    Ok(data_high)
-                }
-            }
-            _ => Err(MotorControllerError::IncorrectResponseType),
-        }?;
+        let target = self.get_target_position()?;
 
-        let data = (((high as u32) << 16) | low as u32) as i32;
+        if target != position {
+            Err(MotorControllerError::IncorrectPosition(position, target))
+        } else {
+            Ok(())
+        }
+    }
 
-        Ok(data)
+    pub fn move_by_counts(&mut self, counts: i32) -> Result<(), MotorControllerError> {
+        self.set_position(counts)
     }
 
-    // pub fn set_position(&mut self, position: i32) -> Result<(), Error> {
-    //     // let mut message: [u8; 11] = MOTOR_SET_POSITION_MAGIC_FRAME;
-    //     // message[7] = ((position as u32) >> 8) as u8;
-    //     // message[8] = position as u8;
-    //     // message[9] = ((position as u32) >> 24) as u8;
-    //     // message[10] = ((position as u32) >> 16) as u8;
+    // Writing the absolute position in position mode sends the motor there. Unlike
+    // a relative move this is safe to repeat, so prefer it where possible.
+    // With the electronic gear numerator at 0, writing 0 clears the count rather
+    // than moving (the manual says to send 1 to go back to the origin), so 0 is
+    // sent as 1 whatever the gear. The position is read back to make sure the
+    // count wasn't cleared instead.
+    pub fn move_to_counts(&mut self, position: i32) -> Result<(), MotorControllerError> {
+        let position = if position == 0 { 1 } else { position };
+
+        let before = self.get_position()?;
+        self.write_registers(ModbusRegister::MotorAbsolutePositionLow, &split_words(position))?;
+        let after = self.get_position()?;
+
+        // The move has only just started, so the count jumping to 0 means it was cleared
+        if after == 0 && before != 0 {
+            Err(MotorControllerError::IncorrectPosition(position, after))
+        } else {
+            Ok(())
+        }
+    }
 
-    //     // let _resp: [u8; 6] = self.request(&message)?;
+    // The vendor's 0x78 command: the same relative move as `set_position`, in a
+    // shorter frame. Returns the pulse count the drive answers with, which the
+    // manual suggests is where the motor is rather than an echo of the request.
+    pub fn write_location(&mut self, position: i32) -> Result<i32, MotorControllerError> {
+        let write_location_message = ModbusWriteLocationRequest {
+            device_address: self.device_address,
+            position,
+        };
 
-    //     // Ok(())
-    //     todo!()
-    // }
+        match self.request_write_location(&write_location_message)? {
+            ModbusResponse::WriteLocationMessage { position, .. } => Ok(position),
+            _ => Err(MotorControllerError::IncorrectResponseType),
+        }
+    }
+
+    pub fn move_by(&mut self, distance: f32) -> Result<(), MotorControllerError> {
+        self.move_by_counts(self.config.metres_to_counts(distance))
+    }
+
+    pub fn move_to(&mut self, distance: f32) -> Result<(), MotorControllerError> {
+        self.move_to_counts(self.config.metres_to_counts(distance))
+    }
 
     pub fn get_status(&mut self) -> Result<MotorStatus, MotorControllerError> {
         let get_status = ModbusRequest {
@@ -281,6 +847,40 @@ impl MotorController {
         }
     }
 
+    // Motor current in amps
+    pub fn get_current(&mut self) -> Result<f32, MotorControllerError> {
+        let current = self.read_register(ModbusRegister::MotorI)?;
+
+        Ok(current_amps(current))
+    }
+
+    // Supply voltage in volts
+    pub fn get_voltage(&mut self) -> Result<f32, MotorControllerError> {
+        let voltage = self.read_register(ModbusRegister::MotorV)?;
+
+        Ok(voltage_volts(voltage))
+    }
+
+    // Drive temperature in degrees C
+    pub fn get_temperature(&mut self) -> Result<u16, MotorControllerError> {
+        self.read_register(ModbusRegister::SystemTemperature)
+    }
+
+    // Output duty cycle, -100~100%
+    pub fn get_output_pwm(&mut self) -> Result<f32, MotorControllerError> {
+        let pwm = self.read_register(ModbusRegister::SystemOutputPWM)?;
+
+        Ok(pwm_percent(pwm))
+    }
+
+    // Alarm, current, speed, voltage, temperature and PWM, for the price of one read
+    pub fn get_telemetry(&mut self) -> Result<Telemetry, MotorControllerError> {
+        let start = Instant::now();
+        let registers = self.read_registers(TELEMETRY_START, TELEMETRY_LEN)?;
+
+        Telemetry::from_registers(registers.values(), start + start.elapsed() / 2)
+    }
+
     // Proportional scalar for the motor's speed afaik
     pub fn set_position_gain(&mut self, gain: i16) -> Result<(), MotorControllerError> {
         let set_pos_gain_message = ModbusRequest {
@@ -295,6 +895,16 @@ impl MotorController {
         Ok(())
     }
 
+    // The electronic gear is a ratio, so both halves are written together.
+    // The manual calls the high register the numerator and the low register the denominator
+    pub fn set_electronic_gear(
+        &mut self,
+        numerator: u16,
+        denominator: u16,
+    ) -> Result<(), MotorControllerError> {
+        self.write_registers(ModbusRegister::MotorElectronicGearHigh, &[numerator, denominator])
+    }
+
     // Constant added to the motor's speed
     pub fn set_position_feedforward(&mut self, ff: i16) -> Result<(), MotorControllerError> {
         let set_pos_ff_message = ModbusRequest {
@@ -309,3 +919,18 @@ impl MotorController {
         Ok(())
     }
 }
+
+// Nothing should be left moving once nobody's in charge of it, so a motor
+// that was last told to move is stopped on the way out
+impl Drop for MotorController {
+    fn drop(&mut self) {
+        if let Some((speed, config)) = self.watchdog.shutdown() {
+            watchdog::stop(self, speed, &config);
+        }
+    }
+}
+
+// 32-bit values go over the wire low word first
+fn split_words(value: i32) -> [u16; 2] {
+    [value as u16, ((value as u32) >> 16) as u16]
+}
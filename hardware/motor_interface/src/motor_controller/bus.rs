@@ -0,0 +1,230 @@
+use crate::message::{ModbusFrameDecoder, ModbusResponse, ModbusResponseError};
+use crate::motor_controller::config::MotorConfig;
+use crate::motor_controller::constants::{MOTOR_ADAPTER_LATENCY, MOTOR_BAUD_RATE};
+use crate::motor_controller::error::MotorControllerError;
+use crate::motor_controller::retry::{RetryPolicy, RetryStats};
+use crate::motor_controller::watchdog::Watchdog;
+use crate::motor_controller::MotorController;
+use crate::transport::{SerialTransport, Transport};
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+// Several drives can hang off the same RS-485 pair, each listening on its own
+// address. The bus owns the one port and hands out a MotorController per drive.
+// Controllers can be moved to different threads; their transactions take
+// turns on the wire.
+#[derive(Clone)]
+pub struct MotorBus {
+    line: Arc<Mutex<BusLine>>,
+    retry_policy: RetryPolicy,
+    config: MotorConfig,
+}
+
+impl MotorBus {
+    pub fn new(port_path: &str) -> Result<MotorBus, MotorControllerError> {
+        MotorBus::with_config(port_path, MotorConfig::default())
+    }
+
+    pub fn with_config(
+        port_path: &str,
+        config: MotorConfig,
+    ) -> Result<MotorBus, MotorControllerError> {
+        config.validate()?;
+
+        let port = SerialTransport::open(port_path, config.baud_rate, config.connection_timeout())
+            .map_err(MotorControllerError::SerialError)?;
+
+        let mut bus = MotorBus::from_transport(port);
+        bus.config = config;
+
+        Ok(bus)
+    }
+
+    pub fn from_transport(transport: impl Transport + 'static) -> MotorBus {
+        MotorBus {
+            line: Arc::new(Mutex::new(BusLine::new(Box::new(transport)))),
+            retry_policy: RetryPolicy::default(),
+            config: MotorConfig::default(),
+        }
+    }
+
+    // Every drive on the bus has to be talking at the same rate
+    pub fn set_baud_rate(&self, baud_rate: u32) -> Result<(), MotorControllerError> {
+        let mut line = self.line.lock().unwrap();
+
+        line.transport
+            .set_baud_rate(baud_rate)
+            .map_err(MotorControllerError::IOError)?;
+        line.frame_gap = frame_gap(baud_rate);
+        line.inter_frame_silence = line.frame_gap.max(MOTOR_ADAPTER_LATENCY);
+
+        Ok(())
+    }
+
+    // How long to wait for a drive to answer before giving up on it
+    pub fn set_timeout(&self, timeout: Duration) -> Result<(), MotorControllerError> {
+        self.line
+            .lock()
+            .unwrap()
+            .transport
+            .set_timeout(timeout)
+            .map_err(MotorControllerError::IOError)
+    }
+
+    // What controllers handed out from now on start with. Each can be changed
+    // afterwards with `MotorController::set_retry_policy`
+    pub fn set_retry_policy(&mut self, retry_policy: RetryPolicy) {
+        self.retry_policy = retry_policy;
+    }
+
+    // The geometry controllers handed out from now on start with. As with
+    // `MotorController::set_config`, the port's settings are left alone
+    pub fn set_config(&mut self, config: MotorConfig) -> Result<(), MotorControllerError> {
+        config.validate()?;
+        self.config = config;
+
+        Ok(())
+    }
+
+    // Bytes thrown away as junk while looking for replies
+    pub fn discarded_bytes(&self) -> u64 {
+        self.line.lock().unwrap().decoder.discarded()
+    }
+
+    // A handle on the drive at `device_address`. Nothing is sent, so this works
+    // whether or not anything is actually listening there
+    pub fn controller(&self, device_address: u8) -> Result<MotorController, MotorControllerError> {
+        // 0 is broadcast, which nothing answers, and 248 up are reserved
+        if !(1..=247).contains(&device_address) {
+            return Err(MotorControllerError::InvalidDeviceAddress(device_address));
+        }
+
+        Ok(MotorController {
+            device_address,
+            line: self.line.clone(),
+            retry_policy: self.retry_policy,
+            retry_stats: RetryStats::default(),
+            config: self.config,
+            watchdog: Watchdog::new(device_address),
+        })
+    }
+}
+
+// The port plus what we need to know to keep the bus timing honest
+pub(crate) struct BusLine {
+    pub(crate) transport: Box<dyn Transport>,
+    pub(crate) decoder: ModbusFrameDecoder,
+    frame_gap: Duration,
+    // How long the input has to go quiet before we call it the end of a frame
+    inter_frame_silence: Duration,
+    last_frame: Option<Instant>,
+}
+
+impl BusLine {
+    pub(crate) fn new(transport: Box<dyn Transport>) -> BusLine {
+        // Not every port can say, in which case it's most likely the default
+        let baud_rate = transport.baud_rate().unwrap_or(MOTOR_BAUD_RATE);
+
+        BusLine {
+            transport,
+            decoder: ModbusFrameDecoder::new(),
+            frame_gap: frame_gap(baud_rate),
+            inter_frame_silence: frame_gap(baud_rate).max(MOTOR_ADAPTER_LATENCY),
+            last_frame: None,
+        }
+    }
+
+    // Drives find the end of a frame by the line going quiet, so the next
+    // request can't go out until the last frame is at least a gap old
+    fn wait_for_silence(&self) {
+        if let Some(last_frame) = self.last_frame {
+            let quiet = last_frame.elapsed();
+            if quiet < self.frame_gap {
+                thread::sleep(self.frame_gap - quiet);
+            }
+        }
+    }
+
+    fn end_of_frame(&mut self) {
+        self.last_frame = Some(Instant::now());
+    }
+
+    // One request and its reply. Whatever was left over from before is thrown
+    // away first, so a late reply to an earlier try can't be taken for this one
+    pub(crate) fn exchange(
+        &mut self,
+        frame: &[u8],
+    ) -> Result<ModbusResponse, MotorControllerError> {
+        self.wait_for_silence();
+
+        self.transport
+            .clear_buffers()
+            .map_err(MotorControllerError::IOError)?;
+        self.decoder.silence();
+        self.transport
+            .write_all(frame)
+            .map_err(MotorControllerError::IOError)?;
+        self.transport
+            .flush()
+            .map_err(MotorControllerError::IOError)?;
+
+        let response = self.read_response();
+        self.end_of_frame();
+
+        response.map_err(MotorControllerError::ResponseError)
+    }
+
+    // Read until a whole reply turns up, skipping over anything that isn't one,
+    // or until the port's timeout has passed without one
+    fn read_response(&mut self) -> Result<ModbusResponse, ModbusResponseError> {
+        let deadline = Instant::now() + self.transport.timeout();
+        let mut last_read: Option<Instant> = None;
+        let mut buf = [0u8; 64];
+
+        loop {
+            if let Some(frame) = self.decoder.next_frame() {
+                return ModbusResponse::from_reader(&mut &frame[..]);
+            }
+
+            if Instant::now() >= deadline {
+                return Err(ModbusResponseError::IOError(std::io::Error::new(
+                    std::io::ErrorKind::TimedOut,
+                    "No valid response before the timeout",
+                )));
+            }
+
+            match self.transport.read(&mut buf) {
+                Ok(0) => {
+                    return Err(ModbusResponseError::IOError(
+                        std::io::ErrorKind::UnexpectedEof.into(),
+                    ))
+                }
+                Ok(n) => {
+                    let now = Instant::now();
+                    if let Some(last_read) = last_read {
+                        if now - last_read > self.inter_frame_silence {
+                            self.decoder.silence();
+                        }
+                    }
+                    last_read = Some(now);
+
+                    self.decoder.push(&buf[..n]);
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(ModbusResponseError::IOError(e)),
+            }
+        }
+    }
+}
+
+// Modbus RTU wants 3.5 characters of silence between frames, counting 11 bits
+// a character. Above 19200 baud the spec pins it at 1.75ms instead
+fn frame_gap(baud_rate: u32) -> Duration {
+    if baud_rate > 19_200 {
+        Duration::from_micros(1_750)
+    } else {
+        Duration::from_micros(3_500_000 * 11 / baud_rate.max(1) as u64)
+    }
+}
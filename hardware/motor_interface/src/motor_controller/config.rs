@@ -0,0 +1,186 @@
+use crate::message::ModbusRegister;
+use crate::motor_controller::constants::{
+    MOTOR_BAUD_RATE, MOTOR_CONNECTION_TIMEOUT, MOTOR_ENCODER_COUNT, MOTOR_GEAR, MOTOR_MAX_RPM,
+    MOTOR_WHEEL_DIST, MOTOR_WHEEL_LENGTH,
+};
+use serde::Deserialize;
+use std::path::Path;
+use std::time::Duration;
+use thiserror::Error;
+
+// Speed registers are in tenths of an RPM
+const SPEED_REGISTER_SCALE: f32 = 10.0;
+
+// The robot's wheels, gearbox and wiring. The defaults are the constants this
+// library was built around, so only what's different needs setting. Field
+// names double as the keys in config files and ros2_control parameters.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MotorConfig {
+    // Motor turns per wheel turn
+    pub gear: u32,
+    // Distance the wheel rolls in one turn, in metres
+    pub wheel_length: f32,
+    // Encoder counts per motor turn
+    pub encoder_count: u32,
+    // Between the middles of the two wheels, in metres
+    pub wheel_separation: f32,
+    // Fastest the motor is asked to turn, before the gearbox
+    pub max_rpm: u32,
+    pub baud_rate: u32,
+    // How long to wait for a drive to answer
+    pub connection_timeout_ms: u32,
+}
+
+#[derive(Debug, Error)]
+pub enum ConfigError {
+    #[error("{0} {1}")]
+    Invalid(&'static str, &'static str),
+    #[error("No parameter called {0}")]
+    UnknownParameter(String),
+    #[error("Can't use {1:?} for {0}: {2}")]
+    BadValue(String, String, String),
+    #[error("Couldn't read config file! {0}")]
+    IOError(#[from] std::io::Error),
+    #[error("Bad TOML config! {0}")]
+    Toml(#[from] toml::de::Error),
+    #[error("Bad YAML config! {0}")]
+    Yaml(#[from] serde_yaml::Error),
+    #[error("Don't know how to read {0}, expected .toml, .yaml or .yml")]
+    UnknownFormat(String),
+}
+
+pub(crate) fn parse<T: std::str::FromStr>(key: &str, value: &str) -> Result<T, ConfigError>
+where
+    T::Err: std::fmt::Display,
+{
+    value
+        .trim()
+        .parse()
+        .map_err(|e: T::Err| ConfigError::BadValue(key.into(), value.into(), e.to_string()))
+}
+
+impl Default for MotorConfig {
+    fn default() -> Self {
+        MotorConfig {
+            gear: MOTOR_GEAR,
+            wheel_length: MOTOR_WHEEL_LENGTH,
+            encoder_count: MOTOR_ENCODER_COUNT,
+            wheel_separation: MOTOR_WHEEL_DIST,
+            max_rpm: MOTOR_MAX_RPM,
+            baud_rate: MOTOR_BAUD_RATE,
+            connection_timeout_ms: MOTOR_CONNECTION_TIMEOUT.as_millis() as u32,
+        }
+    }
+}
+
+impl MotorConfig {
+    // Picks the format from the extension
+    pub fn load(path: impl AsRef<Path>) -> Result<MotorConfig, ConfigError> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path)?;
+
+        let config = match path.extension().and_then(|e| e.to_str()) {
+            Some("toml") => MotorConfig::from_toml(&text)?,
+            Some("yaml" | "yml") => MotorConfig::from_yaml(&text)?,
+            _ => return Err(ConfigError::UnknownFormat(path.display().to_string())),
+        };
+
+        Ok(config)
+    }
+
+    pub fn from_toml(text: &str) -> Result<MotorConfig, ConfigError> {
+        let config: MotorConfig = toml::from_str(text)?;
+        config.validate()?;
+
+        Ok(config)
+    }
+
+    pub fn from_yaml(text: &str) -> Result<MotorConfig, ConfigError> {
+        let config: MotorConfig = serde_yaml::from_str(text)?;
+        config.validate()?;
+
+        Ok(config)
+    }
+
+    // One setting by name, as ros2_control hands them over. Nothing changes if
+    // the value is no good, but it isn't checked against the other settings
+    // until `validate`.
+    pub fn set(&mut self, key: &str, value: &str) -> Result<(), ConfigError> {
+        match key {
+            "gear" => self.gear = parse(key, value)?,
+            "wheel_length" => self.wheel_length = parse(key, value)?,
+            "encoder_count" => self.encoder_count = parse(key, value)?,
+            "wheel_separation" => self.wheel_separation = parse(key, value)?,
+            "max_rpm" => self.max_rpm = parse(key, value)?,
+            "baud_rate" => self.baud_rate = parse(key, value)?,
+            "connection_timeout_ms" => self.connection_timeout_ms = parse(key, value)?,
+            _ => return Err(ConfigError::UnknownParameter(key.into())),
+        }
+
+        Ok(())
+    }
+
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let positive = |value: f32| value.is_finite() && value > 0.0;
+
+        if self.gear == 0 {
+            Err(ConfigError::Invalid("gear", "must be at least 1"))
+        } else if !positive(self.wheel_length) {
+            Err(ConfigError::Invalid("wheel_length", "must be more than 0"))
+        } else if self.encoder_count == 0 {
+            Err(ConfigError::Invalid("encoder_count", "must be at least 1"))
+        } else if !positive(self.wheel_separation) {
+            Err(ConfigError::Invalid(
+                "wheel_separation",
+                "must be more than 0",
+            ))
+        } else if self.max_rpm == 0
+            || self.max_rpm as f32 * SPEED_REGISTER_SCALE
+                > ModbusRegister::MotorTargetSpeed.info().max as f32
+        {
+            Err(ConfigError::Invalid("max_rpm", "must be in 1~3000"))
+        } else if self.baud_rate == 0 {
+            Err(ConfigError::Invalid("baud_rate", "must be more than 0"))
+        } else if self.connection_timeout_ms == 0 {
+            Err(ConfigError::Invalid(
+                "connection_timeout_ms",
+                "must be more than 0",
+            ))
+        } else {
+            Ok(())
+        }
+    }
+
+    pub fn connection_timeout(&self) -> Duration {
+        Duration::from_millis(self.connection_timeout_ms as u64)
+    }
+
+    // Fastest a wheel goes at `max_rpm`, in m/s
+    pub fn max_wheel_speed(&self) -> f32 {
+        self.max_rpm as f32 / 60.0 * self.wheel_length / self.gear as f32
+    }
+
+    pub fn metres_per_count(&self) -> f64 {
+        self.wheel_length as f64 / (self.encoder_count as f64 * self.gear as f64)
+    }
+
+    // Speed register value (tenths of an RPM at the motor) for a speed at the wheel
+    pub(crate) fn velocity_to_speed_register(&self, speed: f32) -> i16 {
+        (speed * 60.0 / self.wheel_length * self.gear as f32 * SPEED_REGISTER_SCALE) as i16
+    }
+
+    pub(crate) fn speed_register_to_velocity(&self, rpm: i16) -> f32 {
+        rpm as f32 / 60.0 * self.wheel_length / self.gear as f32 / SPEED_REGISTER_SCALE
+    }
+
+    // Encoder counts at the motor, through the gearbox, to distance at the wheel
+    pub(crate) fn metres_to_counts(&self, distance: f32) -> i32 {
+        (distance / self.wheel_length * self.gear as f32 * self.encoder_count as f32).round() as i32
+    }
+
+    pub(crate) fn counts_to_metres(&self, counts: i32) -> f32 {
+        counts as f32 / self.encoder_count as f32 / self.gear as f32 * self.wheel_length
+    }
+}
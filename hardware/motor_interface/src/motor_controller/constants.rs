@@ -1,12 +1,20 @@
 use std::time::Duration;
 
 // MOTOR CONNECTION CONSTANTS
-pub(super) const MOTOR_BAUD_RATE: u32 = 19_200;
-pub(super) const MOTOR_CONNECTION_TIMEOUT: Duration = Duration::from_secs(1);
+pub(crate) const MOTOR_BAUD_RATE: u32 = 19_200;
+pub(crate) const MOTOR_CONNECTION_TIMEOUT: Duration = Duration::from_secs(1);
+// The FT232R holds on to received bytes for up to its latency timer before
+// passing them along, so gaps shorter than this can't be told from a slow USB
+pub(crate) const MOTOR_ADAPTER_LATENCY: Duration = Duration::from_millis(16);
+// The manual doesn't say how long a save to flash takes, so give it plenty
+pub(crate) const MOTOR_FLASH_WRITE_TIMEOUT: Duration = Duration::from_secs(1);
+pub(crate) const MOTOR_FLASH_WRITE_POLL: Duration = Duration::from_millis(10);
 
 // MOTOR MAGIC CONSTANTS
 // PHYSICAL
-pub(super) const MOTOR_GEAR: u32 = 16;
-pub(super) const MOTOR_WHEEL_LENGTH: f32 = 0.5843362;
-pub(super) const MOTOR_ENCODER_COUNT: u32 = 4000;
-pub(super) const MOTOR_WHEEL_DIST: f32 = 0.48342;
+pub(crate) const MOTOR_GEAR: u32 = 16;
+pub(crate) const MOTOR_WHEEL_LENGTH: f32 = 0.5843362;
+pub(crate) const MOTOR_ENCODER_COUNT: u32 = 4000;
+// Rated speed of the motor, before the gearbox
+pub(crate) const MOTOR_MAX_RPM: u32 = 3000;
+pub(crate) const MOTOR_WHEEL_DIST: f32 = 0.48342;
@@ -0,0 +1,434 @@
+use crate::motor_controller::config::{parse, ConfigError, MotorConfig};
+use crate::motor_controller::error::MotorControllerError;
+use crate::motor_controller::shaper::{MotionLimits, SpeedShaper};
+use crate::motor_controller::watchdog::{WatchdogConfig, WatchdogTrip};
+use crate::motor_controller::MotorController;
+use std::time::{Duration, Instant};
+use thiserror::Error;
+
+// Which way a wheel turns for a positive speed. The two motors are mounted
+// facing each other, so usually one of them needs reversing (unless the drive's
+// own MotorDirectionPolarity register has already done it).
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum WheelPolarity {
+    #[default]
+    Forward,
+    Reversed,
+}
+
+impl WheelPolarity {
+    // Works both ways, from the base's idea of forward to the drive's and back
+    fn apply(self, value: f32) -> f32 {
+        match self {
+            WheelPolarity::Forward => value,
+            WheelPolarity::Reversed => -value,
+        }
+    }
+
+    fn apply_counts(self, counts: i32) -> i32 {
+        match self {
+            WheelPolarity::Forward => counts,
+            WheelPolarity::Reversed => counts.wrapping_neg(),
+        }
+    }
+
+    fn wheel_state(self, config: &MotorConfig, velocity: f32, counts: i32) -> WheelState {
+        let counts = self.apply_counts(counts);
+
+        WheelState {
+            velocity: self.apply(velocity),
+            position: config.counts_to_metres(counts),
+            counts,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct DiffDriveConfig {
+    // Shared by both wheels
+    pub motor: MotorConfig,
+    pub left_polarity: WheelPolarity,
+    pub right_polarity: WheelPolarity,
+    // Limits for `tick`. Each wheel is held to its own after the twist has
+    // been shaped, as a backstop
+    pub wheel_limits: MotionLimits,
+    pub linear_limits: MotionLimits,
+    pub angular_limits: MotionLimits,
+}
+
+impl DiffDriveConfig {
+    // As `MotorConfig::set`, plus the polarities and the limits, e.g.
+    // `wheel_jerk` or `linear_emergency_deceleration`
+    pub fn set(&mut self, key: &str, value: &str) -> Result<(), ConfigError> {
+        if let Some(limit) = self.limit(key) {
+            *limit = parse(key, value)?;
+            return Ok(());
+        }
+
+        let polarity = match key {
+            "left_polarity" => &mut self.left_polarity,
+            "right_polarity" => &mut self.right_polarity,
+            _ => return self.motor.set(key, value),
+        };
+
+        *polarity = match value.trim() {
+            "forward" => WheelPolarity::Forward,
+            "reversed" => WheelPolarity::Reversed,
+            _ => {
+                return Err(ConfigError::BadValue(
+                    key.into(),
+                    value.into(),
+                    "expected forward or reversed".into(),
+                ))
+            }
+        };
+
+        Ok(())
+    }
+
+    fn limit(&mut self, key: &str) -> Option<&mut f32> {
+        let (group, name) = key.split_once('_')?;
+        let limits = match group {
+            "wheel" => &mut self.wheel_limits,
+            "linear" => &mut self.linear_limits,
+            "angular" => &mut self.angular_limits,
+            _ => return None,
+        };
+
+        limits.field(name)
+    }
+
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        self.motor.validate()?;
+
+        if !self.wheel_limits.is_valid() {
+            Err(ConfigError::Invalid(
+                "wheel_limits",
+                "must all be 0 or more",
+            ))
+        } else if !self.linear_limits.is_valid() {
+            Err(ConfigError::Invalid(
+                "linear_limits",
+                "must all be 0 or more",
+            ))
+        } else if !self.angular_limits.is_valid() {
+            Err(ConfigError::Invalid(
+                "angular_limits",
+                "must all be 0 or more",
+            ))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+// Forward speed of each wheel in m/s, after polarity has been taken out
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct WheelSpeeds {
+    pub left: f32,
+    pub right: f32,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct WheelState {
+    // m/s, forwards positive
+    pub velocity: f32,
+    // Distance the wheel has rolled since the drive powered up, in metres
+    pub position: f32,
+    // The same as raw encoder counts, which wrap around rather than losing precision
+    pub counts: i32,
+}
+
+// Both wheels read back to back. `taken_at` is halfway through, which is as
+// close as the bus gets to reading them at the same moment.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DiffDriveSample {
+    pub left: WheelState,
+    pub right: WheelState,
+    pub taken_at: Instant,
+}
+
+#[derive(Debug, Error)]
+pub enum DiffDriveError {
+    #[error("Left wheel: {0}")]
+    Left(#[source] MotorControllerError),
+    #[error("Right wheel: {0}")]
+    Right(#[source] MotorControllerError),
+    #[error("Bad diff drive config! {0}")]
+    InvalidConfig(#[from] ConfigError),
+}
+
+impl DiffDriveError {
+    pub fn controller_error(&self) -> Option<&MotorControllerError> {
+        match self {
+            DiffDriveError::Left(e) | DiffDriveError::Right(e) => Some(e),
+            DiffDriveError::InvalidConfig(_) => None,
+        }
+    }
+}
+
+// Turns a body twist into wheel speeds. If either wheel would be over the limit
+// both are scaled down together, so the base follows the same arc, just slower.
+// Anything that isn't a number stops the base.
+pub fn wheel_speeds(config: &DiffDriveConfig, linear: f32, angular: f32) -> WheelSpeeds {
+    if !linear.is_finite() || !angular.is_finite() {
+        return WheelSpeeds::default();
+    }
+
+    let half_turn = angular * config.motor.wheel_separation / 2.0;
+    let left = linear - half_turn;
+    let right = linear + half_turn;
+
+    let max_wheel_speed = config.motor.max_wheel_speed();
+    let fastest = left.abs().max(right.abs());
+    let scale = if fastest > max_wheel_speed {
+        max_wheel_speed / fastest
+    } else {
+        1.0
+    };
+
+    WheelSpeeds {
+        left: left * scale,
+        right: right * scale,
+    }
+}
+
+// The pair of drives under a differential-drive robot
+pub struct DiffDriveBase {
+    left: MotorController,
+    right: MotorController,
+    config: DiffDriveConfig,
+    linear_shaper: SpeedShaper,
+    angular_shaper: SpeedShaper,
+    left_shaper: SpeedShaper,
+    right_shaper: SpeedShaper,
+}
+
+impl DiffDriveBase {
+    pub fn new(
+        left_port: &str,
+        left_address: u8,
+        right_port: &str,
+        right_address: u8,
+        config: DiffDriveConfig,
+    ) -> Result<DiffDriveBase, DiffDriveError> {
+        let left = MotorController::with_config(left_port, left_address, config.motor)
+            .map_err(DiffDriveError::Left)?;
+        let right = MotorController::with_config(right_port, right_address, config.motor)
+            .map_err(DiffDriveError::Right)?;
+
+        DiffDriveBase::from_controllers(left, right, config)
+    }
+
+    // For drives that are already set up, e.g. both on one MotorBus. Both are
+    // switched over to `config.motor`
+    pub fn from_controllers(
+        mut left: MotorController,
+        mut right: MotorController,
+        config: DiffDriveConfig,
+    ) -> Result<DiffDriveBase, DiffDriveError> {
+        config.validate()?;
+        let motor = config.motor;
+        left.set_config(motor).map_err(DiffDriveError::Left)?;
+        right.set_config(motor).map_err(DiffDriveError::Right)?;
+
+        Ok(DiffDriveBase {
+            left,
+            right,
+            config,
+            linear_shaper: SpeedShaper::new(config.linear_limits),
+            angular_shaper: SpeedShaper::new(config.angular_limits),
+            left_shaper: SpeedShaper::new(config.wheel_limits),
+            right_shaper: SpeedShaper::new(config.wheel_limits),
+        })
+    }
+
+    pub fn config(&self) -> DiffDriveConfig {
+        self.config
+    }
+
+    pub fn left(&mut self) -> &mut MotorController {
+        &mut self.left
+    }
+
+    pub fn right(&mut self) -> &mut MotorController {
+        &mut self.right
+    }
+
+    // The same watchdog on both wheels. `set_twist` feeds them both
+    pub fn set_watchdog(&mut self, config: Option<WatchdogConfig>) {
+        self.left.set_watchdog(config);
+        self.right.set_watchdog(config);
+    }
+
+    // Left wheel's trips come out first
+    pub fn take_watchdog_trip(&mut self) -> Option<WatchdogTrip> {
+        self.left
+            .take_watchdog_trip()
+            .or_else(|| self.right.take_watchdog_trip())
+    }
+
+    pub fn enable(&mut self) -> Result<(), DiffDriveError> {
+        self.left.enable_modbus().map_err(DiffDriveError::Left)?;
+        self.right.enable_modbus().map_err(DiffDriveError::Right)?;
+        self.left
+            .set_motor_enabled()
+            .map_err(DiffDriveError::Left)?;
+        self.right
+            .set_motor_enabled()
+            .map_err(DiffDriveError::Right)?;
+
+        Ok(())
+    }
+
+    // Both sides are always tried, the first failure is the one reported
+    pub fn disable(&mut self) -> Result<(), DiffDriveError> {
+        let left = self.left.set_motor_disabled().map_err(DiffDriveError::Left);
+        let right = self
+            .right
+            .set_motor_disabled()
+            .map_err(DiffDriveError::Right);
+
+        left.and(right)
+    }
+
+    pub fn stop(&mut self) -> Result<(), DiffDriveError> {
+        self.reset_shapers(WheelSpeeds::default());
+
+        let left = self.left.set_velocity(0.0).map_err(DiffDriveError::Left);
+        let right = self.right.set_velocity(0.0).map_err(DiffDriveError::Right);
+
+        left.and(right).map(|_| ())
+    }
+
+    // Linear in m/s and angular in rad/s, anticlockwise positive. Goes out
+    // straight away, without the limits. Returns the speeds the drives settled
+    // on. If the right wheel can't be told, the left one is stopped again
+    // rather than left driving the robot in circles.
+    pub fn set_twist(&mut self, linear: f32, angular: f32) -> Result<WheelSpeeds, DiffDriveError> {
+        let speeds = self.write_speeds(wheel_speeds(&self.config, linear, angular))?;
+        self.reset_shapers(speeds);
+
+        Ok(speeds)
+    }
+
+    // What `tick` heads for, within the limits
+    pub fn set_twist_target(&mut self, linear: f32, angular: f32) {
+        self.linear_shaper.set_target(linear);
+        self.angular_shaper.set_target(angular);
+    }
+
+    // Call once per control loop. Moves the twist on by `dt` towards its
+    // target, and then each wheel towards the speed that twist needs, and
+    // sends the result as `set_twist` does.
+    pub fn tick(&mut self, dt: Duration) -> Result<WheelSpeeds, DiffDriveError> {
+        // The watchdog has stopped the wheels since the last tick, so start
+        // from there rather than jumping back to speed
+        if self.left.is_watchdog_tripped() || self.right.is_watchdog_tripped() {
+            self.reset_shapers(WheelSpeeds::default());
+        }
+
+        let linear = self.linear_shaper.tick(dt);
+        let angular = self.angular_shaper.tick(dt);
+        let speeds = wheel_speeds(&self.config, linear, angular);
+
+        self.left_shaper.set_target(speeds.left);
+        self.right_shaper.set_target(speeds.right);
+        let speeds = WheelSpeeds {
+            left: self.left_shaper.tick(dt),
+            right: self.right_shaper.tick(dt),
+        };
+
+        self.write_speeds(speeds)
+    }
+
+    // Brakes at the emergency limits on the following ticks, whatever the
+    // target, until `resume`. The ticks have to keep coming for it to stop.
+    // The wheels follow the twist down, braking as hard as their own
+    // emergency limit allows rather than stopping short of it.
+    pub fn emergency_stop(&mut self) {
+        self.linear_shaper.emergency_stop();
+        self.angular_shaper.emergency_stop();
+
+        let limits = MotionLimits {
+            deceleration: self.config.wheel_limits.emergency_deceleration,
+            jerk: 0.0,
+            ..self.config.wheel_limits
+        };
+        self.left_shaper.set_limits(limits);
+        self.right_shaper.set_limits(limits);
+    }
+
+    pub fn is_emergency_stopped(&self) -> bool {
+        self.linear_shaper.is_emergency_stopped()
+    }
+
+    // Ramps back up to the target from wherever the speed has got to
+    pub fn resume(&mut self) {
+        self.linear_shaper.resume();
+        self.angular_shaper.resume();
+        self.left_shaper.set_limits(self.config.wheel_limits);
+        self.right_shaper.set_limits(self.config.wheel_limits);
+    }
+
+    // The wheels were set some other way. Carry on from there
+    fn reset_shapers(&mut self, speeds: WheelSpeeds) {
+        self.linear_shaper.reset((speeds.left + speeds.right) / 2.0);
+        self.angular_shaper
+            .reset((speeds.right - speeds.left) / self.config.motor.wheel_separation);
+        self.left_shaper.reset(speeds.left);
+        self.right_shaper.reset(speeds.right);
+    }
+
+    fn write_speeds(&mut self, speeds: WheelSpeeds) -> Result<WheelSpeeds, DiffDriveError> {
+        let left = self
+            .left
+            .set_velocity(self.config.left_polarity.apply(speeds.left))
+            .map_err(DiffDriveError::Left)?;
+        let right = match self
+            .right
+            .set_velocity(self.config.right_polarity.apply(speeds.right))
+        {
+            Ok(right) => right,
+            Err(e) => {
+                let _ = self.left.set_velocity(0.0);
+                return Err(DiffDriveError::Right(e));
+            }
+        };
+
+        Ok(WheelSpeeds {
+            left: self.config.left_polarity.apply(left),
+            right: self.config.right_polarity.apply(right),
+        })
+    }
+
+    // Velocities first since they change fastest, then positions
+    pub fn sample(&mut self) -> Result<DiffDriveSample, DiffDriveError> {
+        let start = Instant::now();
+
+        let left_velocity = self.left.get_velocity().map_err(DiffDriveError::Left)?;
+        let right_velocity = self.right.get_velocity().map_err(DiffDriveError::Right)?;
+        let left_position = self.left.get_position().map_err(DiffDriveError::Left)?;
+        let right_position = self.right.get_position().map_err(DiffDriveError::Right)?;
+
+        let taken_at = start + start.elapsed() / 2;
+
+        Ok(DiffDriveSample {
+            left: self.config.left_polarity.wheel_state(
+                &self.config.motor,
+                left_velocity,
+                left_position,
+            ),
+            right: self.config.right_polarity.wheel_state(
+                &self.config.motor,
+                right_velocity,
+                right_position,
+            ),
+            taken_at,
+        })
+    }
+}
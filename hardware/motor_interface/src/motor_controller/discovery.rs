@@ -0,0 +1,118 @@
+use crate::message::ModbusRegister;
+use crate::motor_controller::bus::MotorBus;
+use crate::motor_controller::constants::MOTOR_BAUD_RATE;
+use crate::motor_controller::error::MotorControllerError;
+use crate::motor_controller::motor_status::MotorStatus;
+use crate::motor_controller::retry::RetryPolicy;
+use crate::transport::{SerialTransport, Transport};
+use std::ops::RangeInclusive;
+use std::time::Duration;
+
+// The rates the drive can be set to, most likely first
+pub const DISCOVERY_BAUD_RATES: [u32; 5] = [MOTOR_BAUD_RATE, 9_600, 38_400, 57_600, 115_200];
+
+// A reply to a one register read is 7 bytes, about 8ms at 9600 baud, so this
+// leaves the drive a little time to think
+pub const DISCOVERY_TIMEOUT: Duration = Duration::from_millis(30);
+
+// What to try when looking for drives. The defaults try everything, which is
+// 247 addresses at each rate and takes a while on a port with nothing on it.
+#[derive(Debug, Clone)]
+pub struct DiscoveryOptions {
+    pub addresses: RangeInclusive<u8>,
+    pub baud_rates: Vec<u32>,
+    pub timeout: Duration,
+}
+
+impl Default for DiscoveryOptions {
+    fn default() -> Self {
+        DiscoveryOptions {
+            addresses: 1..=247,
+            baud_rates: DISCOVERY_BAUD_RATES.to_vec(),
+            timeout: DISCOVERY_TIMEOUT,
+        }
+    }
+}
+
+// A drive that answered, and what it had to say for itself
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiscoveredDrive {
+    pub baud_rate: u32,
+    pub device_address: u8,
+    // None if the drive wouldn't say
+    pub status: Option<MotorStatus>,
+    pub parameters_saved: Option<bool>,
+}
+
+// Everything found on one serial port
+#[derive(Debug)]
+pub struct PortReport {
+    pub port_name: String,
+    // Err if the port couldn't be opened or used at all
+    pub drives: Result<Vec<DiscoveredDrive>, MotorControllerError>,
+}
+
+// Look for drives on every serial port on the machine
+pub fn discover_ports(options: &DiscoveryOptions) -> Result<Vec<PortReport>, MotorControllerError> {
+    let ports = serialport::available_ports().map_err(MotorControllerError::SerialError)?;
+
+    Ok(ports
+        .into_iter()
+        .map(|port| {
+            let drives = SerialTransport::open(&port.port_name, MOTOR_BAUD_RATE, options.timeout)
+                .map_err(MotorControllerError::SerialError)
+                .and_then(|transport| discover(transport, options));
+
+            PortReport {
+                port_name: port.port_name,
+                drives,
+            }
+        })
+        .collect())
+}
+
+// Look for drives on one port. Only reads are sent, so nothing that answers
+// has its settings touched.
+pub fn discover(
+    transport: impl Transport + 'static,
+    options: &DiscoveryOptions,
+) -> Result<Vec<DiscoveredDrive>, MotorControllerError> {
+    // Most addresses won't answer, so trying them again would only slow things down
+    let mut bus = MotorBus::from_transport(transport);
+    bus.set_retry_policy(RetryPolicy::never());
+    bus.set_timeout(options.timeout)?;
+
+    let mut found = Vec::new();
+
+    for &baud_rate in options.baud_rates.iter() {
+        bus.set_baud_rate(baud_rate)?;
+
+        for device_address in options.addresses.clone() {
+            let mut controller = match bus.controller(device_address) {
+                Ok(controller) => controller,
+                // Reserved addresses can't have anything on them
+                Err(_) => continue,
+            };
+
+            // Anything that goes wrong here means nobody home
+            if controller
+                .read_register(ModbusRegister::DeviceAddress)
+                .is_err()
+            {
+                continue;
+            }
+
+            found.push(DiscoveredDrive {
+                baud_rate,
+                device_address,
+                status: controller.get_status().ok(),
+                parameters_saved: controller
+                    .read_register(ModbusRegister::ParameterSavingFlag)
+                    .ok()
+                    .map(|flag| flag == 2),
+            });
+        }
+    }
+
+    Ok(found)
+}
@@ -1,4 +1,5 @@
-use crate::message::{ModbusRegister, ModbusResponseError};
+use crate::message::{ModbusException, ModbusRegister, ModbusResponseError, RegisterWriteError};
+use crate::motor_controller::config::ConfigError;
 use crate::motor_controller::motor_status::MotorStatusParseError;
 use serialport::Error as SerialError;
 
@@ -16,14 +17,32 @@ pub enum MotorControllerError {
     IOError(#[from] std::io::Error),
     #[error("Invalid client responded to host. Expected {0}, got {1}")]
     InvalidResponder(u8, u8),
+    #[error("Device address {0} is not in 1~247")]
+    InvalidDeviceAddress(u8),
     #[error("Expected data of length {0}, got {1}")]
     IncorrectDataLength(usize, usize),
+    #[error("Device refused function {0:#04x}: {1}")]
+    ModbusException(u8, ModbusException),
+    #[error("Can't read {1} registers from {0:?}")]
+    InvalidRegisterRange(ModbusRegister, usize),
+    #[error("Refused to write register! {0}")]
+    InvalidWrite(#[from] RegisterWriteError),
     #[error("Incorrect response type")]
     IncorrectResponseType,
     #[error("Incorrect response register. Expected {0:?}, got {1:?}")]
     IncorrectResponseRegister(ModbusRegister, ModbusRegister),
     #[error("Expected value {0}, got {1}")]
     IncorrectResponseValue(u16, u16),
+    #[error("Expected target position {0}, got {1}")]
+    IncorrectPosition(i32, i32),
+    #[error("{0:?} didn't take, expected {1}, read back {2}")]
+    ProfileMismatch(ModbusRegister, u16, u16),
+    #[error("Drive couldn't save its parameters to flash")]
+    FlashWriteFailed,
+    #[error("Drive was still saving its parameters after {0:?}")]
+    FlashWriteTimedOut(std::time::Duration),
     #[error("Failed to parse motor status {0}")]
     MotorStatusParseError(#[from] MotorStatusParseError),
+    #[error("Invalid motor config! {0}")]
+    InvalidConfig(#[from] ConfigError),
 }
@@ -1,20 +1,20 @@
 
 use thiserror::Error;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum MotorStatus {
     None,
     Warning(MotorStatusWarning),
     Fatal(MotorStatusFatal),
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum MotorStatusWarning {
     HighTemperature,
     FlashWriteFailed,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum MotorStatusFatal {
     Overheat,
     SystemStall,
@@ -48,3 +48,17 @@ impl TryFrom<u16> for MotorStatus {
         }
     }
 }
+
+impl From<MotorStatus> for u16 {
+    fn from(value: MotorStatus) -> Self {
+        match value {
+            MotorStatus::Fatal(MotorStatusFatal::Overheat) => 0x11,
+            MotorStatus::Fatal(MotorStatusFatal::SystemStall) => 0x12,
+            MotorStatus::Fatal(MotorStatusFatal::UnderVoltage) => 0x13,
+            MotorStatus::Fatal(MotorStatusFatal::LoadTooHeavy) => 0x14,
+            MotorStatus::Warning(MotorStatusWarning::HighTemperature) => 0x10,
+            MotorStatus::Warning(MotorStatusWarning::FlashWriteFailed) => 0x20,
+            MotorStatus::None => 0x0,
+        }
+    }
+}
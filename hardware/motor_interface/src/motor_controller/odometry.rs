@@ -0,0 +1,224 @@
+use crate::motor_controller::config::MotorConfig;
+use crate::motor_controller::diff_drive::DiffDriveSample;
+use std::f64::consts::PI;
+use std::time::{Duration, Instant};
+
+// Below this the turn is treated as a straight line, which is what the arc
+// tends to anyway and doesn't divide by nearly nothing
+const STRAIGHT_LINE_TURN: f64 = 1e-9;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OdometryConfig {
+    // Between the middles of the two wheels, in metres
+    pub wheel_separation: f64,
+    // Distance a wheel rolls per encoder count
+    pub metres_per_count: f64,
+    // How much a wheel's travel can't be trusted, as variance (m^2) per metre
+    // rolled. Covers slip, squashed tyres, a wheel that isn't quite the size
+    // we think, ...
+    pub slip_variance: f64,
+}
+
+impl Default for OdometryConfig {
+    fn default() -> Self {
+        OdometryConfig::from(&MotorConfig::default())
+    }
+}
+
+impl From<&MotorConfig> for OdometryConfig {
+    fn from(value: &MotorConfig) -> Self {
+        OdometryConfig {
+            wheel_separation: value.wheel_separation as f64,
+            metres_per_count: value.metres_per_count(),
+            slip_variance: 1e-3,
+        }
+    }
+}
+
+// Where the robot is relative to where odometry started, theta anticlockwise
+// in -pi~pi
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Pose {
+    pub x: f64,
+    pub y: f64,
+    pub theta: f64,
+}
+
+// In the robot's own frame, so there's never any sideways speed
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Twist {
+    pub linear: f64,
+    pub angular: f64,
+}
+
+// Dead reckoning from the wheel encoders. Feed it each new pair of counts and
+// it follows the arc the wheels must have driven between them.
+#[derive(Debug, Clone)]
+pub struct Odometry {
+    config: OdometryConfig,
+    last_counts: Option<(i32, i32)>,
+    last_sample: Option<Instant>,
+    pose: Pose,
+    twist: Twist,
+    // Over (x, y, theta)
+    pose_covariance: [[f64; 3]; 3],
+    // Over (linear, angular)
+    twist_covariance: [[f64; 2]; 2],
+}
+
+impl Odometry {
+    pub fn new(config: OdometryConfig) -> Odometry {
+        Odometry {
+            config,
+            last_counts: None,
+            last_sample: None,
+            pose: Pose::default(),
+            twist: Twist::default(),
+            pose_covariance: [[0.0; 3]; 3],
+            twist_covariance: [[0.0; 2]; 2],
+        }
+    }
+
+    pub fn config(&self) -> OdometryConfig {
+        self.config
+    }
+
+    pub fn pose(&self) -> Pose {
+        self.pose
+    }
+
+    pub fn twist(&self) -> Twist {
+        self.twist
+    }
+
+    pub fn pose_covariance(&self) -> [[f64; 3]; 3] {
+        self.pose_covariance
+    }
+
+    pub fn twist_covariance(&self) -> [[f64; 2]; 2] {
+        self.twist_covariance
+    }
+
+    // Start again from `pose`, certain of it. The next counts are only used as
+    // the starting point, so it doesn't matter where the encoders are
+    pub fn reset(&mut self, pose: Pose) {
+        *self = Odometry {
+            pose,
+            ..Odometry::new(self.config)
+        };
+    }
+
+    // Counts straight from `get_position`, with forwards positive on both
+    // wheels. `dt` is the time since the last update, and only goes into the
+    // twist. The first update just gives the counts to measure from.
+    pub fn update(&mut self, left_counts: i32, right_counts: i32, dt: Duration) {
+        let Some((last_left, last_right)) = self.last_counts.replace((left_counts, right_counts))
+        else {
+            return;
+        };
+
+        // The drive's counter is 32 bits and rolls over, the difference doesn't care
+        let left = left_counts.wrapping_sub(last_left) as f64 * self.config.metres_per_count;
+        let right = right_counts.wrapping_sub(last_right) as f64 * self.config.metres_per_count;
+
+        self.integrate(left, right, dt.as_secs_f64());
+    }
+
+    pub fn update_from_sample(&mut self, sample: &DiffDriveSample) {
+        let dt = self
+            .last_sample
+            .replace(sample.taken_at)
+            .map_or(Duration::ZERO, |last| {
+                sample.taken_at.saturating_duration_since(last)
+            });
+
+        self.update(sample.left.counts, sample.right.counts, dt);
+    }
+
+    fn integrate(&mut self, left: f64, right: f64, dt: f64) {
+        let separation = self.config.wheel_separation;
+        let distance = (left + right) / 2.0;
+        let turn = (right - left) / separation;
+        let theta = self.pose.theta;
+
+        // Exact for wheels that kept the same ratio of speeds between samples
+        if turn.abs() < STRAIGHT_LINE_TURN {
+            self.pose.x += distance * theta.cos();
+            self.pose.y += distance * theta.sin();
+        } else {
+            let radius = distance / turn;
+            self.pose.x += radius * ((theta + turn).sin() - theta.sin());
+            self.pose.y -= radius * ((theta + turn).cos() - theta.cos());
+        }
+        self.pose.theta = normalise_angle(theta + turn);
+
+        // Each wheel's travel is off by an amount that grows with how far it went
+        let left_variance = self.config.slip_variance * left.abs();
+        let right_variance = self.config.slip_variance * right.abs();
+
+        // The covariance is carried through the usual linearised model, taken
+        // halfway round the turn
+        let (sin, cos) = (theta + turn / 2.0).sin_cos();
+        let pose_jacobian = [
+            [1.0, 0.0, -distance * sin],
+            [0.0, 1.0, distance * cos],
+            [0.0, 0.0, 1.0],
+        ];
+        let lean = distance / (2.0 * separation);
+        // Columns are (right, left)
+        let wheel_jacobian = [
+            [cos / 2.0 - lean * sin, cos / 2.0 + lean * sin],
+            [sin / 2.0 + lean * cos, sin / 2.0 - lean * cos],
+            [1.0 / separation, -1.0 / separation],
+        ];
+
+        let mut covariance = [[0.0; 3]; 3];
+        for (i, row) in covariance.iter_mut().enumerate() {
+            for (j, cell) in row.iter_mut().enumerate() {
+                for k in 0..3 {
+                    for l in 0..3 {
+                        *cell +=
+                            pose_jacobian[i][k] * self.pose_covariance[k][l] * pose_jacobian[j][l];
+                    }
+                }
+                *cell += wheel_jacobian[i][0] * right_variance * wheel_jacobian[j][0]
+                    + wheel_jacobian[i][1] * left_variance * wheel_jacobian[j][1];
+            }
+        }
+        self.pose_covariance = covariance;
+
+        // Without any time passing there's no speed to speak of, so keep the last
+        if dt > 0.0 {
+            let dt2 = dt * dt;
+            self.twist = Twist {
+                linear: distance / dt,
+                angular: turn / dt,
+            };
+            let cross = (right_variance - left_variance) / (2.0 * separation * dt2);
+            self.twist_covariance = [
+                [(left_variance + right_variance) / (4.0 * dt2), cross],
+                [
+                    cross,
+                    (left_variance + right_variance) / (separation * separation * dt2),
+                ],
+            ];
+        }
+    }
+}
+
+impl Default for Odometry {
+    fn default() -> Self {
+        Odometry::new(OdometryConfig::default())
+    }
+}
+
+fn normalise_angle(angle: f64) -> f64 {
+    let wrapped = (angle + PI).rem_euclid(2.0 * PI) - PI;
+
+    // Keep pi as pi rather than flipping it to -pi
+    if wrapped == -PI {
+        PI
+    } else {
+        wrapped
+    }
+}
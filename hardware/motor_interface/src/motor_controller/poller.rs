@@ -0,0 +1,403 @@
+use crate::message::ModbusRegister;
+use crate::motor_controller::bus::MotorBus;
+use crate::motor_controller::error::MotorControllerError;
+use crate::motor_controller::motor_status::MotorStatus;
+use crate::motor_controller::MotorController;
+use arc_swap::ArcSwap;
+use log::warn;
+use std::collections::VecDeque;
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+// What the poller can keep up to date
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PollItem {
+    Speed,
+    Position,
+    Alarm,
+    Current,
+    Voltage,
+    Temperature,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PollRate {
+    pub item: PollItem,
+    // How often to read it from each drive
+    pub period_ms: u32,
+}
+
+impl PollRate {
+    pub fn period(&self) -> Duration {
+        Duration::from_millis(self.period_ms as u64)
+    }
+}
+
+// A reply takes around 10ms at 19200 baud, so this is already most of the bus
+// for a pair of drives. Asking for more than fits just means everything comes
+// round less often.
+pub const DEFAULT_POLL_RATES: [PollRate; 6] = [
+    PollRate {
+        item: PollItem::Speed,
+        period_ms: 50,
+    },
+    PollRate {
+        item: PollItem::Position,
+        period_ms: 50,
+    },
+    PollRate {
+        item: PollItem::Alarm,
+        period_ms: 500,
+    },
+    PollRate {
+        item: PollItem::Current,
+        period_ms: 200,
+    },
+    PollRate {
+        item: PollItem::Voltage,
+        period_ms: 1000,
+    },
+    PollRate {
+        item: PollItem::Temperature,
+        period_ms: 1000,
+    },
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Reading<T> {
+    pub value: T,
+    // When the reply came back
+    pub taken_at: Instant,
+}
+
+impl<T> Reading<T> {
+    pub fn age(&self) -> Duration {
+        self.taken_at.elapsed()
+    }
+}
+
+// The latest of everything read from one drive. Anything not polled, or not
+// read successfully yet, is None
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DriveSnapshot {
+    pub device_address: u8,
+    // Speed register units, tenths of an RPM
+    pub speed: Option<Reading<i16>>,
+    // Encoder counts
+    pub position: Option<Reading<i32>>,
+    pub status: Option<Reading<MotorStatus>>,
+    // Amps
+    pub current: Option<Reading<f32>>,
+    // Volts
+    pub voltage: Option<Reading<f32>>,
+    // Degrees C
+    pub temperature: Option<Reading<u16>>,
+    pub failed_polls: u64,
+    pub failed_writes: u64,
+}
+
+impl DriveSnapshot {
+    fn new(device_address: u8) -> DriveSnapshot {
+        DriveSnapshot {
+            device_address,
+            speed: None,
+            position: None,
+            status: None,
+            current: None,
+            voltage: None,
+            temperature: None,
+            failed_polls: 0,
+            failed_writes: 0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PollSnapshot {
+    // In the order the drives were given to `Poller::start`
+    pub drives: Vec<DriveSnapshot>,
+}
+
+impl PollSnapshot {
+    pub fn drive(&self, device_address: u8) -> Option<&DriveSnapshot> {
+        self.drives
+            .iter()
+            .find(|drive| drive.device_address == device_address)
+    }
+}
+
+struct Write {
+    drive: usize,
+    register: ModbusRegister,
+    value: u16,
+}
+
+struct Queue {
+    writes: VecDeque<Write>,
+    shutdown: bool,
+}
+
+struct Shared {
+    queue: Mutex<Queue>,
+    wake: Condvar,
+    snapshot: ArcSwap<PollSnapshot>,
+}
+
+// Keeps reading a set of drives on one bus from a thread of its own, so the
+// latest values can be picked up without waiting on the wire. Writes are
+// queued and go out ahead of the next poll.
+pub struct Poller {
+    shared: Arc<Shared>,
+    addresses: Vec<u8>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Poller {
+    // Each of `rates` is read from every drive. Polls that are due at the same
+    // time go out in the order they're given
+    pub fn start(
+        bus: &MotorBus,
+        addresses: &[u8],
+        rates: &[PollRate],
+    ) -> Result<Poller, MotorControllerError> {
+        let drives = addresses
+            .iter()
+            .map(|&address| bus.controller(address))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let shared = Arc::new(Shared {
+            queue: Mutex::new(Queue {
+                writes: VecDeque::new(),
+                shutdown: false,
+            }),
+            wake: Condvar::new(),
+            snapshot: ArcSwap::from_pointee(PollSnapshot {
+                drives: addresses.iter().map(|&a| DriveSnapshot::new(a)).collect(),
+            }),
+        });
+
+        let thread = {
+            let shared = shared.clone();
+            let rates = rates.to_vec();
+            thread::spawn(move || poll(shared, drives, rates))
+        };
+
+        Ok(Poller {
+            shared,
+            addresses: addresses.to_vec(),
+            thread: Some(thread),
+        })
+    }
+
+    // Never waits on the poller, so it's fine to call from a control loop
+    pub fn snapshot(&self) -> Arc<PollSnapshot> {
+        self.shared.snapshot.load_full()
+    }
+
+    pub fn drive(&self, device_address: u8) -> Option<DriveSnapshot> {
+        self.shared.snapshot.load().drive(device_address).copied()
+    }
+
+    // Goes out ahead of any polls. A speed not yet sent is replaced rather than
+    // queued behind, so a slow bus only ever sends the latest
+    pub fn set_rpm(&self, device_address: u8, speed: i16) -> Result<(), MotorControllerError> {
+        self.write_register(
+            device_address,
+            ModbusRegister::MotorTargetSpeed,
+            speed as u16,
+        )
+    }
+
+    // As `set_rpm`, any earlier write to the same register still waiting is
+    // replaced. Values the register can't take are turned away here rather than
+    // failing later on the thread
+    pub fn write_register(
+        &self,
+        device_address: u8,
+        register: ModbusRegister,
+        value: u16,
+    ) -> Result<(), MotorControllerError> {
+        let drive = self
+            .addresses
+            .iter()
+            .position(|&address| address == device_address)
+            .ok_or(MotorControllerError::InvalidDeviceAddress(device_address))?;
+        register.check_write(value)?;
+
+        let mut queue = self.shared.queue.lock().unwrap();
+        match queue
+            .writes
+            .iter_mut()
+            .find(|write| write.drive == drive && write.register == register)
+        {
+            Some(write) => write.value = value,
+            None => queue.writes.push_back(Write {
+                drive,
+                register,
+                value,
+            }),
+        }
+        self.shared.wake.notify_all();
+
+        Ok(())
+    }
+}
+
+// Writes still queued are dropped. The drives are stopped as any controller is
+// when it goes away
+impl Drop for Poller {
+    fn drop(&mut self) {
+        if let Ok(mut queue) = self.shared.queue.lock() {
+            queue.shutdown = true;
+            self.shared.wake.notify_all();
+        }
+
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+enum Next {
+    Write(Write),
+    Poll(usize),
+}
+
+struct Due {
+    drive: usize,
+    rate: PollRate,
+    at: Instant,
+}
+
+fn poll(shared: Arc<Shared>, mut drives: Vec<MotorController>, rates: Vec<PollRate>) {
+    let start = Instant::now();
+    let mut schedule: Vec<Due> = (0..drives.len())
+        .flat_map(|drive| {
+            rates.iter().map(move |&rate| Due {
+                drive,
+                rate,
+                at: start,
+            })
+        })
+        .collect();
+
+    loop {
+        let next = {
+            let mut queue = shared.queue.lock().unwrap();
+            loop {
+                if queue.shutdown {
+                    return;
+                }
+                if let Some(write) = queue.writes.pop_front() {
+                    break Next::Write(write);
+                }
+
+                // Earliest first, and the first given of those due together
+                let next = schedule
+                    .iter()
+                    .enumerate()
+                    .min_by_key(|(_, due)| due.at)
+                    .map(|(index, due)| (index, due.at));
+                let now = Instant::now();
+                queue = match next {
+                    Some((index, at)) if at <= now => break Next::Poll(index),
+                    Some((_, at)) => shared.wake.wait_timeout(queue, at - now).unwrap().0,
+                    None => shared.wake.wait(queue).unwrap(),
+                };
+            }
+        };
+
+        match next {
+            Next::Poll(index) => {
+                let due = &mut schedule[index];
+                let result = read(&mut drives[due.drive], due.rate.item);
+                // Running behind is caught up on by skipping, not by bunching up
+                due.at = (due.at + due.rate.period()).max(Instant::now());
+
+                update(&shared, due.drive, |snapshot| match result {
+                    Ok(reading) => reading.store(snapshot),
+                    Err(e) => {
+                        warn!(
+                            "Couldn't poll {:?} from drive {}: {}",
+                            due.rate.item, snapshot.device_address, e
+                        );
+                        snapshot.failed_polls += 1;
+                    }
+                });
+            }
+            Next::Write(write) => {
+                let drive = &mut drives[write.drive];
+                // Speeds go through set_rpm so they count towards the watchdog
+                let result = if write.register == ModbusRegister::MotorTargetSpeed {
+                    drive.set_rpm(write.value as i16).map(|_| ())
+                } else {
+                    drive
+                        .write_register(write.register, write.value)
+                        .map(|_| ())
+                };
+
+                if let Err(e) = result {
+                    warn!(
+                        "Couldn't write {:?} to drive {}: {}",
+                        write.register, drive.device_address, e
+                    );
+                    update(&shared, write.drive, |snapshot| snapshot.failed_writes += 1);
+                }
+            }
+        }
+    }
+}
+
+// Only this thread ever stores, so there's nothing to race with
+fn update(shared: &Shared, drive: usize, change: impl FnOnce(&mut DriveSnapshot)) {
+    let mut snapshot = PollSnapshot::clone(&shared.snapshot.load());
+    change(&mut snapshot.drives[drive]);
+    shared.snapshot.store(Arc::new(snapshot));
+}
+
+enum Value {
+    Speed(i16),
+    Position(i32),
+    Status(MotorStatus),
+    Current(f32),
+    Voltage(f32),
+    Temperature(u16),
+}
+
+struct Polled {
+    value: Value,
+    taken_at: Instant,
+}
+
+impl Polled {
+    fn store(self, snapshot: &mut DriveSnapshot) {
+        let taken_at = self.taken_at;
+        match self.value {
+            Value::Speed(value) => snapshot.speed = Some(Reading { value, taken_at }),
+            Value::Position(value) => snapshot.position = Some(Reading { value, taken_at }),
+            Value::Status(value) => snapshot.status = Some(Reading { value, taken_at }),
+            Value::Current(value) => snapshot.current = Some(Reading { value, taken_at }),
+            Value::Voltage(value) => snapshot.voltage = Some(Reading { value, taken_at }),
+            Value::Temperature(value) => snapshot.temperature = Some(Reading { value, taken_at }),
+        }
+    }
+}
+
+fn read(drive: &mut MotorController, item: PollItem) -> Result<Polled, MotorControllerError> {
+    let value = match item {
+        PollItem::Speed => Value::Speed(drive.get_rpm()?),
+        PollItem::Position => Value::Position(drive.get_position()?),
+        PollItem::Alarm => Value::Status(drive.get_status()?),
+        PollItem::Current => Value::Current(drive.get_current()?),
+        PollItem::Voltage => Value::Voltage(drive.get_voltage()?),
+        PollItem::Temperature => Value::Temperature(drive.get_temperature()?),
+    };
+
+    Ok(Polled {
+        value,
+        taken_at: Instant::now(),
+    })
+}
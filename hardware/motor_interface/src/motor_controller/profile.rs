@@ -0,0 +1,193 @@
+use crate::message::{ModbusRegister, RegisterBank, RegisterWriteError};
+use serde::{Deserialize, Serialize, Serializer};
+use std::collections::BTreeMap;
+use std::path::Path;
+use thiserror::Error;
+
+// The tuning a drive keeps in flash, by register name, so it can be put back on
+// a replacement drive. Registers left out of a profile are left alone when it's
+// applied.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "BTreeMap<String, u16>")]
+pub struct DriveProfile {
+    // In register order
+    values: Vec<(ModbusRegister, u16)>,
+}
+
+#[derive(Debug, Error)]
+pub enum ProfileError {
+    #[error("No register called {0}")]
+    UnknownRegister(String),
+    #[error("{0:?} isn't saved by the drive, so it can't be part of a profile")]
+    NotPersisted(ModbusRegister),
+    #[error("Bad value in profile! {0}")]
+    InvalidValue(#[from] RegisterWriteError),
+    #[error("Couldn't read or write profile file! {0}")]
+    IOError(#[from] std::io::Error),
+    #[error("Bad TOML profile! {0}")]
+    Toml(#[from] toml::de::Error),
+    #[error("Couldn't write TOML profile! {0}")]
+    TomlWrite(#[from] toml::ser::Error),
+    #[error("Bad JSON profile! {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("Don't know how to handle {0}, expected .toml or .json")]
+    UnknownFormat(String),
+}
+
+// A register where the drive and the profile disagree
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProfileDifference {
+    pub register: ModbusRegister,
+    pub profile: u16,
+    pub drive: u16,
+}
+
+impl DriveProfile {
+    // Every register the drive saves, as read in `bank`
+    pub fn from_bank(bank: &RegisterBank) -> DriveProfile {
+        DriveProfile {
+            values: bank
+                .iter()
+                .filter(|(register, _)| register.info().persisted)
+                .collect(),
+        }
+    }
+
+    pub fn get(&self, register: ModbusRegister) -> Option<u16> {
+        self.values
+            .iter()
+            .find(|(r, _)| *r == register)
+            .map(|&(_, value)| value)
+    }
+
+    // Only registers the drive saves, with values they can take
+    pub fn set(&mut self, register: ModbusRegister, value: u16) -> Result<(), ProfileError> {
+        if !register.info().persisted {
+            return Err(ProfileError::NotPersisted(register));
+        }
+        register.check_write(value)?;
+
+        match self.values.iter_mut().find(|(r, _)| *r == register) {
+            Some((_, old)) => *old = value,
+            None => {
+                self.values.push((register, value));
+                self.values.sort_by_key(|&(r, _)| r as u16);
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (ModbusRegister, u16)> + '_ {
+        self.values.iter().copied()
+    }
+
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    // Runs of consecutive registers, which can each go out as one write
+    pub(crate) fn runs(&self) -> Vec<(ModbusRegister, Vec<u16>)> {
+        let mut runs: Vec<(ModbusRegister, Vec<u16>)> = Vec::new();
+        for &(register, value) in &self.values {
+            match runs.last_mut() {
+                Some((start, values)) if *start as usize + values.len() == register as usize => {
+                    values.push(value)
+                }
+                _ => runs.push((register, vec![value])),
+            }
+        }
+
+        runs
+    }
+
+    // Everything in the profile that `bank` has differently. Registers missing
+    // from `bank` count as different, with the drive's value as 0
+    pub fn diff(&self, bank: &RegisterBank) -> Vec<ProfileDifference> {
+        self.values
+            .iter()
+            .filter_map(|&(register, profile)| {
+                let drive = bank.get(register);
+                (drive != Some(profile)).then(|| ProfileDifference {
+                    register,
+                    profile,
+                    drive: drive.unwrap_or(0),
+                })
+            })
+            .collect()
+    }
+
+    // Picks the format from the extension
+    pub fn load(path: impl AsRef<Path>) -> Result<DriveProfile, ProfileError> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path)?;
+
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("toml") => DriveProfile::from_toml(&text),
+            Some("json") => DriveProfile::from_json(&text),
+            _ => Err(ProfileError::UnknownFormat(path.display().to_string())),
+        }
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), ProfileError> {
+        let path = path.as_ref();
+
+        let text = match path.extension().and_then(|e| e.to_str()) {
+            Some("toml") => self.to_toml()?,
+            Some("json") => self.to_json()?,
+            _ => return Err(ProfileError::UnknownFormat(path.display().to_string())),
+        };
+        std::fs::write(path, text)?;
+
+        Ok(())
+    }
+
+    // Parsed as plain names and numbers first, so a bad register comes back as
+    // itself rather than wrapped up in a parse error
+    pub fn from_toml(text: &str) -> Result<DriveProfile, ProfileError> {
+        toml::from_str::<BTreeMap<String, u16>>(text)?.try_into()
+    }
+
+    pub fn from_json(text: &str) -> Result<DriveProfile, ProfileError> {
+        serde_json::from_str::<BTreeMap<String, u16>>(text)?.try_into()
+    }
+
+    pub fn to_toml(&self) -> Result<String, ProfileError> {
+        Ok(toml::to_string(self)?)
+    }
+
+    pub fn to_json(&self) -> Result<String, ProfileError> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+}
+
+// Written out in register order rather than sorted by name
+impl Serialize for DriveProfile {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_map(
+            self.values
+                .iter()
+                .map(|(register, value)| (format!("{:?}", register), value)),
+        )
+    }
+}
+
+impl TryFrom<BTreeMap<String, u16>> for DriveProfile {
+    type Error = ProfileError;
+
+    fn try_from(values: BTreeMap<String, u16>) -> Result<Self, Self::Error> {
+        let mut profile = DriveProfile::default();
+        for (name, value) in values {
+            let register = name
+                .parse()
+                .map_err(|_| ProfileError::UnknownRegister(name))?;
+            profile.set(register, value)?;
+        }
+
+        Ok(profile)
+    }
+}
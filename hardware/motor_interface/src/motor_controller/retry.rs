@@ -0,0 +1,132 @@
+use crate::message::{ModbusException, ModbusResponseError};
+use crate::motor_controller::error::MotorControllerError;
+use std::time::Duration;
+
+// What to do when a transaction goes wrong in a way that might not happen again
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    // Tries in total, so 1 never retries
+    pub max_attempts: u32,
+    // How long each try waits for a reply. None leaves the port's timeout alone
+    pub attempt_timeout: Option<Duration>,
+    // Wait before the first retry, doubling for every one after
+    pub backoff: Duration,
+    // Which failures are worth another go
+    pub retry_timeouts: bool,
+    // Bad checksums, garbled frames, replies that don't match the request
+    pub retry_corrupt_responses: bool,
+    // The drive said it was busy
+    pub retry_busy: bool,
+    // Register writes set a value, so sending one twice does no harm. Moves and
+    // address changes are never repeated whatever this says
+    pub retry_writes: bool,
+}
+
+impl RetryPolicy {
+    // Give up on the first failure, like before there were retries
+    pub fn never() -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: 1,
+            ..RetryPolicy::default()
+        }
+    }
+
+    pub(crate) fn should_retry(&self, kind: RequestKind, error: &MotorControllerError) -> bool {
+        let allowed = match kind {
+            RequestKind::Read => true,
+            RequestKind::Write => self.retry_writes,
+            RequestKind::Once => false,
+        };
+
+        allowed
+            && match classify(error) {
+                Some(Failure::Timeout) => self.retry_timeouts,
+                Some(Failure::Corrupt) => self.retry_corrupt_responses,
+                Some(Failure::Busy) => self.retry_busy,
+                None => false,
+            }
+    }
+
+    // How long to wait before retry number `retry`, counting from 1
+    pub(crate) fn backoff(&self, retry: u32) -> Duration {
+        self.backoff
+            .saturating_mul(1 << (retry.saturating_sub(1)).min(16))
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_attempts: 3,
+            attempt_timeout: None,
+            backoff: Duration::from_millis(10),
+            retry_timeouts: true,
+            retry_corrupt_responses: true,
+            retry_busy: true,
+            retry_writes: true,
+        }
+    }
+}
+
+// Whether a request can safely be sent more than once
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum RequestKind {
+    Read,
+    // Sets a register to a value, so repeating it lands in the same place
+    Write,
+    // Relative moves, address changes, ...
+    Once,
+}
+
+// Counts for every request made through one controller
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RetryStats {
+    pub requests: u64,
+    // Extra attempts over all requests
+    pub retries: u64,
+    // Requests that failed even after retrying
+    pub failures: u64,
+    pub last_request_retries: u32,
+    pub most_retries: u32,
+}
+
+impl RetryStats {
+    pub(crate) fn record(&mut self, retries: u32, failed: bool) {
+        self.requests += 1;
+        self.retries += retries as u64;
+        self.last_request_retries = retries;
+        self.most_retries = self.most_retries.max(retries);
+
+        if failed {
+            self.failures += 1;
+        }
+    }
+}
+
+enum Failure {
+    Timeout,
+    Corrupt,
+    Busy,
+}
+
+fn classify(error: &MotorControllerError) -> Option<Failure> {
+    match error {
+        MotorControllerError::ResponseError(ModbusResponseError::IOError(e)) => match e.kind() {
+            std::io::ErrorKind::TimedOut | std::io::ErrorKind::WouldBlock => Some(Failure::Timeout),
+            _ => None,
+        },
+        MotorControllerError::ResponseError(_)
+        | MotorControllerError::CheckSumFail
+        | MotorControllerError::InvalidResponder(..)
+        | MotorControllerError::IncorrectDataLength(..)
+        | MotorControllerError::IncorrectResponseType
+        | MotorControllerError::IncorrectResponseRegister(..)
+        | MotorControllerError::IncorrectResponseValue(..) => Some(Failure::Corrupt),
+        MotorControllerError::ModbusException(
+            _,
+            ModbusException::ServerDeviceBusy | ModbusException::Acknowledge,
+        ) => Some(Failure::Busy),
+        _ => None,
+    }
+}
@@ -0,0 +1,158 @@
+use std::time::Duration;
+
+// How hard a speed is allowed to change. For a wheel these are in m/s², m/s³
+// at the wheel; for a body twist, in the twist's own units (m/s² or rad/s²).
+// 0 means no limit, which is also the default, so nothing changes until
+// limits are set.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct MotionLimits {
+    // Speeding up, in either direction
+    pub acceleration: f32,
+    // Slowing down towards 0. Braking can usually afford to be harder
+    pub deceleration: f32,
+    // How quickly the acceleration itself can change
+    pub jerk: f32,
+    // Slowing down after `emergency_stop`. Jerk isn't limited then
+    pub emergency_deceleration: f32,
+}
+
+impl MotionLimits {
+    pub(crate) fn field(&mut self, name: &str) -> Option<&mut f32> {
+        match name {
+            "acceleration" => Some(&mut self.acceleration),
+            "deceleration" => Some(&mut self.deceleration),
+            "jerk" => Some(&mut self.jerk),
+            "emergency_deceleration" => Some(&mut self.emergency_deceleration),
+            _ => None,
+        }
+    }
+
+    pub fn is_valid(&self) -> bool {
+        [
+            self.acceleration,
+            self.deceleration,
+            self.jerk,
+            self.emergency_deceleration,
+        ]
+        .iter()
+        .all(|limit| limit.is_finite() && *limit >= 0.0)
+    }
+}
+
+// Walks a speed towards its target within some MotionLimits, one control loop
+// tick at a time. Acceleration builds up and eases off at the jerk limit so
+// the target is met without overshooting.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct SpeedShaper {
+    limits: MotionLimits,
+    target: f32,
+    velocity: f32,
+    acceleration: f32,
+    emergency: bool,
+}
+
+impl SpeedShaper {
+    pub fn new(limits: MotionLimits) -> SpeedShaper {
+        SpeedShaper {
+            limits,
+            ..SpeedShaper::default()
+        }
+    }
+
+    pub fn limits(&self) -> MotionLimits {
+        self.limits
+    }
+
+    pub fn set_limits(&mut self, limits: MotionLimits) {
+        self.limits = limits;
+    }
+
+    pub fn target(&self) -> f32 {
+        self.target
+    }
+
+    // Anything that isn't a number is taken as 0
+    pub fn set_target(&mut self, target: f32) {
+        self.target = if target.is_finite() { target } else { 0.0 };
+    }
+
+    // Where the last tick got to
+    pub fn velocity(&self) -> f32 {
+        self.velocity
+    }
+
+    pub fn acceleration(&self) -> f32 {
+        self.acceleration
+    }
+
+    // Heads for 0 at `emergency_deceleration` whatever the target says, until `resume`
+    pub fn emergency_stop(&mut self) {
+        self.emergency = true;
+    }
+
+    pub fn is_emergency_stopped(&self) -> bool {
+        self.emergency
+    }
+
+    // Picks up from wherever the speed has got to. The target is left alone,
+    // so it'll be ramped back up to
+    pub fn resume(&mut self) {
+        self.emergency = false;
+    }
+
+    // For when the speed has been set some other way. Starts again from
+    // `velocity` with no acceleration, aiming to stay there
+    pub fn reset(&mut self, velocity: f32) {
+        self.set_target(velocity);
+        self.velocity = self.target;
+        self.acceleration = 0.0;
+    }
+
+    // Moves on by `dt` and gives the speed to command now
+    pub fn tick(&mut self, dt: Duration) -> f32 {
+        let dt = dt.as_secs_f32();
+        if dt == 0.0 {
+            return self.velocity;
+        }
+
+        let (target, limit, jerk) = if self.emergency {
+            (0.0, self.limits.emergency_deceleration, 0.0)
+        } else if self.velocity * (self.target - self.velocity) < 0.0 {
+            (self.target, self.limits.deceleration, self.limits.jerk)
+        } else {
+            (self.target, self.limits.acceleration, self.limits.jerk)
+        };
+
+        let error = target - self.velocity;
+        if limit == 0.0 || error == 0.0 {
+            self.velocity = target;
+            self.acceleration = 0.0;
+            return self.velocity;
+        }
+
+        let wanted = if jerk == 0.0 {
+            error.signum() * limit
+        } else {
+            // Any harder than this and the acceleration can't be eased off
+            // again before the target goes past
+            error.signum() * limit.min((2.0 * jerk * error.abs()).sqrt())
+        };
+        self.acceleration = if jerk == 0.0 {
+            wanted
+        } else {
+            let step = jerk * dt;
+            self.acceleration + (wanted - self.acceleration).clamp(-step, step)
+        };
+
+        let velocity = self.velocity + self.acceleration * dt;
+        if (target - velocity) * error <= 0.0 {
+            self.velocity = target;
+            self.acceleration = 0.0;
+        } else {
+            self.velocity = velocity;
+        }
+
+        self.velocity
+    }
+}
@@ -0,0 +1,65 @@
+use crate::message::ModbusRegister;
+use crate::motor_controller::error::MotorControllerError;
+use crate::motor_controller::motor_status::MotorStatus;
+use std::time::Instant;
+
+// The read-only block from the alarm code to the output PWM, all in one go
+pub(crate) const TELEMETRY_START: ModbusRegister = ModbusRegister::MotorAlarmCode;
+pub(crate) const TELEMETRY_LEN: usize = 6;
+
+// Everything the drive reports about how it's doing, as read in one transaction
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Telemetry {
+    pub status: MotorStatus,
+    // Amps
+    pub current: f32,
+    // Speed register units, tenths of an RPM
+    pub rpm: i16,
+    // Supply voltage in volts
+    pub voltage: f32,
+    // Degrees C
+    pub temperature: u16,
+    // Output duty cycle, -100~100%
+    pub pwm: f32,
+    // Halfway through the transaction
+    pub taken_at: Instant,
+}
+
+impl Telemetry {
+    // `registers` starts at TELEMETRY_START
+    pub(crate) fn from_registers(
+        registers: &[u16],
+        taken_at: Instant,
+    ) -> Result<Telemetry, MotorControllerError> {
+        let &[alarm, current, rpm, voltage, temperature, pwm] = registers else {
+            return Err(MotorControllerError::IncorrectDataLength(
+                TELEMETRY_LEN * 2,
+                registers.len() * 2,
+            ));
+        };
+
+        Ok(Telemetry {
+            status: alarm
+                .try_into()
+                .map_err(MotorControllerError::MotorStatusParseError)?,
+            current: current_amps(current),
+            rpm: rpm as i16,
+            voltage: voltage_volts(voltage),
+            temperature,
+            pwm: pwm_percent(pwm),
+            taken_at,
+        })
+    }
+}
+
+pub(crate) fn current_amps(raw: u16) -> f32 {
+    raw as f32 / 2000.0
+}
+
+pub(crate) fn voltage_volts(raw: u16) -> f32 {
+    raw as f32 / 327.0
+}
+
+pub(crate) fn pwm_percent(raw: u16) -> f32 {
+    raw as i16 as f32 / 32768.0 * 100.0
+}
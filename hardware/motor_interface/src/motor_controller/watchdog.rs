@@ -0,0 +1,292 @@
+use crate::motor_controller::error::MotorControllerError;
+use crate::motor_controller::MotorController;
+use log::warn;
+use std::collections::VecDeque;
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+// How often the speed steps down while ramping to a stop
+const RAMP_STEP: Duration = Duration::from_millis(20);
+// Trips nobody has collected yet. Past this the oldest are forgotten
+const MAX_TRIPS: usize = 16;
+
+// What to do when speed commands stop turning up
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WatchdogConfig {
+    // Longest gap allowed between speed commands
+    pub timeout_ms: u32,
+    // How long to take bringing the speed down to 0. 0 stops dead
+    pub ramp_ms: u32,
+    // Also turn the motor off once it's stopped, so it has to be enabled again
+    pub disable_motor: bool,
+}
+
+impl Default for WatchdogConfig {
+    fn default() -> Self {
+        WatchdogConfig {
+            timeout_ms: 500,
+            ramp_ms: 200,
+            disable_motor: false,
+        }
+    }
+}
+
+impl WatchdogConfig {
+    pub fn timeout(&self) -> Duration {
+        Duration::from_millis(self.timeout_ms as u64)
+    }
+
+    pub fn ramp(&self) -> Duration {
+        Duration::from_millis(self.ramp_ms as u64)
+    }
+}
+
+// The watchdog stopped a drive
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WatchdogTrip {
+    pub device_address: u8,
+    // The last speed it was told, in speed register units
+    pub speed: i16,
+    // The drive took the final 0. If not, it may still be moving
+    pub stopped: bool,
+    pub disabled: bool,
+}
+
+struct State {
+    config: Option<WatchdogConfig>,
+    // Where the drive is now, which the thread's own controller may not know
+    device_address: u8,
+    last_command: Instant,
+    // The last speed the drive took, so a failed write doesn't hide that it's moving
+    last_speed: i16,
+    // Goes up with every command, so a stop in progress can tell it's been overtaken
+    generation: u64,
+    tripped: bool,
+    shutdown: bool,
+    trips: VecDeque<WatchdogTrip>,
+}
+
+// Every controller has one. It only starts watching once it's been configured,
+// and only trips if the last command left the motor moving.
+pub(crate) struct Watchdog {
+    shared: Arc<(Mutex<State>, Condvar)>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Watchdog {
+    pub(crate) fn new(device_address: u8) -> Watchdog {
+        Watchdog {
+            shared: Arc::new((
+                Mutex::new(State {
+                    config: None,
+                    device_address,
+                    last_command: Instant::now(),
+                    last_speed: 0,
+                    generation: 0,
+                    tripped: false,
+                    shutdown: false,
+                    trips: VecDeque::new(),
+                }),
+                Condvar::new(),
+            )),
+            thread: None,
+        }
+    }
+
+    pub(crate) fn config(&self) -> Option<WatchdogConfig> {
+        self.shared.0.lock().unwrap().config
+    }
+
+    // `handle` is only called the first time, to get something the watchdog
+    // thread can talk to the drive with
+    pub(crate) fn configure(
+        &mut self,
+        config: Option<WatchdogConfig>,
+        handle: impl FnOnce() -> MotorController,
+    ) {
+        let (state, wake) = &*self.shared;
+        let mut state = state.lock().unwrap();
+        state.config = config;
+        // Give the new timeout a fair go rather than tripping straight away
+        state.last_command = Instant::now();
+        wake.notify_all();
+        drop(state);
+
+        if config.is_some() && self.thread.is_none() {
+            let shared = self.shared.clone();
+            let controller = handle();
+            self.thread = Some(thread::spawn(move || watch(shared, controller)));
+        }
+    }
+
+    // A new speed command is about to go out. Any stop in progress gives way to it
+    pub(crate) fn feed(&self) {
+        let (state, wake) = &*self.shared;
+        let mut state = state.lock().unwrap();
+        state.last_command = Instant::now();
+        state.generation += 1;
+        state.tripped = false;
+        wake.notify_all();
+    }
+
+    // The drive answered the command that was fed with `speed`
+    pub(crate) fn took(&self, speed: i16) {
+        let (state, wake) = &*self.shared;
+        state.lock().unwrap().last_speed = speed;
+        wake.notify_all();
+    }
+
+    // The drive has been moved to a new address
+    pub(crate) fn set_device_address(&self, device_address: u8) {
+        self.shared.0.lock().unwrap().device_address = device_address;
+    }
+
+    pub(crate) fn is_tripped(&self) -> bool {
+        self.shared.0.lock().unwrap().tripped
+    }
+
+    pub(crate) fn take_trip(&self) -> Option<WatchdogTrip> {
+        self.shared.0.lock().unwrap().trips.pop_front()
+    }
+
+    // Stops the thread. Gives back the speed the drive was left at and how to
+    // stop it, unless it's already stopped
+    pub(crate) fn shutdown(&mut self) -> Option<(i16, WatchdogConfig)> {
+        let (state, wake) = &*self.shared;
+        if let Ok(mut state) = state.lock() {
+            state.shutdown = true;
+            wake.notify_all();
+        }
+
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+
+        let state = state.lock().ok()?;
+        if state.tripped || state.last_speed == 0 {
+            return None;
+        }
+
+        // With no watchdog set up there's no ramp to follow, so stop dead
+        let config = state.config.unwrap_or(WatchdogConfig {
+            ramp_ms: 0,
+            ..WatchdogConfig::default()
+        });
+
+        Some((state.last_speed, config))
+    }
+}
+
+fn watch(shared: Arc<(Mutex<State>, Condvar)>, mut controller: MotorController) {
+    let (state, wake) = &*shared;
+    let mut guard = state.lock().unwrap();
+
+    loop {
+        if guard.shutdown {
+            return;
+        }
+
+        let config = match guard.config {
+            Some(config) if !guard.tripped && guard.last_speed != 0 => config,
+            _ => {
+                guard = wake.wait(guard).unwrap();
+                continue;
+            }
+        };
+
+        let deadline = guard.last_command + config.timeout();
+        let now = Instant::now();
+        if now < deadline {
+            guard = wake.wait_timeout(guard, deadline - now).unwrap().0;
+            continue;
+        }
+
+        let speed = guard.last_speed;
+        let generation = guard.generation;
+        controller.device_address = guard.device_address;
+        drop(guard);
+
+        warn!(
+            "No speed command for drive {} in {:?}, stopping it",
+            controller.device_address,
+            config.timeout()
+        );
+        // Each step goes out with the state held, so a new command can't be
+        // sent between checking for one and writing over it
+        let stopped = ramp_down(speed, config.ramp(), |step| {
+            let guard = state.lock().unwrap();
+            if guard.generation != generation || guard.shutdown {
+                return None;
+            }
+
+            Some(controller.write_speed(step))
+        });
+
+        guard = state.lock().unwrap();
+        let Some(stopped) = stopped else {
+            continue;
+        };
+        if guard.generation != generation {
+            continue;
+        }
+
+        let disabled = config.disable_motor && controller.set_motor_disabled().is_ok();
+        guard.tripped = true;
+        // Otherwise it's left to try again on the next trip or on drop
+        if stopped.is_ok() {
+            guard.last_speed = 0;
+        }
+        if guard.trips.len() == MAX_TRIPS {
+            guard.trips.pop_front();
+        }
+        guard.trips.push_back(WatchdogTrip {
+            device_address: controller.device_address,
+            speed,
+            stopped: stopped.is_ok(),
+            disabled,
+        });
+    }
+}
+
+// Brings the speed down to 0 over `ramp`. `write` sends each step, or gives
+// None if something has happened to make the stop pointless. If a step doesn't
+// get through there's no point carrying on gently, so it goes straight to 0.
+fn ramp_down(
+    from: i16,
+    ramp: Duration,
+    mut write: impl FnMut(i16) -> Option<Result<i16, MotorControllerError>>,
+) -> Option<Result<(), MotorControllerError>> {
+    let steps = (ramp.as_millis() / RAMP_STEP.as_millis()).max(1) as i32;
+
+    for step in 1..=steps {
+        let speed = (from as i32 * (steps - step) / steps) as i16;
+
+        if write(speed)?.is_err() {
+            return write(0).map(|result| result.map(|_| ()));
+        }
+        if step < steps {
+            thread::sleep(RAMP_STEP);
+        }
+    }
+
+    Some(Ok(()))
+}
+
+// What's done on drop, without the watchdog thread to race against
+pub(crate) fn stop(controller: &mut MotorController, speed: i16, config: &WatchdogConfig) {
+    let stopped = ramp_down(speed, config.ramp(), |step| {
+        Some(controller.write_speed(step))
+    });
+
+    if let Some(Err(e)) = stopped {
+        warn!(
+            "Couldn't stop drive {} on the way out: {}",
+            controller.device_address, e
+        );
+    } else if config.disable_motor {
+        let _ = controller.set_motor_disabled();
+    }
+}
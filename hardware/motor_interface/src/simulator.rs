@@ -0,0 +1,280 @@
+use crate::message::ModbusRegister;
+use crate::motor_controller::motor_status::MotorStatus;
+use crate::transport::Transport;
+use drive_model::{DriveModel, DEFAULT_BAUD_RATE};
+use std::io::{Read, Write};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+mod drive_model;
+
+const SIMULATOR_DEFAULT_TIMEOUT: Duration = Duration::from_secs(1);
+
+// A software stand-in for a motor drive, answering Modbus frames the way the
+// real controller does. Hand one to `MotorController::from_transport` and keep
+// a clone around to poke at the drive (advance time, raise alarms, ...) while
+// the controller talks to it.
+#[derive(Clone)]
+pub struct SimulatedDrive {
+    model: Arc<Mutex<DriveModel>>,
+    timeout: Duration,
+    // The host's end of the cable
+    line_baud_rate: u32,
+}
+
+impl SimulatedDrive {
+    pub fn new(device_address: u8) -> SimulatedDrive {
+        SimulatedDrive {
+            model: Arc::new(Mutex::new(DriveModel::new(device_address))),
+            timeout: SIMULATOR_DEFAULT_TIMEOUT,
+            line_baud_rate: DEFAULT_BAUD_RATE,
+        }
+    }
+
+    // By default time only moves when `step` is called, which keeps tests
+    // deterministic. In real time mode the drive also catches up on the wall
+    // clock every time it receives a frame.
+    pub fn set_realtime(&self, realtime: bool) {
+        self.model.lock().unwrap().realtime = realtime;
+    }
+
+    // Flip SW1 off (true) or on (false). Takes effect immediately rather than
+    // needing a power cycle like the real thing
+    pub fn set_position_mode(&self, position_mode: bool) {
+        self.model.lock().unwrap().position_mode = position_mode;
+    }
+
+    // The rate the drive itself was set up to talk at, as opposed to
+    // `Transport::set_baud_rate` which changes the host's end. The drive only
+    // hears frames when the two agree.
+    pub fn configure_baud_rate(&self, baud_rate: u32) {
+        self.model.lock().unwrap().baud_rate = baud_rate;
+    }
+
+    pub fn step(&self, dt: Duration) {
+        self.model.lock().unwrap().step(dt);
+    }
+
+    pub fn register(&self, register: ModbusRegister) -> u16 {
+        self.model.lock().unwrap().registers[register as usize]
+    }
+
+    // Backdoor write that skips all the rules a write over the bus would follow
+    pub fn set_register(&self, register: ModbusRegister, value: u16) {
+        self.model.lock().unwrap().registers[register as usize] = value;
+    }
+
+    pub fn position(&self) -> i32 {
+        let low = self.register(ModbusRegister::MotorAbsolutePositionLow) as u32;
+        let high = self.register(ModbusRegister::MotorAbsolutePositionHigh) as u32;
+
+        ((high << 16) | low) as i32
+    }
+
+    pub fn set_position(&self, position: i32) {
+        self.model.lock().unwrap().set_position(position);
+    }
+
+    pub fn raise_alarm(&self, status: MotorStatus) {
+        self.set_register(ModbusRegister::MotorAlarmCode, status.into());
+    }
+
+    pub fn clear_alarm(&self) {
+        self.raise_alarm(MotorStatus::None);
+    }
+
+    pub fn set_supply_voltage(&self, volts: f32) {
+        let mut model = self.model.lock().unwrap();
+        model.supply_voltage = volts;
+        model.step(Duration::ZERO);
+    }
+
+    pub fn set_temperature(&self, celsius: u16) {
+        let mut model = self.model.lock().unwrap();
+        model.temperature = celsius;
+        model.step(Duration::ZERO);
+    }
+
+    // Makes the next save to flash fail with MotorStatusWarning::FlashWriteFailed
+    pub fn set_flash_write_fails(&self, fails: bool) {
+        self.model.lock().unwrap().flash_write_fails = fails;
+    }
+
+    // Keep the saving flag at 1 for the next `count` frames after a save, before
+    // it finishes (or fails)
+    pub fn set_flash_write_frames(&self, count: u32) {
+        self.model.lock().unwrap().flash_write_frames = count;
+    }
+
+    // Act as if the next `count` frames sent to us were lost on the way
+    pub fn ignore_next_frames(&self, count: u32) {
+        self.model.lock().unwrap().frames_to_ignore = count;
+    }
+
+    // Garble the checksum on the next `count` replies
+    pub fn corrupt_next_replies(&self, count: u32) {
+        self.model.lock().unwrap().replies_to_corrupt = count;
+    }
+
+    // Frames addressed to us with a good checksum, whether or not they were answered
+    pub fn frames_received(&self) -> u64 {
+        self.model.lock().unwrap().frames_received
+    }
+
+    // Lose everything that wasn't saved to flash
+    pub fn power_cycle(&self) {
+        self.model.lock().unwrap().power_cycle();
+    }
+}
+
+impl Read for SimulatedDrive {
+    // The reply is produced as soon as the request is written, so if there is
+    // nothing to read now there never will be
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        let mut model = self.model.lock().unwrap();
+
+        if model.outgoing.is_empty() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::TimedOut,
+                "Operation timed out",
+            ));
+        }
+
+        let n = buf.len().min(model.outgoing.len());
+        buf[..n].copy_from_slice(&model.outgoing[..n]);
+        model.outgoing.drain(..n);
+
+        Ok(n)
+    }
+}
+
+impl Write for SimulatedDrive {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let mut model = self.model.lock().unwrap();
+
+        if model.baud_rate == self.line_baud_rate {
+            model.receive(buf);
+        }
+
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Transport for SimulatedDrive {
+    fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    fn set_timeout(&mut self, timeout: Duration) -> std::io::Result<()> {
+        self.timeout = timeout;
+        Ok(())
+    }
+
+    fn baud_rate(&self) -> std::io::Result<u32> {
+        Ok(self.line_baud_rate)
+    }
+
+    fn set_baud_rate(&mut self, baud_rate: u32) -> std::io::Result<()> {
+        self.line_baud_rate = baud_rate;
+        Ok(())
+    }
+
+    fn clear_buffers(&mut self) -> std::io::Result<()> {
+        self.model.lock().unwrap().outgoing.clear();
+        Ok(())
+    }
+}
+
+// Several simulated drives on one cable. Every frame reaches every drive and
+// each only answers to its own address, same as the real bus.
+#[derive(Clone)]
+pub struct SimulatedBus {
+    drives: Vec<SimulatedDrive>,
+    timeout: Duration,
+    line_baud_rate: u32,
+}
+
+impl SimulatedBus {
+    pub fn new(drives: impl IntoIterator<Item = SimulatedDrive>) -> SimulatedBus {
+        SimulatedBus {
+            drives: drives
+                .into_iter()
+                .map(|mut drive| {
+                    drive.line_baud_rate = DEFAULT_BAUD_RATE;
+                    drive
+                })
+                .collect(),
+            timeout: SIMULATOR_DEFAULT_TIMEOUT,
+            line_baud_rate: DEFAULT_BAUD_RATE,
+        }
+    }
+}
+
+impl Read for SimulatedBus {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        for drive in self.drives.iter_mut() {
+            if !drive.model.lock().unwrap().outgoing.is_empty() {
+                return drive.read(buf);
+            }
+        }
+
+        Err(std::io::Error::new(
+            std::io::ErrorKind::TimedOut,
+            "Operation timed out",
+        ))
+    }
+}
+
+impl Write for SimulatedBus {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        for drive in self.drives.iter_mut() {
+            drive.write_all(buf)?;
+        }
+
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Transport for SimulatedBus {
+    fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    fn set_timeout(&mut self, timeout: Duration) -> std::io::Result<()> {
+        self.timeout = timeout;
+        Ok(())
+    }
+
+    fn baud_rate(&self) -> std::io::Result<u32> {
+        Ok(self.line_baud_rate)
+    }
+
+    fn set_baud_rate(&mut self, baud_rate: u32) -> std::io::Result<()> {
+        for drive in self.drives.iter_mut() {
+            drive.set_baud_rate(baud_rate)?;
+        }
+
+        self.line_baud_rate = baud_rate;
+        Ok(())
+    }
+
+    fn clear_buffers(&mut self) -> std::io::Result<()> {
+        for drive in self.drives.iter_mut() {
+            drive.clear_buffers()?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,535 @@
+use crate::crc::crc16;
+use crate::message::{ModbusCommand, ModbusException, ModbusRegister};
+use crate::motor_controller::constants::MOTOR_ENCODER_COUNT;
+use std::time::{Duration, Instant};
+
+// Registers 0x00 through 0x19
+pub(crate) const REGISTER_COUNT: usize = 0x1A;
+pub(crate) const DEFAULT_BAUD_RATE: u32 = 19_200;
+
+// Anything at or above this acceleration means "don't ramp, just go"
+const NO_RAMP_ACCELERATION: u16 = 60_000;
+// Speed registers are in tenths of an RPM
+const SPEED_REGISTER_SCALE: f64 = 10.0;
+// Full-scale speed used to fake the output PWM
+const MAX_SPEED_REGISTER: f64 = 30_000.0;
+
+// What the drive ships with, according to the manual. Values the manual does
+// not give are picked to be plausible rather than correct.
+const DEFAULT_REGISTERS: [u16; REGISTER_COUNT] = [
+    0,      // EnableModbus
+    0,      // EnableMotor
+    0,      // MotorTargetSpeed
+    20_000, // MotorAcceleration
+    0,      // MotorInitialSpeed
+    1_000,  // MotorSpeedLoopProportionalCoefficient
+    100,    // MotorSpeedLoopIntegrationTime
+    500,    // MotorPositionLoopProportionalCoefficient
+    0,      // MotorSpeedFeedForwardVoltage
+    0,      // MotorDirectionPolarity
+    1,      // MotorElectronicGearHigh
+    1,      // MotorElectronicGearLow
+    0,      // MotorTargetPositionLow
+    0,      // MotorTargetPositionHigh
+    0,      // MotorAlarmCode
+    0,      // MotorI
+    0,      // MotorCurrentSpeed
+    0,      // MotorV
+    0,      // SystemTemperature
+    0,      // SystemOutputPWM
+    0,      // ParameterSavingFlag
+    0,      // DeviceAddress
+    0,      // MotorAbsolutePositionLow
+    0,      // MotorAbsolutePositionHigh
+    100,    // MotorSpeedFilterFrequency
+    0,      // MotorSpecialFunction
+];
+
+// The state of one simulated drive. Frames go in through `receive`, replies
+// come out of `outgoing`, and the physics only move when `step` is called (or
+// on every frame, if running in real time).
+pub(crate) struct DriveModel {
+    pub(crate) registers: [u16; REGISTER_COUNT],
+    factory_registers: [u16; REGISTER_COUNT],
+    saved_registers: Option<[u16; REGISTER_COUNT]>,
+    // Speed in register units, kept fractional so slow ramps still move
+    speed: f64,
+    // Encoder counts, kept fractional so slow speeds still move
+    position: f64,
+    pub(crate) supply_voltage: f32,
+    pub(crate) temperature: u16,
+    pub(crate) flash_write_fails: bool,
+    // How many frames a save to flash takes, and how many are left of the current one
+    pub(crate) flash_write_frames: u32,
+    saving: Option<u32>,
+    // What the drive talks at. Anything sent at another rate is noise to it
+    pub(crate) baud_rate: u32,
+    // SW1 off. Target speed becomes the top speed of a move rather than a setpoint
+    pub(crate) position_mode: bool,
+    // Where the current move is heading, in encoder counts
+    goal: Option<f64>,
+    pub(crate) realtime: bool,
+    // Faults to inject into the next few frames addressed to us
+    pub(crate) frames_to_ignore: u32,
+    pub(crate) replies_to_corrupt: u32,
+    pub(crate) frames_received: u64,
+    last_tick: Option<Instant>,
+    incoming: Vec<u8>,
+    pub(crate) outgoing: Vec<u8>,
+}
+
+impl DriveModel {
+    pub(crate) fn new(device_address: u8) -> DriveModel {
+        let mut registers = DEFAULT_REGISTERS;
+        registers[ModbusRegister::DeviceAddress as usize] = device_address as u16;
+
+        let mut model = DriveModel {
+            registers,
+            factory_registers: registers,
+            saved_registers: None,
+            speed: 0.0,
+            position: 0.0,
+            supply_voltage: 24.0,
+            temperature: 30,
+            flash_write_fails: false,
+            flash_write_frames: 0,
+            saving: None,
+            baud_rate: DEFAULT_BAUD_RATE,
+            position_mode: false,
+            goal: None,
+            realtime: false,
+            frames_to_ignore: 0,
+            replies_to_corrupt: 0,
+            frames_received: 0,
+            last_tick: None,
+            incoming: Vec::new(),
+            outgoing: Vec::new(),
+        };
+        model.update_telemetry();
+        model
+    }
+
+    pub(crate) fn device_address(&self) -> u8 {
+        self.registers[ModbusRegister::DeviceAddress as usize] as u8
+    }
+
+    fn register(&self, register: ModbusRegister) -> u16 {
+        self.registers[register as usize]
+    }
+
+    fn alarm_is_fatal(&self) -> bool {
+        (0x11..=0x14).contains(&self.register(ModbusRegister::MotorAlarmCode))
+    }
+
+    pub(crate) fn set_position(&mut self, position: i32) {
+        self.position = position as f64;
+        self.update_telemetry();
+    }
+
+    pub(crate) fn step(&mut self, dt: Duration) {
+        let seconds = dt.as_secs_f64();
+        let enabled = self.register(ModbusRegister::EnableMotor) == 1;
+
+        let target_speed = self.register(ModbusRegister::MotorTargetSpeed) as i16 as f64;
+
+        let target = match self.goal {
+            _ if !enabled || self.alarm_is_fatal() => 0.0,
+            Some(goal) if goal == self.position => 0.0,
+            Some(goal) => target_speed.abs().copysign(goal - self.position),
+            None => target_speed,
+        };
+
+        let previous_speed = self.speed;
+        let acceleration = self.register(ModbusRegister::MotorAcceleration);
+        let change = target - self.speed;
+
+        // How long within this step we spend ramping, after which we cruise at the new speed
+        let ramp_seconds = if self.alarm_is_fatal() {
+            // The drive cuts the output, we don't bother modelling the coast down
+            self.speed = 0.0;
+            0.0
+        } else if acceleration >= NO_RAMP_ACCELERATION || change == 0.0 {
+            self.speed = target;
+            0.0
+        } else {
+            let rate = acceleration as f64 * SPEED_REGISTER_SCALE;
+            let seconds_to_target = change.abs() / rate;
+
+            if seconds_to_target >= seconds {
+                self.speed += change.signum() * rate * seconds;
+                seconds
+            } else {
+                self.speed = target;
+                seconds_to_target
+            }
+        };
+
+        // Area under the speed curve, a trapezoid for the ramp then a rectangle
+        let revolutions = ((previous_speed + self.speed) / 2.0 * ramp_seconds
+            + self.speed * (seconds - ramp_seconds))
+            / SPEED_REGISTER_SCALE
+            / 60.0;
+        let previous_position = self.position;
+        self.position += revolutions * MOTOR_ENCODER_COUNT as f64;
+
+        // We don't model braking into the target, the motor just stops dead on it
+        if let Some(goal) = self.goal {
+            if (goal - previous_position).signum() != (goal - self.position).signum() {
+                self.position = goal;
+                self.speed = 0.0;
+            }
+        }
+
+        self.update_telemetry();
+    }
+
+    fn update_telemetry(&mut self) {
+        let rpm = self.speed / SPEED_REGISTER_SCALE;
+        let position = self.position.round() as i64 as i32 as u32;
+        let pwm = (self.speed / MAX_SPEED_REGISTER * 32_767.0).clamp(-32_768.0, 32_767.0);
+        // A bit of idle draw plus something proportional to how hard we're working
+        let current_amps = 0.1 + rpm.abs() * 0.001;
+
+        self.registers[ModbusRegister::MotorCurrentSpeed as usize] =
+            self.speed.round() as i16 as u16;
+        self.registers[ModbusRegister::MotorAbsolutePositionLow as usize] = position as u16;
+        self.registers[ModbusRegister::MotorAbsolutePositionHigh as usize] =
+            (position >> 16) as u16;
+        self.registers[ModbusRegister::SystemOutputPWM as usize] = pwm as i16 as u16;
+        self.registers[ModbusRegister::MotorI as usize] =
+            (current_amps * 2000.0).min(32_767.0) as u16;
+        self.registers[ModbusRegister::MotorV as usize] =
+            (self.supply_voltage * 327.0).clamp(0.0, 32_767.0) as u16;
+        self.registers[ModbusRegister::SystemTemperature as usize] = self.temperature;
+    }
+
+    fn tick_realtime(&mut self) {
+        if !self.realtime {
+            return;
+        }
+
+        let now = Instant::now();
+        if let Some(last_tick) = self.last_tick {
+            self.step(now - last_tick);
+        }
+        self.last_tick = Some(now);
+    }
+
+    // Takes bytes off the wire, answering every complete frame addressed to us
+    pub(crate) fn receive(&mut self, bytes: &[u8]) {
+        self.incoming.extend_from_slice(bytes);
+
+        while self.incoming.len() >= 2 {
+            let frame_len = match request_length(&self.incoming) {
+                Some(RequestLength::Known(len)) => len,
+                Some(RequestLength::NeedMore) => return,
+                // Not a function we know, so no telling where it ends. Frames
+                // arrive one per write, so take the lot
+                None => self.incoming.len(),
+            };
+
+            if self.incoming.len() < frame_len {
+                return;
+            }
+
+            let frame: Vec<u8> = self.incoming.drain(..frame_len).collect();
+            let crc = ((frame[frame_len - 2] as u16) << 8) | frame[frame_len - 1] as u16;
+
+            // Real drives stay silent on a bad checksum or someone else's address
+            if frame_len < 4
+                || crc16(&frame[..frame_len - 2]) != crc
+                || frame[0] != self.device_address()
+            {
+                continue;
+            }
+
+            self.frames_received += 1;
+            if self.frames_to_ignore > 0 {
+                self.frames_to_ignore -= 1;
+                continue;
+            }
+
+            self.tick_realtime();
+            self.tick_flash_write();
+
+            if let Some(reply) = self.handle_frame(&frame[..frame_len - 2]) {
+                let mut reply = with_crc(reply);
+                if self.replies_to_corrupt > 0 {
+                    self.replies_to_corrupt -= 1;
+                    let last = reply.len() - 1;
+                    reply[last] ^= 0xff;
+                }
+                self.outgoing.extend_from_slice(&reply);
+            }
+        }
+    }
+
+    fn handle_frame(&mut self, frame: &[u8]) -> Option<Vec<u8>> {
+        let command: ModbusCommand = match frame[1].try_into() {
+            Ok(command) => command,
+            Err(_) => return Some(exception(frame, ModbusException::IllegalFunction)),
+        };
+
+        match command {
+            ModbusCommand::ReadRegister => {
+                let start = word(&frame[2..4]) as usize;
+                let count = word(&frame[4..6]) as usize;
+
+                // The most that fits in a reply, as per the spec
+                if count == 0 || count > 125 {
+                    return Some(exception(frame, ModbusException::IllegalDataValue));
+                }
+                if start + count > REGISTER_COUNT {
+                    return Some(exception(frame, ModbusException::IllegalDataAddress));
+                }
+
+                let mut reply = vec![frame[0], frame[1], (count * 2) as u8];
+                for value in &self.registers[start..start + count] {
+                    reply.push((value >> 8) as u8);
+                    reply.push(*value as u8);
+                }
+
+                Some(reply)
+            }
+            ModbusCommand::WriteRegister => {
+                let register: ModbusRegister = match word(&frame[2..4]).try_into() {
+                    Ok(register) => register,
+                    Err(_) => return Some(exception(frame, ModbusException::IllegalDataAddress)),
+                };
+                self.write_register(register, word(&frame[4..6]));
+
+                // Writes are confirmed by echoing the request back
+                Some(frame.to_vec())
+            }
+            ModbusCommand::WritePulse => {
+                let start = word(&frame[2..4]) as usize;
+                let count = word(&frame[4..6]) as usize;
+
+                if count == 0 || frame[6] as usize != count * 2 {
+                    return Some(exception(frame, ModbusException::IllegalDataValue));
+                }
+                if start + count > REGISTER_COUNT {
+                    return Some(exception(frame, ModbusException::IllegalDataAddress));
+                }
+
+                for (i, value) in frame[7..].chunks(2).enumerate() {
+                    let register: ModbusRegister = ((start + i) as u16).try_into().ok()?;
+                    // Both words of the absolute position are taken together below
+                    if register == ModbusRegister::MotorAbsolutePositionLow
+                        || register == ModbusRegister::MotorAbsolutePositionHigh
+                    {
+                        continue;
+                    }
+                    self.write_register(register, word(value));
+                }
+
+                if self.register(ModbusRegister::EnableModbus) == 1 {
+                    // Writing the target position, which is read-only to a single
+                    // register write, is how a relative move is sent
+                    let target = ModbusRegister::MotorTargetPositionLow;
+                    if let Some(distance) = block_long(frame, target) {
+                        self.registers[target as usize] = distance as u16;
+                        self.registers[target as usize + 1] = ((distance as u32) >> 16) as u16;
+
+                        if self.position_mode {
+                            self.goal = Some(self.position + distance as f64);
+                        }
+                    }
+
+                    let absolute = ModbusRegister::MotorAbsolutePositionLow;
+                    if let Some(position) = block_long(frame, absolute) {
+                        self.write_absolute_position(position);
+                    }
+                }
+
+                // Confirmed by echoing where we wrote to and how many
+                Some(frame[..6].to_vec())
+            }
+            ModbusCommand::ChangeDeviceAddress => {
+                let new_address = word(&frame[4..6]);
+
+                if word(&frame[2..4]) != ModbusRegister::DeviceAddress as u16 {
+                    return Some(exception(frame, ModbusException::IllegalDataAddress));
+                }
+                if !(1..=247).contains(&new_address) {
+                    return Some(exception(frame, ModbusException::IllegalDataValue));
+                }
+
+                if self.register(ModbusRegister::EnableModbus) == 1 {
+                    self.registers[ModbusRegister::DeviceAddress as usize] = new_address;
+                }
+
+                // Echoed back from the address the request was sent to
+                Some(frame.to_vec())
+            }
+            ModbusCommand::WriteLocation => {
+                let distance =
+                    ((word(&frame[2..4]) as u32) << 16 | word(&frame[4..6]) as u32) as i32;
+
+                // Same as writing the target position registers
+                if self.register(ModbusRegister::EnableModbus) == 1 {
+                    let target = ModbusRegister::MotorTargetPositionLow as usize;
+                    self.registers[target] = distance as u16;
+                    self.registers[target + 1] = ((distance as u32) >> 16) as u16;
+
+                    if self.position_mode {
+                        self.goal = Some(self.position + distance as f64);
+                    }
+                }
+
+                // Answers with where the motor is, low word first
+                let position = ModbusRegister::MotorAbsolutePositionLow as usize;
+                let mut reply = vec![frame[0], frame[1]];
+                for value in &self.registers[position..position + 2] {
+                    reply.push((value >> 8) as u8);
+                    reply.push(*value as u8);
+                }
+
+                Some(reply)
+            }
+        }
+    }
+
+    fn write_register(&mut self, register: ModbusRegister, value: u16) {
+        // Only the modbus enable register can be touched until modbus is enabled
+        if self.register(ModbusRegister::EnableModbus) != 1
+            && register != ModbusRegister::EnableModbus
+        {
+            return;
+        }
+
+        match register {
+            // Read-only, the drive acknowledges but does nothing
+            ModbusRegister::MotorTargetPositionLow
+            | ModbusRegister::MotorTargetPositionHigh
+            | ModbusRegister::MotorAlarmCode
+            | ModbusRegister::MotorI
+            | ModbusRegister::MotorCurrentSpeed
+            | ModbusRegister::MotorV
+            | ModbusRegister::SystemTemperature
+            | ModbusRegister::SystemOutputPWM
+            | ModbusRegister::DeviceAddress => {}
+            ModbusRegister::EnableMotor => {
+                // Disabling the output is how an alarm gets cleared
+                if value == 0 {
+                    self.registers[ModbusRegister::MotorAlarmCode as usize] = 0;
+                }
+                self.registers[register as usize] = value;
+            }
+            ModbusRegister::ParameterSavingFlag => match value {
+                1 => {
+                    self.registers[register as usize] = 1;
+                    self.saving = Some(self.flash_write_frames);
+                    self.tick_flash_write();
+                }
+                _ => {
+                    self.saved_registers = None;
+                    self.saving = None;
+                    self.registers[register as usize] = 0;
+                }
+            },
+            // Half a position can't be a move, but 0 on its own is still a clear
+            ModbusRegister::MotorAbsolutePositionLow if value == 0 => {
+                self.write_absolute_position(0)
+            }
+            ModbusRegister::MotorAbsolutePositionLow
+            | ModbusRegister::MotorAbsolutePositionHigh => {}
+            _ => self.registers[register as usize] = value,
+        }
+    }
+
+    // As the manual has it: with the electronic gear numerator at 0, writing 0
+    // clears the count. Anything else is an absolute move
+    fn write_absolute_position(&mut self, position: i32) {
+        if position == 0 && self.register(ModbusRegister::MotorElectronicGearHigh) == 0 {
+            self.goal = None;
+            self.set_position(0);
+        } else if self.position_mode {
+            self.goal = Some(position as f64);
+        }
+    }
+
+    // Counts down a save in progress, finishing it when it gets to 0
+    fn tick_flash_write(&mut self) {
+        match self.saving {
+            Some(0) => {
+                self.saving = None;
+                if self.flash_write_fails {
+                    self.registers[ModbusRegister::MotorAlarmCode as usize] = 0x20;
+                    self.registers[ModbusRegister::ParameterSavingFlag as usize] = 0;
+                } else {
+                    self.registers[ModbusRegister::ParameterSavingFlag as usize] = 2;
+                    self.saved_registers = Some(self.registers);
+                }
+            }
+            Some(left) => self.saving = Some(left - 1),
+            None => {}
+        }
+    }
+
+    // Pretend the drive was power cycled
+    pub(crate) fn power_cycle(&mut self) {
+        self.registers = self.saved_registers.unwrap_or(self.factory_registers);
+        self.registers[ModbusRegister::MotorAlarmCode as usize] = 0;
+        self.registers[ModbusRegister::ParameterSavingFlag as usize] =
+            if self.saved_registers.is_some() { 2 } else { 0 };
+        self.speed = 0.0;
+        self.position = 0.0;
+        self.goal = None;
+        self.saving = None;
+        self.incoming.clear();
+        self.outgoing.clear();
+        self.update_telemetry();
+    }
+}
+
+enum RequestLength {
+    Known(usize),
+    NeedMore,
+}
+
+// How long the request starting at the front of `buf` is going to be
+fn request_length(buf: &[u8]) -> Option<RequestLength> {
+    match ModbusCommand::try_from(buf[1]).ok()? {
+        ModbusCommand::ReadRegister
+        | ModbusCommand::WriteRegister
+        | ModbusCommand::WriteLocation
+        | ModbusCommand::ChangeDeviceAddress => Some(RequestLength::Known(8)),
+        ModbusCommand::WritePulse => match buf.get(6) {
+            Some(&byte_count) => Some(RequestLength::Known(7 + byte_count as usize + 2)),
+            None => Some(RequestLength::NeedMore),
+        },
+    }
+}
+
+// The 32-bit value a write multiple frame puts in `register` and the one after it, if it covers both
+fn block_long(frame: &[u8], register: ModbusRegister) -> Option<i32> {
+    let start = word(&frame[2..4]) as usize;
+    let count = word(&frame[4..6]) as usize;
+    let offset = (register as usize).checked_sub(start)?;
+
+    if offset + 2 > count {
+        return None;
+    }
+
+    let low = word(&frame[7 + offset * 2..]) as u32;
+    let high = word(&frame[9 + offset * 2..]) as u32;
+
+    Some(((high << 16) | low) as i32)
+}
+
+fn word(bytes: &[u8]) -> u16 {
+    ((bytes[0] as u16) << 8) | bytes[1] as u16
+}
+
+// The drive's way of saying no to `frame`
+fn exception(frame: &[u8], exception: ModbusException) -> Vec<u8> {
+    vec![frame[0], frame[1] | 0x80, exception.into()]
+}
+
+fn with_crc(mut frame: Vec<u8>) -> Vec<u8> {
+    let crc = crc16(&frame);
+    frame.push((crc >> 8) as u8);
+    frame.push(crc as u8);
+    frame
+}
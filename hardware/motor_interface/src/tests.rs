@@ -1,13 +1,47 @@
 use super::*;
 use motor_controller::MotorController;
+use crate::simulator::SimulatedDrive;
 use std::{thread::sleep as zzz, time::Duration};
 
+#[cfg(feature = "bench")]
+mod bench;
+mod bus;
+mod config;
+mod diff_drive;
+mod discovery;
+mod ffi;
+mod frame_decoder;
 mod magic_strings;
+mod odometry;
+mod poller;
+mod profile;
+mod register_info;
+mod registers;
+mod retry;
+mod shaper;
+mod simulator;
+mod telemetry;
+mod transport;
+mod watchdog;
+
+// A controller for a simulated drive at 0x01, already in modbus mode if `enabled`
+fn simulated_controller(enabled: bool) -> (MotorController, SimulatedDrive) {
+    let drive = SimulatedDrive::new(0x01);
+    let mut controller = MotorController::from_transport(drive.clone(), 0x01);
+    if enabled {
+        controller
+            .enable_modbus()
+            .unwrap_or_else(|e| panic!("Failed to enable modbus mode! {}", e));
+    }
+
+    (controller, drive)
+}
 
 const MOTOR_PATH : &str = "/dev/serial/by-id/usb-FTDI_FT232R_USB_UART_AB0KJXLJ-if00-port0";
 // const MOTOR_PATH : &str = "/dev/serial/by-id/usb-FTDI_FT232R_USB_UART_AB0KJDBY-if00-port0";
 
 #[test]
+#[ignore = "needs a motor drive plugged in at MOTOR_PATH"]
 fn this_tests_motors() {
     // NB When testing motors, both dip switches must be in the ON position (down).
     const MOTOR_SPEED: f32 = -0.5;
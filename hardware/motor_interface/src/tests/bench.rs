@@ -0,0 +1,208 @@
+use super::simulated_controller;
+use crate::bench::*;
+use crate::message::ModbusRegister;
+use crate::motor_controller::motor_status::{MotorStatus, MotorStatusFatal};
+use crate::motor_controller::MotorController;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+fn args(line: &str) -> Vec<String> {
+    line.split_whitespace().map(String::from).collect()
+}
+
+fn parse(line: &str) -> Result<Invocation, BenchError> {
+    parse_args(&args(line), None)
+}
+
+fn execute_on(controller: &mut MotorController, line: &str) -> String {
+    let command = parse(line).unwrap().command;
+    let mut out = Vec::new();
+    execute(controller, &command, &mut out, &AtomicBool::new(false)).unwrap();
+
+    String::from_utf8(out).unwrap()
+}
+
+#[test]
+fn options_can_go_anywhere() {
+    let invocation =
+        parse("write --address 0x02 MotorTargetSpeed -500 --port /dev/ttyUSB0").unwrap();
+
+    assert_eq!(Some("/dev/ttyUSB0".into()), invocation.port);
+    assert_eq!(0x02, invocation.address);
+    assert_eq!(
+        Command::Write(ModbusRegister::MotorTargetSpeed, -500i16 as u16),
+        invocation.command
+    );
+
+    assert_eq!(
+        Command::Rpm {
+            rpm: 150.0,
+            hold: Some(Duration::from_millis(1_500))
+        },
+        parse("rpm 150 --for 1.5").unwrap().command
+    );
+    assert_eq!(
+        Command::Scan {
+            baud_rates: vec![9_600, 19_200],
+            addresses: 1..=4
+        },
+        parse("scan --baud 9600 --addresses 1-4 --baud 19200")
+            .unwrap()
+            .command
+    );
+    assert_eq!(
+        Command::Read(Some(ModbusRegister::MotorAcceleration)),
+        parse("read 0x03").unwrap().command
+    );
+    assert_eq!(
+        Some("/dev/ttyS1".into()),
+        parse_args(&args("enable"), Some("/dev/ttyS1".into()))
+            .unwrap()
+            .port
+    );
+}
+
+#[test]
+fn nonsense_is_a_usage_error() {
+    for line in [
+        "",
+        "spin",
+        "read MotorWarpFactor",
+        "write MotorAcceleration",
+        "write MotorAcceleration -1",
+        "rpm fast",
+        "enable please",
+        "dump",
+    ] {
+        assert!(matches!(parse(line), Err(BenchError::Usage(_))), "{line:?}");
+    }
+}
+
+#[test]
+fn drive_commands_go_through_the_controller() {
+    let (mut controller, drive) = simulated_controller(false);
+
+    assert_eq!("Motor enabled\n", execute_on(&mut controller, "enable"));
+    assert_eq!(1, drive.register(ModbusRegister::EnableMotor));
+
+    assert_eq!(
+        "MotorTargetSpeed = -1505 (-150.5 rpm)\nStopped\n",
+        execute_on(&mut controller, "rpm -150.5 --for 0")
+    );
+    assert_eq!(
+        "MotorAcceleration = 8000 (8000.0 rpm/s)\n",
+        execute_on(&mut controller, "write MotorAcceleration 8000")
+    );
+
+    let all = execute_on(&mut controller, "read");
+    assert_eq!(ModbusRegister::ALL.len(), all.lines().count());
+    assert!(all.contains("\nMotorAcceleration = 8000 (8000.0 rpm/s)\n"));
+
+    // Out of range for the register, so refused before it's sent
+    let mut out = Vec::new();
+    assert!(matches!(
+        execute(
+            &mut controller,
+            &parse("write MotorSpecialFunction 9").unwrap().command,
+            &mut out,
+            &AtomicBool::new(false)
+        ),
+        Err(BenchError::Controller(_))
+    ));
+}
+
+#[test]
+fn clear_alarm_says_what_it_cleared() {
+    let (mut controller, drive) = simulated_controller(false);
+    execute_on(&mut controller, "enable");
+    drive.raise_alarm(MotorStatus::Fatal(MotorStatusFatal::SystemStall));
+
+    assert_eq!(
+        "Alarm was Fatal(SystemStall), now None\n",
+        execute_on(&mut controller, "clear-alarm")
+    );
+    // Back on, as it was before
+    assert_eq!(1, drive.register(ModbusRegister::EnableMotor));
+
+    execute_on(&mut controller, "disable");
+    drive.raise_alarm(MotorStatus::Fatal(MotorStatusFatal::SystemStall));
+    execute_on(&mut controller, "clear-alarm");
+    assert_eq!(0, drive.register(ModbusRegister::EnableMotor));
+}
+
+#[test]
+fn rpm_runs_until_interrupted() {
+    // Ctrl-C has to cut a long --for short too
+    for (line, expected) in [
+        ("rpm 150", "Running, Ctrl-C to stop\nStopped\n"),
+        ("rpm 150 --for 600", "Stopped\n"),
+    ] {
+        let (mut controller, drive) = simulated_controller(false);
+        execute_on(&mut controller, "enable");
+        let interrupted = AtomicBool::new(false);
+        let mut out = Vec::new();
+        let started = Instant::now();
+
+        thread::scope(|scope| {
+            scope.spawn(|| {
+                thread::sleep(Duration::from_millis(100));
+                // Still going well after it was set
+                assert_eq!(1_500, drive.register(ModbusRegister::MotorTargetSpeed));
+                interrupted.store(true, Ordering::SeqCst);
+            });
+
+            let command = parse(line).unwrap().command;
+            execute(&mut controller, &command, &mut out, &interrupted).unwrap();
+        });
+
+        assert!(started.elapsed() < Duration::from_secs(5), "{line:?}");
+        assert_eq!(
+            format!("MotorTargetSpeed = 1500 (150.0 rpm)\n{expected}"),
+            String::from_utf8(out).unwrap()
+        );
+        drop(controller);
+        assert_eq!(0, drive.register(ModbusRegister::MotorTargetSpeed));
+    }
+}
+
+#[test]
+fn watch_prints_a_line_per_reading() {
+    let (mut controller, drive) = simulated_controller(false);
+    drive.set_position(1_234);
+
+    let out = execute_on(&mut controller, "watch --period 1 --count 3");
+
+    assert_eq!(3, out.lines().count());
+    assert!(out.lines().all(|line| line.ends_with("position 1234")));
+}
+
+#[test]
+fn profiles_dump_diff_and_load() {
+    let (mut controller, drive) = simulated_controller(false);
+    execute_on(&mut controller, "enable");
+    let path =
+        std::env::temp_dir().join(format!("happy-bench-profile-{}.toml", std::process::id()));
+    let path = path.to_str().unwrap();
+
+    assert!(execute_on(&mut controller, &format!("dump {path}")).starts_with("Saved 11 parameters"));
+    assert!(execute_on(&mut controller, "dump -").starts_with("MotorAcceleration = 20000\n"));
+
+    drive.set_register(ModbusRegister::MotorSpeedFilterFrequency, 250);
+    assert_eq!(
+        "MotorSpeedFilterFrequency: drive 250, file 100\n",
+        execute_on(&mut controller, &format!("diff {path}"))
+    );
+
+    assert_eq!(
+        "Wrote 11 parameters\nSaved to flash\n",
+        execute_on(&mut controller, &format!("load {path} --persist"))
+    );
+    assert_eq!(
+        100,
+        drive.register(ModbusRegister::MotorSpeedFilterFrequency)
+    );
+    assert!(execute_on(&mut controller, &format!("diff {path}")).starts_with("Drive matches"));
+
+    std::fs::remove_file(path).unwrap();
+}
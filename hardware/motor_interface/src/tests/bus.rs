@@ -0,0 +1,155 @@
+use crate::crc::crc16;
+use crate::message::ModbusRegister;
+use crate::motor_controller::bus::MotorBus;
+use crate::motor_controller::error::MotorControllerError;
+use crate::motor_controller::retry::RetryPolicy;
+use crate::simulator::{SimulatedBus, SimulatedDrive};
+use crate::transport::{LoopbackTransport, Transport};
+use std::io::{Read, Write};
+use std::thread;
+use std::time::{Duration, Instant};
+
+fn simulated_bus(addresses: &[u8]) -> (MotorBus, Vec<SimulatedDrive>) {
+    let drives: Vec<_> = addresses.iter().map(|&a| SimulatedDrive::new(a)).collect();
+    let bus = MotorBus::from_transport(SimulatedBus::new(drives.clone()));
+
+    (bus, drives)
+}
+
+#[test]
+fn bus_talks_to_each_drive_separately() {
+    let (bus, drives) = simulated_bus(&[0x01, 0x02]);
+
+    let mut left = bus.controller(0x01).unwrap();
+    let mut right = bus.controller(0x02).unwrap();
+
+    left.enable_modbus().unwrap();
+    right.enable_modbus().unwrap();
+    left.set_rpm(100).unwrap();
+    right.set_rpm(-250).unwrap();
+
+    assert_eq!(
+        100,
+        drives[0].register(ModbusRegister::MotorTargetSpeed) as i16
+    );
+    assert_eq!(
+        -250,
+        drives[1].register(ModbusRegister::MotorTargetSpeed) as i16
+    );
+}
+
+#[test]
+fn bus_serialises_controllers_on_different_threads() {
+    let (bus, _drives) = simulated_bus(&[0x01, 0x02, 0x03]);
+
+    let workers: Vec<_> = (1..=3)
+        .map(|address| {
+            let mut controller = bus.controller(address).unwrap();
+            thread::spawn(move || {
+                controller.enable_modbus().unwrap();
+                for rpm in 0..20 {
+                    let rpm = rpm * address as i16;
+                    controller.set_rpm(rpm).unwrap();
+                    assert_eq!(
+                        address as u16,
+                        controller
+                            .read_register(ModbusRegister::DeviceAddress)
+                            .unwrap()
+                    );
+                }
+            })
+        })
+        .collect();
+
+    for worker in workers {
+        worker.join().unwrap();
+    }
+}
+
+#[test]
+fn bus_leaves_a_gap_between_frames() {
+    const TRANSACTIONS: u32 = 10;
+
+    let (bus, _drives) = simulated_bus(&[0x01]);
+    let mut controller = bus.controller(0x01).unwrap();
+
+    let start = Instant::now();
+    for _ in 0..TRANSACTIONS {
+        controller.get_rpm().unwrap();
+    }
+
+    // 3.5 characters at 19200 baud is about 2ms
+    assert!(start.elapsed() >= Duration::from_millis(2) * (TRANSACTIONS - 1));
+}
+
+#[test]
+fn bus_rejects_reply_from_the_wrong_drive() {
+    let (host, mut drive) = LoopbackTransport::pair();
+
+    let responder = thread::spawn(move || {
+        let mut request = [0u8; 8];
+        drive.read_exact(&mut request).unwrap();
+
+        // Drive 2 answers a question meant for drive 1
+        let mut response = vec![0x02, 0x03, 0x02, 0x00, 0x10];
+        let crc = crc16(&response);
+        response.extend([(crc >> 8) as u8, crc as u8]);
+        drive.write_all(&response).unwrap();
+
+        drive
+    });
+
+    let mut bus = MotorBus::from_transport(host);
+    bus.set_retry_policy(RetryPolicy::never());
+    let mut controller = bus.controller(0x01).unwrap();
+
+    assert!(matches!(
+        controller.get_rpm(),
+        Err(MotorControllerError::InvalidResponder(0x01, 0x02))
+    ));
+
+    responder.join().unwrap();
+}
+
+#[test]
+fn bus_drops_late_replies_before_the_next_request() {
+    let (mut host, mut drive) = LoopbackTransport::pair();
+    host.set_timeout(Duration::from_millis(50)).unwrap();
+
+    // A reply to some earlier request turns up after the host stopped waiting
+    drive
+        .write_all(&[0x01, 0x03, 0x02, 0xde, 0xad, 0x00, 0x00])
+        .unwrap();
+
+    let responder = thread::spawn(move || {
+        let mut request = [0u8; 8];
+        drive.read_exact(&mut request).unwrap();
+
+        let mut response = vec![0x01, 0x03, 0x02, 0x00, 0x10];
+        let crc = crc16(&response);
+        response.extend([(crc >> 8) as u8, crc as u8]);
+        drive.write_all(&response).unwrap();
+
+        drive
+    });
+
+    let bus = MotorBus::from_transport(host);
+    let mut controller = bus.controller(0x01).unwrap();
+    assert_eq!(0x10, controller.get_rpm().unwrap());
+
+    responder.join().unwrap();
+}
+
+#[test]
+fn bus_refuses_reserved_addresses() {
+    let (bus, _drives) = simulated_bus(&[0x01]);
+
+    assert!(matches!(
+        bus.controller(0x00),
+        Err(MotorControllerError::InvalidDeviceAddress(0x00))
+    ));
+    assert!(matches!(
+        bus.controller(248),
+        Err(MotorControllerError::InvalidDeviceAddress(248))
+    ));
+}
@@ -0,0 +1,167 @@
+use super::simulated_controller;
+use crate::ffi::*;
+use crate::message::ModbusRegister;
+use crate::motor_controller::config::{ConfigError, MotorConfig};
+use crate::motor_controller::constants::*;
+use std::ffi::CString;
+
+#[test]
+fn defaults_are_the_old_constants() {
+    let config = MotorConfig::default();
+
+    assert_eq!(MOTOR_GEAR, config.gear);
+    assert_eq!(MOTOR_WHEEL_LENGTH, config.wheel_length);
+    assert_eq!(MOTOR_ENCODER_COUNT, config.encoder_count);
+    assert_eq!(MOTOR_WHEEL_DIST, config.wheel_separation);
+    assert_eq!(MOTOR_BAUD_RATE, config.baud_rate);
+    assert_eq!(MOTOR_CONNECTION_TIMEOUT, config.connection_timeout());
+    config.validate().unwrap();
+}
+
+#[test]
+fn files_only_need_what_is_different() {
+    let toml = MotorConfig::from_toml("gear = 20\nwheel_length = 0.6\n").unwrap();
+    let yaml = MotorConfig::from_yaml("gear: 20\nwheel_length: 0.6\n").unwrap();
+
+    let expected = MotorConfig {
+        gear: 20,
+        wheel_length: 0.6,
+        ..MotorConfig::default()
+    };
+    assert_eq!(expected, toml);
+    assert_eq!(expected, yaml);
+}
+
+#[test]
+fn files_are_checked() {
+    assert!(matches!(
+        MotorConfig::from_toml("gears = 20\n"),
+        Err(ConfigError::Toml(_))
+    ));
+    assert!(matches!(
+        MotorConfig::from_yaml("gear: 0\n"),
+        Err(ConfigError::Invalid("gear", _))
+    ));
+    assert!(matches!(
+        MotorConfig::from_toml("max_rpm = 5000\n"),
+        Err(ConfigError::Invalid("max_rpm", _))
+    ));
+    assert!(matches!(
+        MotorConfig::load("robot.json"),
+        Err(ConfigError::IOError(_))
+    ));
+}
+
+#[test]
+fn load_picks_the_format_from_the_extension() {
+    let dir = std::env::temp_dir();
+    let toml = dir.join(format!("happy-motor-config-{}.toml", std::process::id()));
+    let yaml = dir.join(format!("happy-motor-config-{}.yml", std::process::id()));
+    let other = dir.join(format!("happy-motor-config-{}.ini", std::process::id()));
+    std::fs::write(&toml, "wheel_separation = 0.6\n").unwrap();
+    std::fs::write(&yaml, "wheel_separation: 0.6\n").unwrap();
+    std::fs::write(&other, "wheel_separation = 0.6\n").unwrap();
+
+    assert_eq!(0.6, MotorConfig::load(&toml).unwrap().wheel_separation);
+    assert_eq!(0.6, MotorConfig::load(&yaml).unwrap().wheel_separation);
+    assert!(matches!(
+        MotorConfig::load(&other),
+        Err(ConfigError::UnknownFormat(_))
+    ));
+
+    for path in [toml, yaml, other] {
+        std::fs::remove_file(path).unwrap();
+    }
+}
+
+#[test]
+fn set_by_name_leaves_bad_values_alone() {
+    let mut config = MotorConfig::default();
+
+    config.set("encoder_count", " 1024 ").unwrap();
+    assert_eq!(1024, config.encoder_count);
+
+    assert!(matches!(
+        config.set("encoder_count", "lots"),
+        Err(ConfigError::BadValue(..))
+    ));
+    assert!(matches!(
+        config.set("wheels", "2"),
+        Err(ConfigError::UnknownParameter(_))
+    ));
+    assert_eq!(1024, config.encoder_count);
+}
+
+#[test]
+fn controller_uses_its_config() {
+    let (mut controller, drive) = simulated_controller(false);
+    controller.enable_modbus().unwrap();
+
+    controller.set_velocity(0.5).unwrap();
+    let default_speed = drive.register(ModbusRegister::MotorTargetSpeed) as i16;
+
+    controller
+        .set_config(MotorConfig {
+            gear: MOTOR_GEAR * 2,
+            ..MotorConfig::default()
+        })
+        .unwrap();
+    let velocity = controller.set_velocity(0.5).unwrap();
+
+    assert!((velocity - 0.5).abs() < 0.001);
+    let geared_speed = drive.register(ModbusRegister::MotorTargetSpeed) as i16;
+    assert!((geared_speed - 2 * default_speed).abs() <= 1);
+
+    assert!(controller
+        .set_config(MotorConfig {
+            wheel_length: f32::NAN,
+            ..MotorConfig::default()
+        })
+        .is_err());
+    assert_eq!(MOTOR_GEAR * 2, controller.config().gear);
+}
+
+#[test]
+fn ffi_sets_parameters_by_name() {
+    let mut config = motor_config_default();
+    let key = CString::new("wheel_length").unwrap();
+    let value = CString::new("0.25").unwrap();
+    let bad = CString::new("-1").unwrap();
+
+    unsafe {
+        assert_eq!(
+            MotorControllerStatus::Ok,
+            motor_config_set(&mut config, key.as_ptr(), value.as_ptr())
+        );
+        assert_eq!(0.25, config.wheel_length);
+        assert_eq!(MotorControllerStatus::Ok, motor_config_validate(&config));
+
+        assert_eq!(
+            MotorControllerStatus::Ok,
+            motor_config_set(&mut config, key.as_ptr(), bad.as_ptr())
+        );
+        assert_eq!(
+            MotorControllerStatus::InvalidArgument,
+            motor_config_validate(&config)
+        );
+    }
+
+    let mut diff_drive = diff_drive_config_default();
+    let polarity = CString::new("right_polarity").unwrap();
+    let reversed = CString::new("reversed").unwrap();
+    unsafe {
+        assert_eq!(
+            MotorControllerStatus::Ok,
+            diff_drive_config_set(&mut diff_drive, polarity.as_ptr(), reversed.as_ptr())
+        );
+        assert_eq!(
+            MotorControllerStatus::Ok,
+            diff_drive_config_set(&mut diff_drive, key.as_ptr(), value.as_ptr())
+        );
+    }
+    assert_eq!(
+        crate::motor_controller::diff_drive::WheelPolarity::Reversed,
+        diff_drive.right_polarity
+    );
+    assert_eq!(0.25, diff_drive.motor.wheel_length);
+}
@@ -0,0 +1,186 @@
+use crate::message::ModbusRegister;
+use crate::motor_controller::bus::MotorBus;
+use crate::motor_controller::config::MotorConfig;
+use crate::motor_controller::diff_drive::*;
+use crate::motor_controller::retry::RetryPolicy;
+use crate::motor_controller::shaper::MotionLimits;
+use crate::motor_controller::MotorController;
+use crate::simulator::{SimulatedBus, SimulatedDrive};
+use crate::transport::{LoopbackTransport, Transport};
+use std::time::Duration;
+
+fn config() -> DiffDriveConfig {
+    DiffDriveConfig {
+        // Tops out at 1 m/s
+        motor: MotorConfig {
+            wheel_length: 0.32,
+            wheel_separation: 0.5,
+            ..MotorConfig::default()
+        },
+        left_polarity: WheelPolarity::Forward,
+        right_polarity: WheelPolarity::Reversed,
+        ..DiffDriveConfig::default()
+    }
+}
+
+fn simulated_base() -> (DiffDriveBase, SimulatedDrive, SimulatedDrive) {
+    let left = SimulatedDrive::new(0x01);
+    let right = SimulatedDrive::new(0x02);
+    let bus = MotorBus::from_transport(SimulatedBus::new([left.clone(), right.clone()]));
+
+    let base = DiffDriveBase::from_controllers(
+        bus.controller(0x01).unwrap(),
+        bus.controller(0x02).unwrap(),
+        config(),
+    )
+    .unwrap();
+
+    (base, left, right)
+}
+
+#[test]
+fn twist_turns_into_wheel_speeds() {
+    let speeds = wheel_speeds(&config(), 0.5, 1.0);
+    assert!((speeds.left - 0.25).abs() < 1e-6);
+    assert!((speeds.right - 0.75).abs() < 1e-6);
+
+    let spin = wheel_speeds(&config(), 0.0, -2.0);
+    assert!((spin.left - 0.5).abs() < 1e-6);
+    assert!((spin.right + 0.5).abs() < 1e-6);
+
+    assert_eq!(
+        WheelSpeeds::default(),
+        wheel_speeds(&config(), f32::NAN, 1.0)
+    );
+}
+
+#[test]
+fn saturation_keeps_the_same_arc() {
+    let (linear, angular) = (2.0, 4.0);
+    let speeds = wheel_speeds(&config(), linear, angular);
+
+    assert!((speeds.right - 1.0).abs() < 1e-6);
+    // Same ratio of turning to driving as was asked for
+    let achieved_linear = (speeds.left + speeds.right) / 2.0;
+    let achieved_angular = (speeds.right - speeds.left) / config().motor.wheel_separation;
+    assert!((achieved_angular / achieved_linear - angular / linear).abs() < 1e-5);
+}
+
+#[test]
+fn set_twist_applies_polarity() {
+    let (mut base, left, right) = simulated_base();
+    base.enable().unwrap();
+
+    let speeds = base.set_twist(0.3, 0.0).unwrap();
+
+    assert!((speeds.left - 0.3).abs() < 0.001);
+    assert!((speeds.right - 0.3).abs() < 0.001);
+    assert!(left.register(ModbusRegister::MotorTargetSpeed) as i16 > 0);
+    assert!((right.register(ModbusRegister::MotorTargetSpeed) as i16) < 0);
+}
+
+#[test]
+fn sample_reads_both_wheels_forwards() {
+    let (mut base, left, right) = simulated_base();
+    base.enable().unwrap();
+    left.set_position(64_000);
+    right.set_position(-64_000);
+
+    let sample = base.sample().unwrap();
+
+    // 64000 counts is one turn of the wheel
+    assert!((sample.left.position - 0.32).abs() < 1e-4);
+    assert!((sample.right.position - 0.32).abs() < 1e-4);
+    assert_eq!(64_000, sample.right.counts);
+}
+
+#[test]
+fn left_wheel_stops_if_the_right_one_cannot_be_told() {
+    let left = SimulatedDrive::new(0x01);
+    let (mut host, _nobody) = LoopbackTransport::pair();
+    host.set_timeout(Duration::from_millis(5)).unwrap();
+    let mut right = MotorController::from_transport(host, 0x02);
+    right.set_retry_policy(RetryPolicy::never());
+
+    let mut base = DiffDriveBase::from_controllers(
+        MotorController::from_transport(left.clone(), 0x01),
+        right,
+        config(),
+    )
+    .unwrap();
+    base.left().enable_modbus().unwrap();
+
+    let error = base.set_twist(0.5, 0.0).unwrap_err();
+
+    assert!(matches!(error, DiffDriveError::Right(_)));
+    assert_eq!(0, left.register(ModbusRegister::MotorTargetSpeed));
+}
+
+#[test]
+fn tick_ramps_the_twist_within_the_limits() {
+    let left = SimulatedDrive::new(0x01);
+    let right = SimulatedDrive::new(0x02);
+    let bus = MotorBus::from_transport(SimulatedBus::new([left.clone(), right.clone()]));
+    let mut base = DiffDriveBase::from_controllers(
+        bus.controller(0x01).unwrap(),
+        bus.controller(0x02).unwrap(),
+        DiffDriveConfig {
+            linear_limits: MotionLimits {
+                acceleration: 0.5,
+                deceleration: 1.0,
+                emergency_deceleration: 2.0,
+                ..MotionLimits::default()
+            },
+            ..config()
+        },
+    )
+    .unwrap();
+    base.enable().unwrap();
+    let tick = Duration::from_millis(100);
+
+    base.set_twist_target(0.4, 0.0);
+    let first = base.tick(tick).unwrap();
+    assert!((first.left - 0.05).abs() < 0.001);
+    assert!((first.right - 0.05).abs() < 0.001);
+    assert!(left.register(ModbusRegister::MotorTargetSpeed) as i16 > 0);
+
+    for _ in 0..10 {
+        base.tick(tick).unwrap();
+    }
+    let cruising = base.tick(tick).unwrap();
+    assert!((cruising.left - 0.4).abs() < 0.001);
+
+    base.emergency_stop();
+    assert!((base.tick(tick).unwrap().left - 0.2).abs() < 0.001);
+    assert_eq!(WheelSpeeds::default(), base.tick(tick).unwrap());
+    assert_eq!(0, left.register(ModbusRegister::MotorTargetSpeed));
+    assert_eq!(0, right.register(ModbusRegister::MotorTargetSpeed));
+
+    base.resume();
+    assert!(base.tick(tick).unwrap().left > 0.0);
+}
+
+#[test]
+fn limits_must_be_sensible() {
+    let left = SimulatedDrive::new(0x01);
+    let right = SimulatedDrive::new(0x02);
+    let result = DiffDriveBase::from_controllers(
+        MotorController::from_transport(left, 0x01),
+        MotorController::from_transport(right, 0x02),
+        DiffDriveConfig {
+            wheel_limits: MotionLimits {
+                jerk: f32::NAN,
+                ..MotionLimits::default()
+            },
+            ..config()
+        },
+    );
+
+    assert!(matches!(result, Err(DiffDriveError::InvalidConfig(_))));
+
+    let mut config = config();
+    config.set("wheel_emergency_deceleration", "3.5").unwrap();
+    config.set("wheel_length", "0.4").unwrap();
+    assert_eq!(3.5, config.wheel_limits.emergency_deceleration);
+    assert_eq!(0.4, config.motor.wheel_length);
+}
@@ -0,0 +1,76 @@
+use crate::motor_controller::discovery::{discover, DiscoveredDrive, DiscoveryOptions};
+use crate::motor_controller::motor_status::{MotorStatus, MotorStatusFatal};
+use crate::simulator::{SimulatedBus, SimulatedDrive};
+use crate::transport::LoopbackTransport;
+
+fn quick_options() -> DiscoveryOptions {
+    DiscoveryOptions {
+        addresses: 1..=16,
+        ..DiscoveryOptions::default()
+    }
+}
+
+#[test]
+fn discovery_finds_every_drive_on_the_bus() {
+    let left = SimulatedDrive::new(0x03);
+    let right = SimulatedDrive::new(0x0b);
+    right.raise_alarm(MotorStatus::Fatal(MotorStatusFatal::Overheat));
+
+    let found = discover(SimulatedBus::new([left, right]), &quick_options()).unwrap();
+
+    assert_eq!(
+        vec![
+            DiscoveredDrive {
+                baud_rate: 19_200,
+                device_address: 0x03,
+                status: Some(MotorStatus::None),
+                parameters_saved: Some(false),
+            },
+            DiscoveredDrive {
+                baud_rate: 19_200,
+                device_address: 0x0b,
+                status: Some(MotorStatus::Fatal(MotorStatusFatal::Overheat)),
+                parameters_saved: Some(false),
+            },
+        ],
+        found
+    );
+}
+
+#[test]
+fn discovery_finds_drives_at_other_baud_rates() {
+    let drive = SimulatedDrive::new(0x07);
+    drive.configure_baud_rate(57_600);
+
+    let found = discover(drive, &quick_options()).unwrap();
+
+    assert_eq!(1, found.len());
+    assert_eq!(57_600, found[0].baud_rate);
+    assert_eq!(0x07, found[0].device_address);
+}
+
+#[test]
+fn discovery_skips_baud_rates_it_was_not_asked_about() {
+    let drive = SimulatedDrive::new(0x07);
+    drive.configure_baud_rate(9_600);
+
+    let options = DiscoveryOptions {
+        baud_rates: vec![19_200],
+        ..quick_options()
+    };
+
+    assert!(discover(drive, &options).unwrap().is_empty());
+}
+
+#[test]
+fn discovery_finds_nothing_on_an_empty_bus() {
+    let (host, _nobody) = LoopbackTransport::pair();
+
+    let options = DiscoveryOptions {
+        addresses: 1..=3,
+        baud_rates: vec![19_200],
+        ..DiscoveryOptions::default()
+    };
+
+    assert!(discover(host, &options).unwrap().is_empty());
+}
@@ -0,0 +1,218 @@
+use super::simulated_controller;
+use crate::ffi::*;
+use crate::message::ModbusRegister;
+use crate::motor_controller::motor_status::{MotorStatus, MotorStatusFatal};
+use crate::motor_controller::MotorController;
+use crate::simulator::SimulatedDrive;
+use crate::transport::LoopbackTransport;
+use crate::transport::Transport;
+use std::ffi::{CStr, CString};
+use std::time::Duration;
+
+fn last_error() -> String {
+    let message = unsafe { motor_controller_last_error() };
+    assert!(!message.is_null());
+
+    unsafe { CStr::from_ptr(message) }
+        .to_string_lossy()
+        .into_owned()
+}
+
+fn boxed_controller() -> (*mut MotorController, SimulatedDrive) {
+    let (controller, drive) = simulated_controller(false);
+
+    (Box::into_raw(Box::new(controller)), drive)
+}
+
+#[test]
+fn ffi_reads_back_through_out_parameters() {
+    let (ptr, drive) = boxed_controller();
+    drive.set_position(12_345);
+
+    unsafe {
+        assert_eq!(
+            MotorControllerStatus::Ok,
+            motor_controller_enable_modbus(ptr)
+        );
+        assert_eq!(
+            MotorControllerStatus::Ok,
+            motor_controller_set_motor_enabled(ptr)
+        );
+
+        let mut position = 0;
+        assert_eq!(
+            MotorControllerStatus::Ok,
+            motor_controller_get_position(ptr, &mut position)
+        );
+        assert_eq!(12_345, position);
+
+        let mut velocity = f32::NAN;
+        assert_eq!(
+            MotorControllerStatus::Ok,
+            motor_controller_set_velocity(ptr, 0.5, &mut velocity)
+        );
+        assert!((velocity - 0.5).abs() < 0.001);
+        assert_eq!(
+            MotorControllerStatus::Ok,
+            motor_controller_set_velocity(ptr, 0.0, std::ptr::null_mut())
+        );
+
+        motor_controller_free(ptr);
+    }
+}
+
+#[test]
+fn ffi_reports_null_pointers() {
+    unsafe {
+        assert_eq!(
+            MotorControllerStatus::NullPointer,
+            motor_controller_enable_modbus(std::ptr::null_mut())
+        );
+        assert_eq!("motor controller is null", last_error());
+
+        let (ptr, _drive) = boxed_controller();
+        assert_eq!(
+            MotorControllerStatus::NullPointer,
+            motor_controller_get_velocity(ptr, std::ptr::null_mut())
+        );
+        assert_eq!("out_velocity is null", last_error());
+
+        motor_controller_free(ptr);
+        motor_controller_free(std::ptr::null_mut());
+    }
+}
+
+#[test]
+fn ffi_reports_alarms_and_telemetry() {
+    let (ptr, drive) = boxed_controller();
+    drive.set_supply_voltage(24.0);
+    drive.set_temperature(41);
+    drive.raise_alarm(MotorStatus::Fatal(MotorStatusFatal::UnderVoltage));
+
+    unsafe {
+        let mut alarm = MotorAlarm::None;
+        assert_eq!(
+            MotorControllerStatus::Ok,
+            motor_controller_get_status(ptr, &mut alarm)
+        );
+        assert_eq!(MotorAlarm::UnderVoltage, alarm);
+        assert!(motor_alarm_is_fatal(alarm));
+        assert!(!motor_alarm_is_fatal(MotorAlarm::HighTemperature));
+
+        let mut voltage = 0.0;
+        assert_eq!(
+            MotorControllerStatus::Ok,
+            motor_controller_get_voltage(ptr, &mut voltage)
+        );
+        assert!((voltage - 24.0).abs() < 0.01);
+
+        let mut temperature = 0;
+        assert_eq!(
+            MotorControllerStatus::Ok,
+            motor_controller_get_temperature(ptr, &mut temperature)
+        );
+        assert_eq!(41, temperature);
+
+        let mut stats = std::mem::zeroed();
+        assert_eq!(
+            MotorControllerStatus::Ok,
+            motor_controller_get_retry_stats(ptr, &mut stats)
+        );
+        assert_eq!(3, stats.requests);
+
+        motor_controller_free(ptr);
+    }
+}
+
+#[test]
+fn ffi_reads_and_writes_registers_by_number() {
+    let (ptr, drive) = boxed_controller();
+
+    unsafe {
+        assert_eq!(
+            MotorControllerStatus::Ok,
+            motor_controller_enable_modbus(ptr)
+        );
+        assert_eq!(
+            MotorControllerStatus::Ok,
+            motor_controller_write_register(ptr, 0x03, 1_234)
+        );
+        assert_eq!(1_234, drive.register(ModbusRegister::MotorAcceleration));
+
+        let mut value = 0;
+        assert_eq!(
+            MotorControllerStatus::Ok,
+            motor_controller_read_register(ptr, 0x03, &mut value)
+        );
+        assert_eq!(1_234, value);
+
+        let frames = drive.frames_received();
+        assert_eq!(
+            MotorControllerStatus::InvalidArgument,
+            motor_controller_read_register(ptr, 0x40, &mut value)
+        );
+        assert_eq!("No register 0x0040", last_error());
+        assert_eq!(frames, drive.frames_received());
+
+        let mut rpm = 0;
+        assert_eq!(
+            MotorControllerStatus::Ok,
+            motor_controller_set_rpm(ptr, 150, &mut rpm)
+        );
+        assert_eq!(150, rpm);
+
+        motor_controller_free(ptr);
+    }
+}
+
+#[test]
+fn ffi_reports_a_port_that_cannot_be_opened() {
+    let path = CString::new("/dev/this-is-not-a-motor").unwrap();
+    let mut ptr: *mut MotorController = std::ptr::null_mut();
+
+    let status = unsafe { motor_controller_new(path.as_ptr(), 0x01, &mut ptr) };
+
+    assert_eq!(MotorControllerStatus::SerialError, status);
+    assert!(ptr.is_null());
+    assert!(last_error().contains("serial"));
+}
+
+#[test]
+fn ffi_reports_timeouts() {
+    let (mut host, _drive) = LoopbackTransport::pair();
+    host.set_timeout(Duration::from_millis(5)).unwrap();
+    let ptr = Box::into_raw(Box::new(MotorController::from_transport(host, 0x01)));
+
+    let mut velocity = 0.0;
+    let status = unsafe { motor_controller_get_velocity(ptr, &mut velocity) };
+
+    assert_eq!(MotorControllerStatus::Timeout, status);
+    assert!(!last_error().is_empty());
+
+    unsafe { motor_controller_free(ptr) };
+}
+
+#[test]
+fn ffi_last_error_is_per_thread() {
+    unsafe {
+        motor_controller_enable_modbus(std::ptr::null_mut());
+    }
+
+    let other = std::thread::spawn(|| unsafe { motor_controller_last_error().is_null() });
+    assert!(other.join().unwrap());
+}
+
+#[test]
+fn checked_in_header_matches_ffi() {
+    let generated = include_str!(concat!(env!("OUT_DIR"), "/motor_interface.h"));
+    let checked_in = std::fs::read_to_string(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/../happy_ros2_control/include/motor_interface/motor_interface.h"
+    ))
+    .expect("Failed to read the checked-in header!");
+
+    assert!(
+        generated == checked_in,
+        "motor_interface.h is out of date with src/ffi.rs, rebuild with HAPPY_UPDATE_HEADER=1 set"
+    );
+}
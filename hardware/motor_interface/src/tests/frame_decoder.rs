@@ -0,0 +1,189 @@
+use crate::crc::crc16;
+use crate::message::ModbusFrameDecoder;
+use crate::motor_controller::MotorController;
+use crate::transport::LoopbackTransport;
+use std::io::{Read, Write};
+use std::thread;
+
+// Reply to a read of the current speed, 0x0010
+const GET_RPM_RESPONSE: [u8; 7] = [0x01, 0x03, 0x02, 0x00, 0x10, 0xb9, 0x88];
+
+// xorshift, so the fuzzing is random enough but the same every run
+struct Noise(u64);
+
+impl Noise {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn byte(&mut self) -> u8 {
+        self.next() as u8
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+
+    // Up to `max` random bytes
+    fn bytes(&mut self, max: usize) -> Vec<u8> {
+        let n = self.below(max);
+        (0..n).map(|_| self.byte()).collect()
+    }
+}
+
+fn has_good_crc(frame: &[u8]) -> bool {
+    let crc = ((frame[frame.len() - 2] as u16) << 8) | frame[frame.len() - 1] as u16;
+    crc16(&frame[..frame.len() - 2]) == crc
+}
+
+fn decode_all(decoder: &mut ModbusFrameDecoder) -> Vec<Vec<u8>> {
+    std::iter::from_fn(|| decoder.next_frame()).collect()
+}
+
+#[test]
+fn decoder_checks_the_test_frame() {
+    assert!(has_good_crc(&GET_RPM_RESPONSE));
+}
+
+#[test]
+fn decoder_skips_junk_in_front_of_a_frame() {
+    let mut decoder = ModbusFrameDecoder::new();
+
+    decoder.push(&[0x00, 0xff, 0x13]);
+    decoder.push(&GET_RPM_RESPONSE);
+
+    assert_eq!(vec![GET_RPM_RESPONSE.to_vec()], decode_all(&mut decoder));
+    assert_eq!(3, decoder.discarded());
+}
+
+#[test]
+fn decoder_waits_for_the_rest_of_a_frame() {
+    let mut decoder = ModbusFrameDecoder::new();
+
+    for byte in &GET_RPM_RESPONSE[..6] {
+        decoder.push(&[*byte]);
+        assert!(decoder.next_frame().is_none());
+    }
+    decoder.push(&GET_RPM_RESPONSE[6..]);
+
+    assert_eq!(Some(GET_RPM_RESPONSE.to_vec()), decoder.next_frame());
+    assert_eq!(0, decoder.discarded());
+}
+
+#[test]
+fn decoder_skips_a_frame_with_a_bad_checksum() {
+    let mut corrupted = GET_RPM_RESPONSE;
+    corrupted[4] ^= 0x40;
+
+    let mut decoder = ModbusFrameDecoder::new();
+    decoder.push(&corrupted);
+    decoder.push(&GET_RPM_RESPONSE);
+
+    assert_eq!(vec![GET_RPM_RESPONSE.to_vec()], decode_all(&mut decoder));
+}
+
+#[test]
+fn decoder_is_not_held_up_by_junk_that_looks_like_a_long_frame() {
+    let mut decoder = ModbusFrameDecoder::new();
+
+    // Claims 250 bytes of data are coming
+    decoder.push(&[0x01, 0x03, 0xfa]);
+    decoder.push(&GET_RPM_RESPONSE);
+
+    assert_eq!(Some(GET_RPM_RESPONSE.to_vec()), decoder.next_frame());
+}
+
+#[test]
+fn decoder_drops_a_partial_frame_on_silence() {
+    let mut decoder = ModbusFrameDecoder::new();
+
+    decoder.push(&GET_RPM_RESPONSE[..4]);
+    decoder.silence();
+    decoder.push(&GET_RPM_RESPONSE[4..]);
+
+    assert!(decoder.next_frame().is_none());
+    assert_eq!(4, decoder.discarded());
+
+    // The tail could still be the start of something until the line goes quiet again
+    decoder.silence();
+    assert_eq!(7, decoder.discarded());
+}
+
+#[test]
+fn decoder_survives_random_bytes() {
+    let mut noise = Noise(0x2545_f491_4f6c_dd1d);
+
+    for _ in 0..2_000 {
+        let mut decoder = ModbusFrameDecoder::new();
+        let stream = noise.bytes(1_500);
+
+        let mut rest = &stream[..];
+        while !rest.is_empty() {
+            let chunk = (1 + noise.below(64)).min(rest.len());
+            decoder.push(&rest[..chunk]);
+            rest = &rest[chunk..];
+
+            if noise.below(10) == 0 {
+                decoder.silence();
+            }
+
+            for frame in decode_all(&mut decoder) {
+                assert!(frame.len() >= 5);
+                assert!(has_good_crc(&frame));
+            }
+        }
+    }
+}
+
+#[test]
+fn decoder_finds_a_frame_buried_in_random_bytes() {
+    let mut noise = Noise(0x9e37_79b9_7f4a_7c15);
+
+    for _ in 0..2_000 {
+        let mut stream = noise.bytes(40);
+        stream.extend_from_slice(&GET_RPM_RESPONSE);
+        stream.extend(noise.bytes(40));
+
+        let mut decoder = ModbusFrameDecoder::new();
+        let mut frames = Vec::new();
+
+        let mut rest = &stream[..];
+        while !rest.is_empty() {
+            let chunk = (1 + noise.below(16)).min(rest.len());
+            decoder.push(&rest[..chunk]);
+            rest = &rest[chunk..];
+
+            frames.extend(decode_all(&mut decoder));
+        }
+
+        assert!(
+            frames.contains(&GET_RPM_RESPONSE.to_vec()),
+            "Lost the frame in {:02x?}",
+            stream
+        );
+    }
+}
+
+#[test]
+fn controller_reads_through_line_noise() {
+    let (host, mut drive) = LoopbackTransport::pair();
+
+    let responder = thread::spawn(move || {
+        let mut request = [0u8; 8];
+        drive.read_exact(&mut request).unwrap();
+
+        drive.write_all(&[0xff, 0x00, 0x01]).unwrap();
+        drive.write_all(&GET_RPM_RESPONSE).unwrap();
+
+        drive
+    });
+
+    let mut controller = MotorController::from_transport(host, 0x01);
+    assert_eq!(0x10, controller.get_rpm().unwrap());
+    assert_eq!(3, controller.discarded_bytes());
+
+    responder.join().unwrap();
+}
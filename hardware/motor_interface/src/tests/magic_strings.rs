@@ -1,4 +1,7 @@
-use crate::message::{ModbusCommand, ModbusRegister, ModbusRequest};
+use crate::message::{
+    ModbusCommand, ModbusException, ModbusRegister, ModbusRequest, ModbusResponse,
+    ModbusWriteLocationRequest, ModbusWriteMultipleRequest,
+};
 
 const MOTOR_GET_VELOCITY_MAGIC_FRAME: [u8; 6] = [0x01, 0x03, 0x00, 0x10, 0x0, 0x1];
 const MOTOR_SET_VELOCITY_MAGIC_FRAME: [u8; 6] = [0x01, 0x06, 0x00, 0x02, 0x0, 0x0];
@@ -6,6 +9,8 @@ const MOTOR_GET_POSITION_L_MAGIC_FRAME: [u8; 6] = [0x01, 0x03, 0x0, 0x16, 0x0, 0
 const MOTOR_GET_POSITION_H_MAGIC_FRAME: [u8; 6] = [0x01, 0x03, 0x0, 0x17, 0x0, 0x01];
 const MOTOR_SET_POSITION_MAGIC_FRAME: [u8; 11] =
     [0x01, 0x10, 0x0, 0x0c, 0x0, 0x02, 0x04, 0x0, 0x0, 0x0, 0x0];
+// The usual example from the Modbus spec, a read of a register that doesn't exist
+const ILLEGAL_DATA_ADDRESS_RESPONSE_MAGIC_FRAME: [u8; 5] = [0x01, 0x83, 0x02, 0xc0, 0xf1];
 const MOTOR_GET_STATUS_MAGIC_FRAME: [u8; 6] = [0x01, 0x03, 0x0, 0x0E, 0x0, 0x01];
 const MOTOR_SET_POSITION_GAIN_MAGIC_FRAME: [u8; 6] = [0x01, 0x06, 0x00, 0x07, 0x0, 0x0];
 const MOTOR_SET_POSITION_FF_MAGIC_FRAME: [u8; 6] = [0x01, 0x06, 0x00, 0x19, 0x0, 0x0];
@@ -14,6 +19,19 @@ const MOTOR_DISABLE_MAGIC_FRAME: [u8; 8] = [0x01, 0x06, 0x0, 0x1, 0x0, 0x0, 0xd8
 const MOTOR_ENABLE_MAGIC_FRAME: [u8; 8] = [0x01, 0x06, 0x0, 0x1, 0x0, 0x01, 0x19, 0xca];
 const MODBUS_ENABLE_MAGIC_FRAME : [u8; 8] = [0x01, 0x06, 0x0, 0x0, 0x0, 0x01, 0x48, 0x0a];
 
+// Straight out of the manual: move forward one revolution (4000 pulses), and the drive's reply
+const MOTOR_MOVE_ONE_REVOLUTION_MAGIC_FRAME: [u8; 13] =
+    [0x01, 0x10, 0x0, 0x0c, 0x0, 0x02, 0x04, 0x0f, 0xa0, 0x0, 0x0, 0xf0, 0xcc];
+const MOTOR_MOVE_ONE_REVOLUTION_RESPONSE_MAGIC_FRAME: [u8; 8] =
+    [0x01, 0x10, 0x0, 0x0c, 0x0, 0x02, 0x81, 0xcb];
+const MOTOR_MOVE_BACK_ONE_REVOLUTION_MAGIC_FRAME: [u8; 13] =
+    [0x01, 0x10, 0x0, 0x0c, 0x0, 0x02, 0x04, 0xf0, 0x60, 0xff, 0xff, 0xc1, 0x54];
+const MOTOR_WRITE_LOCATION_MAGIC_FRAME: [u8; 8] = [0x01, 0x78, 0x0, 0x0, 0x27, 0x10, 0xbb, 0xfc];
+const MOTOR_WRITE_LOCATION_RESPONSE_MAGIC_FRAME: [u8; 8] =
+    [0x01, 0x78, 0x27, 0x0e, 0x0, 0x0, 0xca, 0xb7];
+const MOTOR_MOVE_TO_TWO_REVOLUTIONS_MAGIC_FRAME: [u8; 13] =
+    [0x01, 0x10, 0x0, 0x16, 0x0, 0x02, 0x04, 0x1f, 0x40, 0x0, 0x0, 0x74, 0x89];
+
 #[test]
 fn check_motor_enable() {
     let sut = ModbusRequest {
@@ -72,4 +90,218 @@ fn check_motor_get_position_high() {
     };
 
     assert_eq!(MOTOR_GET_POSITION_H_MAGIC_FRAME, sut.to_message_bytes()[..6]);
-}
\ No newline at end of file
+}
+#[test]
+fn check_motor_get_velocity() {
+    let sut = ModbusRequest {
+        device_address: 0x1,
+        command: ModbusCommand::ReadRegister,
+        register: ModbusRegister::MotorCurrentSpeed,
+        value: 0x1,
+    };
+
+    assert_eq!(MOTOR_GET_VELOCITY_MAGIC_FRAME, sut.to_message_bytes()[..6]);
+}
+
+#[test]
+fn check_motor_set_velocity() {
+    let sut = ModbusRequest {
+        device_address: 0x1,
+        command: ModbusCommand::WriteRegister,
+        register: ModbusRegister::MotorTargetSpeed,
+        value: 0x0,
+    };
+
+    assert_eq!(MOTOR_SET_VELOCITY_MAGIC_FRAME, sut.to_message_bytes()[..6]);
+}
+
+#[test]
+fn check_motor_get_status() {
+    let sut = ModbusRequest {
+        device_address: 0x1,
+        command: ModbusCommand::ReadRegister,
+        register: ModbusRegister::MotorAlarmCode,
+        value: 0x1,
+    };
+
+    assert_eq!(MOTOR_GET_STATUS_MAGIC_FRAME, sut.to_message_bytes()[..6]);
+}
+
+#[test]
+fn check_motor_set_position_gain() {
+    let sut = ModbusRequest {
+        device_address: 0x1,
+        command: ModbusCommand::WriteRegister,
+        register: ModbusRegister::MotorPositionLoopProportionalCoefficient,
+        value: 0x0,
+    };
+
+    assert_eq!(MOTOR_SET_POSITION_GAIN_MAGIC_FRAME, sut.to_message_bytes()[..6]);
+}
+
+#[test]
+fn check_motor_set_position_ff() {
+    let sut = ModbusRequest {
+        device_address: 0x1,
+        command: ModbusCommand::WriteRegister,
+        register: ModbusRegister::MotorSpecialFunction,
+        value: 0x0,
+    };
+
+    assert_eq!(MOTOR_SET_POSITION_FF_MAGIC_FRAME, sut.to_message_bytes()[..6]);
+}
+
+#[test]
+fn check_motor_set_position() {
+    let sut = ModbusWriteMultipleRequest {
+        device_address: 0x1,
+        register: ModbusRegister::MotorTargetPositionLow,
+        values: vec![0x0, 0x0],
+    };
+
+    assert_eq!(MOTOR_SET_POSITION_MAGIC_FRAME, sut.to_message_bytes()[..11]);
+}
+
+#[test]
+fn check_motor_move_one_revolution() {
+    let sut = ModbusWriteMultipleRequest {
+        device_address: 0x1,
+        register: ModbusRegister::MotorTargetPositionLow,
+        values: vec![0x0fa0, 0x0],
+    };
+
+    assert_eq!(MOTOR_MOVE_ONE_REVOLUTION_MAGIC_FRAME, sut.to_message_bytes()[..]);
+}
+
+#[test]
+fn check_motor_move_one_revolution_response() {
+    let sut = ModbusResponse::from_reader(&mut &MOTOR_MOVE_ONE_REVOLUTION_RESPONSE_MAGIC_FRAME[..])
+        .unwrap();
+
+    assert!(matches!(
+        sut,
+        ModbusResponse::WriteMultipleMessage {
+            device_address: 0x1,
+            command: ModbusCommand::WritePulse,
+            register: ModbusRegister::MotorTargetPositionLow,
+            count: 2,
+        }
+    ));
+}
+
+#[test]
+fn check_write_multiple_response_bad_checksum() {
+    let mut frame = MOTOR_MOVE_ONE_REVOLUTION_RESPONSE_MAGIC_FRAME;
+    frame[7] ^= 0xff;
+
+    assert!(ModbusResponse::from_reader(&mut &frame[..]).is_err());
+}
+
+#[test]
+fn check_motor_move_back_one_revolution() {
+    let sut = ModbusWriteMultipleRequest {
+        device_address: 0x1,
+        register: ModbusRegister::MotorTargetPositionLow,
+        values: vec![(-4000i32) as u16, ((-4000i32) as u32 >> 16) as u16],
+    };
+
+    assert_eq!(MOTOR_MOVE_BACK_ONE_REVOLUTION_MAGIC_FRAME, sut.to_message_bytes()[..]);
+}
+
+#[test]
+fn check_motor_move_to_two_revolutions() {
+    let sut = ModbusWriteMultipleRequest {
+        device_address: 0x1,
+        register: ModbusRegister::MotorAbsolutePositionLow,
+        values: vec![8000, 0],
+    };
+
+    assert_eq!(MOTOR_MOVE_TO_TWO_REVOLUTIONS_MAGIC_FRAME, sut.to_message_bytes()[..]);
+}
+
+#[test]
+fn check_motor_write_location() {
+    let sut = ModbusWriteLocationRequest {
+        device_address: 0x1,
+        position: 10_000,
+    };
+
+    assert_eq!(MOTOR_WRITE_LOCATION_MAGIC_FRAME, sut.to_message_bytes());
+}
+
+#[test]
+fn check_motor_write_location_negative() {
+    let sut = ModbusWriteLocationRequest {
+        device_address: 0x1,
+        position: -4_000,
+    };
+
+    assert_eq!([0x01, 0x78, 0xff, 0xff, 0xf0, 0x60], sut.to_message_bytes()[..6]);
+}
+
+#[test]
+fn check_motor_write_location_response() {
+    let sut = ModbusResponse::from_reader(&mut &MOTOR_WRITE_LOCATION_RESPONSE_MAGIC_FRAME[..])
+        .unwrap();
+
+    assert!(matches!(
+        sut,
+        ModbusResponse::WriteLocationMessage {
+            device_address: 0x1,
+            command: ModbusCommand::WriteLocation,
+            position: 9_998,
+        }
+    ));
+}
+
+#[test]
+fn check_write_location_response_bad_checksum() {
+    let mut frame = MOTOR_WRITE_LOCATION_RESPONSE_MAGIC_FRAME;
+    frame[6] ^= 0xff;
+
+    assert!(ModbusResponse::from_reader(&mut &frame[..]).is_err());
+}
+
+#[test]
+fn check_change_device_address() {
+    let sut = ModbusRequest {
+        device_address: 0x1,
+        command: ModbusCommand::ChangeDeviceAddress,
+        register: ModbusRegister::DeviceAddress,
+        value: 0x2,
+    };
+
+    assert_eq!([0x01, 0x7a, 0x0, 0x15, 0x0, 0x02], sut.to_message_bytes()[..6]);
+}
+
+#[test]
+fn check_exception_response() {
+    let mut reader = &ILLEGAL_DATA_ADDRESS_RESPONSE_MAGIC_FRAME[..];
+    let sut = ModbusResponse::from_reader(&mut reader).unwrap();
+
+    assert!(matches!(
+        sut,
+        ModbusResponse::ExceptionMessage {
+            device_address: 0x1,
+            function: 0x03,
+            exception: ModbusException::IllegalDataAddress,
+        }
+    ));
+    // Nothing left over to trip up the next response
+    assert!(reader.is_empty());
+}
+
+#[test]
+fn check_exception_response_bad_checksum() {
+    let mut frame = ILLEGAL_DATA_ADDRESS_RESPONSE_MAGIC_FRAME;
+    frame[4] ^= 0xff;
+
+    assert!(ModbusResponse::from_reader(&mut &frame[..]).is_err());
+}
+
+#[test]
+fn check_exception_codes_round_trip() {
+    for code in 0..=0xff {
+        assert_eq!(code, u8::from(ModbusException::from(code)));
+    }
+}
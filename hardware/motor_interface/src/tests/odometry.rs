@@ -0,0 +1,143 @@
+use crate::motor_controller::odometry::*;
+use std::f64::consts::PI;
+use std::time::Duration;
+
+const DT: Duration = Duration::from_millis(100);
+
+fn config() -> OdometryConfig {
+    OdometryConfig {
+        wheel_separation: 0.5,
+        metres_per_count: 0.001,
+        slip_variance: 1e-3,
+    }
+}
+
+// Feeds `steps` updates of each wheel moving by so many counts
+fn drive(odometry: &mut Odometry, counts: &mut (i32, i32), step: (i32, i32), steps: usize) {
+    for _ in 0..steps {
+        counts.0 = counts.0.wrapping_add(step.0);
+        counts.1 = counts.1.wrapping_add(step.1);
+        odometry.update(counts.0, counts.1, DT);
+    }
+}
+
+fn close(expected: f64, actual: f64) {
+    assert!(
+        (expected - actual).abs() < 1e-6,
+        "expected {expected}, got {actual}"
+    );
+}
+
+#[test]
+fn first_update_only_primes() {
+    let mut odometry = Odometry::new(config());
+
+    odometry.update(123_456, -654_321, DT);
+
+    assert_eq!(Pose::default(), odometry.pose());
+    assert_eq!(Twist::default(), odometry.twist());
+}
+
+#[test]
+fn straight_line() {
+    let mut odometry = Odometry::new(config());
+    let mut counts = (0, 0);
+    odometry.update(0, 0, DT);
+
+    drive(&mut odometry, &mut counts, (50, 50), 20);
+
+    close(1.0, odometry.pose().x);
+    close(0.0, odometry.pose().y);
+    close(0.0, odometry.pose().theta);
+    close(0.5, odometry.twist().linear);
+    close(0.0, odometry.twist().angular);
+}
+
+#[test]
+fn arc_lands_on_the_circle() {
+    let mut odometry = Odometry::new(config());
+    let mut counts = (0, 0);
+    odometry.update(0, 0, DT);
+
+    // A quarter turn on a 1m radius: wheels on 0.75m and 1.25m radii. One big
+    // step has to come out as exact as lots of small ones.
+    let left = (0.75 * PI / 2.0 / 0.001) as i32;
+    let right = (1.25 * PI / 2.0 / 0.001) as i32;
+    drive(&mut odometry, &mut counts, (left, right), 1);
+
+    let pose = odometry.pose();
+    assert!((pose.x - 1.0).abs() < 1e-3);
+    assert!((pose.y - 1.0).abs() < 1e-3);
+    assert!((pose.theta - PI / 2.0).abs() < 1e-3);
+}
+
+#[test]
+fn spinning_on_the_spot_wraps_theta() {
+    let mut odometry = Odometry::new(config());
+    let mut counts = (0, 0);
+    odometry.update(0, 0, DT);
+
+    // Each step turns 0.1 rad
+    drive(&mut odometry, &mut counts, (-25, 25), 40);
+
+    close(0.0, odometry.pose().x);
+    close(0.0, odometry.pose().y);
+    close(4.0 - 2.0 * PI, odometry.pose().theta);
+    close(1.0, odometry.twist().angular);
+}
+
+#[test]
+fn encoder_rollover_is_not_a_jump() {
+    let mut odometry = Odometry::new(config());
+    let mut counts = (i32::MAX - 100, i32::MAX - 100);
+    odometry.update(counts.0, counts.1, DT);
+
+    drive(&mut odometry, &mut counts, (50, 50), 4);
+
+    assert!(counts.0 < 0);
+    close(0.2, odometry.pose().x);
+}
+
+#[test]
+fn covariance_grows_with_distance_and_resets() {
+    let mut odometry = Odometry::new(config());
+    let mut counts = (0, 0);
+    odometry.update(0, 0, DT);
+
+    drive(&mut odometry, &mut counts, (50, 50), 10);
+    let short = odometry.pose_covariance();
+    drive(&mut odometry, &mut counts, (50, 50), 10);
+    let long = odometry.pose_covariance();
+
+    assert!(short[0][0] > 0.0);
+    assert!(long[0][0] > short[0][0]);
+    // Driving straight, uncertain heading turns into uncertain sideways position
+    assert!(long[1][1] > short[1][1]);
+    close(long[0][1], long[1][0]);
+    close(long[0][2], long[2][0]);
+    close(long[1][2], long[2][1]);
+
+    odometry.reset(Pose {
+        x: 1.0,
+        y: 2.0,
+        theta: 0.5,
+    });
+    assert_eq!([[0.0; 3]; 3], odometry.pose_covariance());
+    odometry.update(999, 999, DT);
+    close(1.0, odometry.pose().x);
+}
+
+#[test]
+fn no_slip_means_no_uncertainty() {
+    let mut odometry = Odometry::new(OdometryConfig {
+        slip_variance: 0.0,
+        ..config()
+    });
+    let mut counts = (0, 0);
+    odometry.update(0, 0, DT);
+
+    drive(&mut odometry, &mut counts, (30, 70), 10);
+
+    assert_eq!([[0.0; 3]; 3], odometry.pose_covariance());
+    assert_eq!([[0.0; 2]; 2], odometry.twist_covariance());
+}
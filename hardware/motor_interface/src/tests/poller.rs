@@ -0,0 +1,135 @@
+use crate::ffi::*;
+use crate::message::ModbusRegister;
+use crate::motor_controller::bus::MotorBus;
+use crate::motor_controller::motor_status::{MotorStatus, MotorStatusFatal};
+use crate::motor_controller::poller::*;
+use crate::motor_controller::retry::RetryPolicy;
+use crate::simulator::{SimulatedBus, SimulatedDrive};
+use std::thread;
+use std::time::{Duration, Instant};
+
+const FAST: [PollRate; 3] = [
+    PollRate {
+        item: PollItem::Speed,
+        period_ms: 5,
+    },
+    PollRate {
+        item: PollItem::Position,
+        period_ms: 5,
+    },
+    PollRate {
+        item: PollItem::Alarm,
+        period_ms: 5,
+    },
+];
+
+fn simulated_bus() -> (MotorBus, SimulatedDrive, SimulatedDrive) {
+    let left = SimulatedDrive::new(0x01);
+    let right = SimulatedDrive::new(0x02);
+    let bus = MotorBus::from_transport(SimulatedBus::new([left.clone(), right.clone()]));
+
+    (bus, left, right)
+}
+
+// Waits for the poller to catch up with something
+fn eventually(poller: &Poller, address: u8, check: impl Fn(&DriveSnapshot) -> bool) {
+    let deadline = Instant::now() + Duration::from_secs(2);
+    while !check(&poller.drive(address).unwrap()) {
+        assert!(Instant::now() < deadline, "{:?}", poller.drive(address));
+        thread::sleep(Duration::from_millis(1));
+    }
+}
+
+#[test]
+fn snapshot_follows_the_drives() {
+    let (bus, left, right) = simulated_bus();
+    left.set_position(-70_000);
+    right.raise_alarm(MotorStatus::Fatal(MotorStatusFatal::SystemStall));
+
+    let poller = Poller::start(&bus, &[0x01, 0x02], &FAST).unwrap();
+    eventually(&poller, 0x01, |drive| drive.position.is_some());
+    eventually(&poller, 0x02, |drive| drive.status.is_some());
+
+    let snapshot = poller.snapshot();
+    let left_snapshot = snapshot.drive(0x01).unwrap();
+    assert_eq!(-70_000, left_snapshot.position.unwrap().value);
+    assert_eq!(0, left_snapshot.speed.unwrap().value);
+    // Never asked for
+    assert_eq!(None, left_snapshot.voltage);
+    assert_eq!(
+        MotorStatus::Fatal(MotorStatusFatal::SystemStall),
+        snapshot.drive(0x02).unwrap().status.unwrap().value
+    );
+
+    left.set_position(12_345);
+    eventually(&poller, 0x01, |drive| {
+        drive.position.map(|reading| reading.value) == Some(12_345)
+    });
+    assert!(poller.drive(0x01).unwrap().position.unwrap().age() < Duration::from_secs(1));
+}
+
+#[test]
+fn writes_go_out_in_the_background() {
+    let (bus, left, _right) = simulated_bus();
+    bus.controller(0x01).unwrap().enable_modbus().unwrap();
+    let poller = Poller::start(&bus, &[0x01, 0x02], &FAST).unwrap();
+
+    poller.set_rpm(0x01, -250).unwrap();
+    let deadline = Instant::now() + Duration::from_secs(2);
+    while left.register(ModbusRegister::MotorTargetSpeed) as i16 != -250 {
+        assert!(Instant::now() < deadline);
+        thread::sleep(Duration::from_millis(1));
+    }
+    assert_eq!(0, poller.drive(0x01).unwrap().failed_writes);
+
+    // Not one of the poller's
+    assert!(poller.set_rpm(0x03, 100).is_err());
+}
+
+#[test]
+fn missing_drives_count_as_failed_polls() {
+    let (mut bus, _left, _right) = simulated_bus();
+    bus.set_retry_policy(RetryPolicy::never());
+    bus.set_timeout(Duration::from_millis(5)).unwrap();
+
+    let poller = Poller::start(&bus, &[0x01, 0x09], &FAST).unwrap();
+
+    eventually(&poller, 0x09, |drive| drive.failed_polls >= 3);
+    eventually(&poller, 0x01, |drive| drive.speed.is_some());
+    assert_eq!(None, poller.drive(0x09).unwrap().speed);
+
+    poller.set_rpm(0x09, 100).unwrap();
+    eventually(&poller, 0x09, |drive| drive.failed_writes == 1);
+}
+
+#[test]
+fn ffi_flattens_the_snapshot() {
+    let (bus, left, _right) = simulated_bus();
+    left.set_position(4_000);
+    let poller = Poller::start(&bus, &[0x01], &FAST).unwrap();
+    eventually(&poller, 0x01, |drive| drive.position.is_some());
+    let ptr = Box::into_raw(Box::new(poller));
+
+    unsafe {
+        let mut drive = std::mem::zeroed();
+        assert_eq!(
+            MotorControllerStatus::Ok,
+            motor_poller_read(ptr, 0x01, &mut drive)
+        );
+        assert_eq!(4_000, drive.position);
+        assert!(drive.position_age >= 0.0);
+        assert_eq!(MotorAlarm::None, drive.alarm);
+        assert!(drive.voltage_age < 0.0);
+
+        assert_eq!(
+            MotorControllerStatus::InvalidArgument,
+            motor_poller_read(ptr, 0x02, &mut drive)
+        );
+        assert_eq!(
+            MotorControllerStatus::InvalidArgument,
+            motor_poller_write_register(ptr, 0x01, 0x0040, 1)
+        );
+
+        motor_poller_free(ptr);
+    }
+}
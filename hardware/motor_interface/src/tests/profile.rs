@@ -0,0 +1,192 @@
+use super::simulated_controller;
+use crate::ffi::*;
+use crate::message::ModbusRegister;
+use crate::motor_controller::error::MotorControllerError;
+use crate::motor_controller::profile::*;
+use crate::motor_controller::MotorController;
+use crate::simulator::SimulatedDrive;
+use std::ffi::CString;
+
+fn tuned() -> DriveProfile {
+    DriveProfile::from_toml(
+        "MotorAcceleration = 8000\n\
+         MotorSpeedLoopProportionalCoefficient = 1500\n\
+         MotorSpeedLoopIntegrationTime = 40\n\
+         MotorSpeedFilterFrequency = 250\n",
+    )
+    .unwrap()
+}
+
+#[test]
+fn dump_has_every_saved_register_in_order() {
+    let (mut controller, drive) = simulated_controller(true);
+    drive.set_register(
+        ModbusRegister::MotorPositionLoopProportionalCoefficient,
+        900,
+    );
+
+    let profile = controller.read_profile().unwrap();
+
+    let registers: Vec<_> = profile.iter().map(|(register, _)| register).collect();
+    let persisted: Vec<_> = ModbusRegister::ALL
+        .into_iter()
+        .filter(|register| register.info().persisted)
+        .collect();
+    assert_eq!(persisted, registers);
+    assert_eq!(
+        Some(900),
+        profile.get(ModbusRegister::MotorPositionLoopProportionalCoefficient)
+    );
+    assert_eq!(None, profile.get(ModbusRegister::MotorTargetSpeed));
+
+    let toml = profile.to_toml().unwrap();
+    assert!(toml.starts_with("MotorAcceleration = 20000\n"));
+    assert_eq!(profile, DriveProfile::from_toml(&toml).unwrap());
+    assert_eq!(
+        profile,
+        DriveProfile::from_json(&profile.to_json().unwrap()).unwrap()
+    );
+}
+
+#[test]
+fn bad_profiles_are_turned_away() {
+    assert!(matches!(
+        DriveProfile::from_toml("MotorWarpFactor = 9\n"),
+        Err(ProfileError::UnknownRegister(name)) if name == "MotorWarpFactor"
+    ));
+    assert!(matches!(
+        DriveProfile::from_json(r#"{ "EnableMotor": 1 }"#),
+        Err(ProfileError::NotPersisted(ModbusRegister::EnableMotor))
+    ));
+    assert!(matches!(
+        DriveProfile::from_toml("MotorSpeedLoopIntegrationTime = 1\n"),
+        Err(ProfileError::InvalidValue(_))
+    ));
+}
+
+#[test]
+fn apply_then_save_survives_a_power_cycle() {
+    let (mut controller, drive) = simulated_controller(true);
+    let profile = tuned();
+
+    let differences = controller.diff_profile(&profile).unwrap();
+    assert_eq!(4, differences.len());
+    assert_eq!(
+        ProfileDifference {
+            register: ModbusRegister::MotorAcceleration,
+            profile: 8_000,
+            drive: 20_000,
+        },
+        differences[0]
+    );
+
+    controller.apply_profile(&profile).unwrap();
+    assert!(controller.diff_profile(&profile).unwrap().is_empty());
+    // Left alone, as the profile doesn't mention it
+    assert_eq!(
+        500,
+        drive.register(ModbusRegister::MotorPositionLoopProportionalCoefficient)
+    );
+
+    // Not saved yet, so a power cycle loses it
+    drive.power_cycle();
+    assert_eq!(4, controller.diff_profile(&profile).unwrap().len());
+
+    controller.enable_modbus().unwrap();
+    controller.apply_profile(&profile).unwrap();
+    controller.save_parameters().unwrap();
+    drive.power_cycle();
+    assert!(controller.diff_profile(&profile).unwrap().is_empty());
+}
+
+#[test]
+fn apply_notices_when_the_drive_ignores_it() {
+    let drive = SimulatedDrive::new(0x01);
+    // Modbus never enabled, so the drive acknowledges the writes and drops them
+    let mut controller = MotorController::from_transport(drive.clone(), 0x01);
+
+    assert!(matches!(
+        controller.apply_profile(&tuned()),
+        Err(MotorControllerError::ProfileMismatch(
+            ModbusRegister::MotorAcceleration,
+            8_000,
+            20_000
+        ))
+    ));
+}
+
+#[test]
+fn failed_flash_writes_are_reported() {
+    let (mut controller, drive) = simulated_controller(true);
+    drive.set_flash_write_fails(true);
+
+    assert!(matches!(
+        controller.save_parameters(),
+        Err(MotorControllerError::FlashWriteFailed)
+    ));
+}
+
+#[test]
+fn slow_flash_writes_are_waited_for() {
+    let (mut controller, drive) = simulated_controller(true);
+    drive.set_flash_write_frames(5);
+
+    controller.save_parameters().unwrap();
+    assert_eq!(2, drive.register(ModbusRegister::ParameterSavingFlag));
+
+    // The failure only turns up once the drive is done
+    drive.set_flash_write_fails(true);
+    assert!(matches!(
+        controller.save_parameters(),
+        Err(MotorControllerError::FlashWriteFailed)
+    ));
+}
+
+#[test]
+fn flash_writes_that_never_finish_time_out() {
+    let (mut controller, drive) = simulated_controller(true);
+    drive.set_flash_write_frames(u32::MAX);
+
+    assert!(matches!(
+        controller.save_parameters(),
+        Err(MotorControllerError::FlashWriteTimedOut(_))
+    ));
+}
+
+#[test]
+fn ffi_saves_and_loads_profile_files() {
+    let (controller, drive) = simulated_controller(true);
+    drive.set_register(ModbusRegister::MotorSpeedFilterFrequency, 300);
+    let ptr = Box::into_raw(Box::new(controller));
+    let path =
+        std::env::temp_dir().join(format!("happy-drive-profile-{}.json", std::process::id()));
+    let c_path = CString::new(path.to_str().unwrap()).unwrap();
+
+    unsafe {
+        assert_eq!(
+            MotorControllerStatus::Ok,
+            motor_controller_save_profile(ptr, c_path.as_ptr())
+        );
+        assert_eq!(
+            Some(300),
+            DriveProfile::load(&path)
+                .unwrap()
+                .get(ModbusRegister::MotorSpeedFilterFrequency)
+        );
+
+        drive.set_register(ModbusRegister::MotorSpeedFilterFrequency, 100);
+        assert_eq!(
+            MotorControllerStatus::Ok,
+            motor_controller_load_profile(ptr, c_path.as_ptr(), true)
+        );
+        assert_eq!(
+            300,
+            drive.register(ModbusRegister::MotorSpeedFilterFrequency)
+        );
+        assert_eq!(2, drive.register(ModbusRegister::ParameterSavingFlag));
+
+        motor_controller_free(ptr);
+    }
+
+    std::fs::remove_file(path).unwrap();
+}
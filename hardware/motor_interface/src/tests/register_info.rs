@@ -0,0 +1,134 @@
+use super::simulated_controller;
+use crate::ffi::*;
+use crate::message::*;
+use crate::motor_controller::error::MotorControllerError;
+use crate::simulator::SimulatedDrive;
+
+#[test]
+fn every_register_has_its_own_info() {
+    for (address, register) in ModbusRegister::ALL.into_iter().enumerate() {
+        assert_eq!(register as usize, address);
+        assert_eq!(register, register.info().register);
+        assert!(register.info().min <= register.info().max);
+    }
+}
+
+#[test]
+fn defaults_match_a_fresh_drive() {
+    let drive = SimulatedDrive::new(0x01);
+
+    for register in ModbusRegister::ALL {
+        if let Some(default) = register.info().default {
+            assert_eq!(default, drive.register(register), "{register:?}");
+            assert!(register.check_write(default).is_ok(), "{register:?}");
+        }
+    }
+}
+
+#[test]
+fn bad_writes_never_reach_the_wire() {
+    let (mut controller, drive) = simulated_controller(true);
+    let frames = drive.frames_received();
+
+    assert!(matches!(
+        controller.write_register(ModbusRegister::MotorCurrentSpeed, 100),
+        Err(MotorControllerError::InvalidWrite(
+            RegisterWriteError::ReadOnly(ModbusRegister::MotorCurrentSpeed)
+        ))
+    ));
+    assert!(matches!(
+        controller.set_rpm(-30_001),
+        Err(MotorControllerError::InvalidWrite(
+            RegisterWriteError::OutOfRange(
+                ModbusRegister::MotorTargetSpeed,
+                -30_001,
+                -30_000,
+                30_000
+            )
+        ))
+    ));
+    assert!(matches!(
+        controller.set_position_gain(10),
+        Err(MotorControllerError::InvalidWrite(
+            RegisterWriteError::OutOfRange(..)
+        ))
+    ));
+    // Half a relative move
+    assert!(matches!(
+        controller.write_register(ModbusRegister::MotorTargetPositionLow, 1),
+        Err(MotorControllerError::InvalidWrite(
+            RegisterWriteError::BlockWriteOnly(_)
+        ))
+    ));
+    // Half an absolute move, which with the wrong gear could clear the count
+    assert!(matches!(
+        controller.write_register(ModbusRegister::MotorAbsolutePositionLow, 0),
+        Err(MotorControllerError::InvalidWrite(
+            RegisterWriteError::BlockWriteOnly(ModbusRegister::MotorAbsolutePositionLow)
+        ))
+    ));
+    assert!(matches!(
+        controller.set_electronic_gear(3, 0),
+        Err(MotorControllerError::InvalidWrite(
+            RegisterWriteError::OutOfRange(ModbusRegister::MotorElectronicGearLow, ..)
+        ))
+    ));
+    assert_eq!(frames, drive.frames_received());
+
+    // The edges themselves are fine
+    assert_eq!(-30_000, controller.set_rpm(-30_000).unwrap());
+    assert_eq!(
+        -30_000,
+        drive.register(ModbusRegister::MotorTargetSpeed) as i16
+    );
+}
+
+#[test]
+fn ffi_refuses_bad_writes() {
+    let (controller, drive) = simulated_controller(true);
+    let ptr = Box::into_raw(Box::new(controller));
+
+    unsafe {
+        assert_eq!(
+            MotorControllerStatus::InvalidArgument,
+            motor_controller_write_register(ptr, ModbusRegister::MotorV as u16, 1)
+        );
+        assert_eq!(
+            MotorControllerStatus::InvalidArgument,
+            motor_controller_write_register(ptr, ModbusRegister::MotorSpecialFunction as u16, 4)
+        );
+        assert_eq!(0, drive.register(ModbusRegister::MotorSpecialFunction));
+
+        motor_controller_free(ptr);
+    }
+}
+
+#[test]
+fn values_print_in_real_units() {
+    assert_eq!(
+        "MotorTargetSpeed = 1500 (150.0 rpm)",
+        ModbusRegister::MotorTargetSpeed.value(1_500).to_string()
+    );
+    assert_eq!(
+        "MotorCurrentSpeed = -600 (-60.0 rpm)",
+        ModbusRegister::MotorCurrentSpeed
+            .value(-600i16 as u16)
+            .to_string()
+    );
+    assert_eq!(
+        "MotorSpeedLoopProportionalCoefficient = 1000 (1.000)",
+        ModbusRegister::MotorSpeedLoopProportionalCoefficient
+            .value(1_000)
+            .to_string()
+    );
+    assert_eq!(
+        "EnableMotor = 1",
+        ModbusRegister::EnableMotor.value(1).to_string()
+    );
+
+    let bank = RegisterBank::new(ModbusRegister::MotorI, vec![4_000, 0, 7_848]);
+    assert_eq!(
+        "MotorI = 4000 (2.0 A)\nMotorCurrentSpeed = 0 (0.0 rpm)\nMotorV = 7848 (24.0 V)\n",
+        bank.to_string()
+    );
+}
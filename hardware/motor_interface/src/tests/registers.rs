@@ -0,0 +1,169 @@
+use super::simulated_controller;
+use crate::crc::crc16;
+use crate::ffi::*;
+use crate::message::{ModbusRegister, RegisterBank};
+use crate::motor_controller::error::MotorControllerError;
+use crate::motor_controller::retry::RetryPolicy;
+use crate::motor_controller::MotorController;
+use crate::simulator::SimulatedDrive;
+use crate::transport::LoopbackTransport;
+use std::io::{Read, Write};
+use std::thread;
+
+#[test]
+fn block_read_is_one_transaction() {
+    let drive = SimulatedDrive::new(0x01);
+    drive.set_register(ModbusRegister::MotorAcceleration, 500);
+    drive.set_register(ModbusRegister::MotorInitialSpeed, 20);
+    let mut controller = MotorController::from_transport(drive.clone(), 0x01);
+    let frames = drive.frames_received();
+
+    let bank = controller
+        .read_registers(ModbusRegister::MotorTargetSpeed, 3)
+        .unwrap();
+
+    assert_eq!(frames + 1, drive.frames_received());
+    assert_eq!(ModbusRegister::MotorTargetSpeed, bank.start());
+    assert_eq!(3, bank.len());
+    assert_eq!(Some(500), bank.get(ModbusRegister::MotorAcceleration));
+    assert_eq!(Some(20), bank.get(ModbusRegister::MotorInitialSpeed));
+    assert_eq!(None, bank.get(ModbusRegister::EnableMotor));
+    assert_eq!(
+        None,
+        bank.get(ModbusRegister::MotorSpeedLoopIntegrationTime)
+    );
+}
+
+#[test]
+fn ranges_outside_the_drive_are_not_sent() {
+    let (mut controller, drive) = simulated_controller(false);
+    let frames = drive.frames_received();
+
+    assert!(matches!(
+        controller.read_registers(ModbusRegister::MotorI, 0),
+        Err(MotorControllerError::InvalidRegisterRange(
+            ModbusRegister::MotorI,
+            0
+        ))
+    ));
+    assert!(matches!(
+        controller.read_registers(ModbusRegister::MotorAbsolutePositionHigh, 4),
+        Err(MotorControllerError::InvalidRegisterRange(..))
+    ));
+    controller
+        .read_registers(ModbusRegister::MotorAbsolutePositionHigh, 3)
+        .unwrap();
+
+    assert_eq!(frames + 1, drive.frames_received());
+}
+
+#[test]
+fn empty_block_writes_are_not_sent() {
+    let (mut controller, drive) = simulated_controller(false);
+    let frames = drive.frames_received();
+
+    assert!(matches!(
+        controller.write_registers(ModbusRegister::MotorAcceleration, &[]),
+        Err(MotorControllerError::InvalidRegisterRange(
+            ModbusRegister::MotorAcceleration,
+            0
+        ))
+    ));
+
+    assert_eq!(frames, drive.frames_received());
+}
+
+#[test]
+fn position_is_read_in_one_go() {
+    let (mut controller, drive) = simulated_controller(false);
+
+    // Right across a low word rollover, which two separate reads could tear
+    for position in [-70_000, 0xffff, 0x1_0000, i32::MIN] {
+        drive.set_position(position);
+        let frames = drive.frames_received();
+
+        assert_eq!(position, controller.get_position().unwrap());
+        assert_eq!(frames + 1, drive.frames_received());
+    }
+}
+
+#[test]
+fn short_replies_are_caught() {
+    let (host, mut drive) = LoopbackTransport::pair();
+
+    let responder = thread::spawn(move || {
+        let mut request = [0u8; 8];
+        drive.read_exact(&mut request).unwrap();
+
+        // Asked for 2 registers, answers with 1
+        let mut response = vec![0x01, 0x03, 0x02, 0x00, 0x10];
+        let crc = crc16(&response);
+        response.extend([(crc >> 8) as u8, crc as u8]);
+        drive.write_all(&response).unwrap();
+
+        drive
+    });
+
+    let mut controller = MotorController::from_transport(host, 0x01);
+    controller.set_retry_policy(RetryPolicy::never());
+
+    assert!(matches!(
+        controller.get_position(),
+        Err(MotorControllerError::IncorrectDataLength(4, 2))
+    ));
+
+    responder.join().unwrap();
+}
+
+#[test]
+fn bank_decodes_words() {
+    let bank = RegisterBank::new(
+        ModbusRegister::MotorAbsolutePositionLow,
+        vec![0x2345, 0xfff1, 100],
+    );
+
+    assert_eq!(
+        Some(0xfff1_2345u32 as i32),
+        bank.get_i32(ModbusRegister::MotorAbsolutePositionLow)
+    );
+    assert_eq!(
+        Some(-15),
+        bank.get_i16(ModbusRegister::MotorAbsolutePositionHigh)
+    );
+    // The high half would be off the end
+    assert_eq!(
+        None,
+        bank.get_i32(ModbusRegister::MotorSpeedFilterFrequency)
+    );
+    assert_eq!(
+        vec![
+            (ModbusRegister::MotorAbsolutePositionLow, 0x2345),
+            (ModbusRegister::MotorAbsolutePositionHigh, 0xfff1),
+            (ModbusRegister::MotorSpeedFilterFrequency, 100),
+        ],
+        bank.iter().collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn ffi_reads_a_block() {
+    let drive = SimulatedDrive::new(0x01);
+    drive.set_position(-2);
+    let ptr = Box::into_raw(Box::new(MotorController::from_transport(drive, 0x01)));
+
+    unsafe {
+        let mut values = [0u16; 2];
+        assert_eq!(
+            MotorControllerStatus::Ok,
+            motor_controller_read_registers(ptr, 0x16, 2, values.as_mut_ptr())
+        );
+        assert_eq!([0xfffe, 0xffff], values);
+
+        assert_eq!(
+            MotorControllerStatus::InvalidArgument,
+            motor_controller_read_registers(ptr, 0x19, 2, values.as_mut_ptr())
+        );
+
+        motor_controller_free(ptr);
+    }
+}
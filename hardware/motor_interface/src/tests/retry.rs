@@ -0,0 +1,111 @@
+use super::simulated_controller;
+use crate::message::ModbusRegister;
+use crate::motor_controller::error::MotorControllerError;
+use crate::motor_controller::retry::{RetryPolicy, RetryStats};
+use crate::motor_controller::MotorController;
+use crate::simulator::SimulatedDrive;
+
+fn enabled_controller() -> (MotorController, SimulatedDrive) {
+    let (mut controller, drive) = simulated_controller(true);
+    controller.set_motor_enabled().unwrap();
+    controller.reset_retry_stats();
+
+    (controller, drive)
+}
+
+#[test]
+fn read_recovers_from_a_lost_frame() {
+    let (mut controller, drive) = enabled_controller();
+
+    drive.ignore_next_frames(1);
+
+    assert_eq!(0, controller.get_rpm().unwrap());
+    assert_eq!(1, controller.retry_stats().last_request_retries);
+}
+
+#[test]
+fn read_recovers_from_a_corrupt_reply() {
+    let (mut controller, drive) = enabled_controller();
+
+    drive.corrupt_next_replies(2);
+
+    assert_eq!(0, controller.get_rpm().unwrap());
+    assert_eq!(
+        RetryStats {
+            requests: 1,
+            retries: 2,
+            failures: 0,
+            last_request_retries: 2,
+            most_retries: 2,
+        },
+        controller.retry_stats()
+    );
+}
+
+#[test]
+fn retries_give_up_after_max_attempts() {
+    let (mut controller, drive) = enabled_controller();
+
+    drive.ignore_next_frames(10);
+    let before = drive.frames_received();
+
+    assert!(controller.get_rpm().is_err());
+    assert_eq!(3, drive.frames_received() - before);
+    assert_eq!(1, controller.retry_stats().failures);
+}
+
+#[test]
+fn register_writes_are_retried() {
+    let (mut controller, drive) = enabled_controller();
+
+    drive.ignore_next_frames(1);
+    controller.set_rpm(120).unwrap();
+
+    assert_eq!(120, drive.register(ModbusRegister::MotorTargetSpeed));
+}
+
+#[test]
+fn register_writes_are_not_retried_if_told_not_to() {
+    let (mut controller, drive) = enabled_controller();
+
+    controller.set_retry_policy(RetryPolicy {
+        retry_writes: false,
+        ..RetryPolicy::default()
+    });
+    drive.ignore_next_frames(1);
+
+    assert!(controller.set_rpm(120).is_err());
+}
+
+#[test]
+fn relative_moves_are_never_retried() {
+    let (mut controller, drive) = enabled_controller();
+    drive.set_position_mode(true);
+
+    drive.ignore_next_frames(1);
+    let before = drive.frames_received();
+
+    assert!(controller.move_by_counts(4_000).is_err());
+    assert_eq!(1, drive.frames_received() - before);
+
+    drive.corrupt_next_replies(1);
+    let before = drive.frames_received();
+
+    // The drive got it and is moving, so sending it again would go twice as far
+    assert!(controller.write_location(4_000).is_err());
+    assert_eq!(1, drive.frames_received() - before);
+}
+
+#[test]
+fn never_policy_fails_straight_away() {
+    let (mut controller, drive) = enabled_controller();
+
+    controller.set_retry_policy(RetryPolicy::never());
+    drive.ignore_next_frames(1);
+
+    assert!(matches!(
+        controller.get_rpm(),
+        Err(MotorControllerError::ResponseError(_))
+    ));
+    assert_eq!(0, controller.retry_stats().retries);
+}
@@ -0,0 +1,117 @@
+use crate::motor_controller::shaper::*;
+use std::time::Duration;
+
+const DT: Duration = Duration::from_millis(10);
+
+fn close(expected: f32, actual: f32) {
+    assert!(
+        (expected - actual).abs() < 1e-4,
+        "expected {expected}, got {actual}"
+    );
+}
+
+// Ticks until the speed gets to `goal`, giving every speed on the way
+fn run(shaper: &mut SpeedShaper, goal: f32) -> Vec<f32> {
+    let mut speeds = Vec::new();
+    while speeds.len() < 1_000 {
+        speeds.push(shaper.tick(DT));
+        if shaper.velocity() == goal {
+            break;
+        }
+    }
+
+    speeds
+}
+
+#[test]
+fn no_limits_goes_straight_there() {
+    let mut shaper = SpeedShaper::new(MotionLimits::default());
+    shaper.set_target(1.5);
+
+    assert_eq!(1.5, shaper.tick(DT));
+    shaper.set_target(f32::NAN);
+    assert_eq!(0.0, shaper.tick(DT));
+}
+
+#[test]
+fn acceleration_and_braking_have_their_own_limits() {
+    let mut shaper = SpeedShaper::new(MotionLimits {
+        acceleration: 1.0,
+        deceleration: 2.0,
+        ..MotionLimits::default()
+    });
+
+    shaper.set_target(0.5);
+    let speeds = run(&mut shaper, 0.5);
+    assert!((50..=51).contains(&speeds.len()));
+    close(0.01, speeds[0]);
+
+    shaper.set_target(-0.5);
+    let speeds = run(&mut shaper, -0.5);
+    // Braking down to 0 takes 25 ticks, then speeding up the other way 50
+    assert!((74..=76).contains(&speeds.len()));
+    close(0.48, speeds[0]);
+}
+
+#[test]
+fn jerk_limits_how_fast_acceleration_changes() {
+    let limits = MotionLimits {
+        acceleration: 2.0,
+        deceleration: 2.0,
+        jerk: 10.0,
+        ..MotionLimits::default()
+    };
+    let mut shaper = SpeedShaper::new(limits);
+    shaper.set_target(1.0);
+
+    let mut last_acceleration = 0.0;
+    for _ in 0..1_000 {
+        let speed = shaper.tick(DT);
+        assert!(speed <= 1.0);
+        assert!(shaper.acceleration() <= limits.acceleration);
+        // Only the final snap onto the target is allowed to jump
+        if speed < 1.0 {
+            let change = (shaper.acceleration() - last_acceleration).abs();
+            assert!(change <= limits.jerk * DT.as_secs_f32() + 1e-5);
+        }
+        last_acceleration = shaper.acceleration();
+    }
+    assert_eq!(1.0, shaper.velocity());
+}
+
+#[test]
+fn emergency_stop_overrides_the_target_until_resumed() {
+    let mut shaper = SpeedShaper::new(MotionLimits {
+        acceleration: 1.0,
+        deceleration: 1.0,
+        jerk: 5.0,
+        emergency_deceleration: 10.0,
+    });
+    shaper.reset(1.0);
+
+    shaper.emergency_stop();
+    shaper.set_target(2.0);
+    let speeds = run(&mut shaper, 0.0);
+    assert!((10..=11).contains(&speeds.len()));
+    close(0.9, speeds[0]);
+    assert_eq!(0.0, shaper.tick(DT));
+
+    shaper.resume();
+    assert!(shaper.tick(DT) > 0.0);
+    assert_eq!(2.0, shaper.target());
+}
+
+#[test]
+fn limits_have_to_be_positive_numbers() {
+    assert!(MotionLimits::default().is_valid());
+    assert!(!MotionLimits {
+        jerk: -1.0,
+        ..MotionLimits::default()
+    }
+    .is_valid());
+    assert!(!MotionLimits {
+        acceleration: f32::INFINITY,
+        ..MotionLimits::default()
+    }
+    .is_valid());
+}
@@ -0,0 +1,386 @@
+use super::simulated_controller;
+use crate::crc::crc16;
+use crate::message::{
+    ModbusCommand, ModbusException, ModbusRegister, ModbusRequest, ModbusResponse,
+};
+use crate::motor_controller::constants::MOTOR_WHEEL_LENGTH;
+use crate::motor_controller::error::MotorControllerError;
+use crate::motor_controller::motor_status::{MotorStatus, MotorStatusFatal, MotorStatusWarning};
+use crate::motor_controller::MotorController;
+use crate::simulator::SimulatedDrive;
+use std::io::Write;
+use std::time::Duration;
+
+#[test]
+fn this_tests_simulated_motors() {
+    // The same dance as this_tests_motors, but against the simulator
+    const MOTOR_SPEED: f32 = -0.5;
+
+    let (mut controller, drive) = simulated_controller(true);
+
+    assert_eq!(MotorStatus::None, controller.get_status().unwrap());
+
+    controller
+        .set_motor_enabled()
+        .unwrap_or_else(|e| panic!("Failed to enable motor! {}", e));
+    assert_eq!(0.0, controller.get_velocity().unwrap());
+
+    let new_velo = controller
+        .set_velocity(MOTOR_SPEED)
+        .unwrap_or_else(|e| panic!("Failed to set velocity! {}", e));
+    assert!((new_velo - MOTOR_SPEED).abs() < 0.001);
+
+    drive.step(Duration::from_secs(1));
+
+    let running_speed = controller.get_velocity().unwrap();
+    assert!((running_speed - MOTOR_SPEED).abs() < 0.001);
+    assert!(controller.get_position().unwrap() < 0);
+
+    controller.set_velocity(0.0).unwrap();
+    drive.step(Duration::from_secs(1));
+
+    assert_eq!(0.0, controller.get_velocity().unwrap());
+    assert_eq!(MotorStatus::None, controller.get_status().unwrap());
+}
+
+#[test]
+fn simulated_drive_ignores_writes_until_modbus_enabled() {
+    let (mut controller, drive) = simulated_controller(false);
+
+    controller.set_motor_enabled().unwrap();
+    assert_eq!(0, drive.register(ModbusRegister::EnableMotor));
+
+    controller.enable_modbus().unwrap();
+    controller.set_motor_enabled().unwrap();
+    assert_eq!(1, drive.register(ModbusRegister::EnableMotor));
+}
+
+#[test]
+fn simulated_drive_ramps_with_acceleration() {
+    let (mut controller, drive) = simulated_controller(true);
+    controller.set_motor_enabled().unwrap();
+
+    // 1000 RPM/s, so half way to 1000 RPM after half a second
+    drive.set_register(ModbusRegister::MotorAcceleration, 1_000);
+    controller.set_rpm(10_000).unwrap();
+
+    drive.step(Duration::from_millis(500));
+    assert_eq!(5_000, controller.get_rpm().unwrap());
+
+    drive.step(Duration::from_millis(1_000));
+    assert_eq!(10_000, controller.get_rpm().unwrap());
+}
+
+#[test]
+fn simulated_drive_accumulates_position() {
+    let (mut controller, drive) = simulated_controller(true);
+    controller.set_motor_enabled().unwrap();
+
+    // No ramp, 60 RPM is one revolution a second
+    drive.set_register(ModbusRegister::MotorAcceleration, 60_000);
+    controller.set_rpm(600).unwrap();
+    drive.step(Duration::ZERO);
+
+    drive.step(Duration::from_secs(2));
+    assert_eq!(8_000, controller.get_position().unwrap());
+
+    controller.set_rpm(-600).unwrap();
+    drive.step(Duration::ZERO);
+    drive.step(Duration::from_secs(3));
+    assert_eq!(-4_000, controller.get_position().unwrap());
+}
+
+#[test]
+fn simulated_drive_position_crosses_word_boundary() {
+    let (mut controller, drive) = simulated_controller(true);
+
+    drive.set_position(0x0001_ffff);
+    assert_eq!(0x0001_ffff, controller.get_position().unwrap());
+
+    drive.set_position(-70_000);
+    assert_eq!(-70_000, controller.get_position().unwrap());
+}
+
+#[test]
+fn simulated_drive_reports_scripted_alarms() {
+    let (mut controller, drive) = simulated_controller(true);
+    controller.set_motor_enabled().unwrap();
+    drive.set_register(ModbusRegister::MotorAcceleration, 60_000);
+    controller.set_rpm(1_000).unwrap();
+    drive.step(Duration::from_millis(100));
+
+    let alarms = [
+        MotorStatus::Warning(MotorStatusWarning::HighTemperature),
+        MotorStatus::Warning(MotorStatusWarning::FlashWriteFailed),
+        MotorStatus::Fatal(MotorStatusFatal::Overheat),
+        MotorStatus::Fatal(MotorStatusFatal::SystemStall),
+        MotorStatus::Fatal(MotorStatusFatal::UnderVoltage),
+        MotorStatus::Fatal(MotorStatusFatal::LoadTooHeavy),
+    ];
+
+    for alarm in alarms {
+        drive.raise_alarm(alarm);
+        assert_eq!(alarm, controller.get_status().unwrap());
+    }
+
+    // A fatal alarm stops the motor
+    drive.step(Duration::from_millis(100));
+    assert_eq!(0, controller.get_rpm().unwrap());
+
+    // Disabling the output clears it
+    controller.set_motor_disabled().unwrap();
+    assert_eq!(MotorStatus::None, controller.get_status().unwrap());
+}
+
+#[test]
+fn simulated_drive_reports_voltage_and_temperature() {
+    let drive = SimulatedDrive::new(0x01);
+
+    drive.set_supply_voltage(36.0);
+    drive.set_temperature(55);
+
+    assert_eq!(36 * 327, drive.register(ModbusRegister::MotorV));
+    assert_eq!(55, drive.register(ModbusRegister::SystemTemperature));
+}
+
+#[test]
+fn simulated_drive_ignores_other_addresses() {
+    let drive = SimulatedDrive::new(0x02);
+    let mut controller = MotorController::from_transport(drive, 0x01);
+
+    assert!(controller.get_rpm().is_err());
+}
+
+#[test]
+fn simulated_drive_forgets_unsaved_parameters() {
+    let (mut controller, drive) = simulated_controller(true);
+
+    controller.set_position_gain(1_234).unwrap();
+    assert_eq!(
+        1_234,
+        drive.register(ModbusRegister::MotorPositionLoopProportionalCoefficient)
+    );
+
+    drive.power_cycle();
+
+    assert_eq!(0, drive.register(ModbusRegister::EnableModbus));
+    assert_eq!(
+        500,
+        drive.register(ModbusRegister::MotorPositionLoopProportionalCoefficient)
+    );
+}
+
+#[test]
+fn simulated_drive_writes_register_blocks() {
+    let (mut controller, drive) = simulated_controller(true);
+
+    controller.set_electronic_gear(3, 7).unwrap();
+
+    assert_eq!(3, drive.register(ModbusRegister::MotorElectronicGearHigh));
+    assert_eq!(7, drive.register(ModbusRegister::MotorElectronicGearLow));
+}
+
+fn position_mode_controller() -> (MotorController, SimulatedDrive) {
+    let (mut controller, drive) = simulated_controller(true);
+    drive.set_position_mode(true);
+
+    controller.set_motor_enabled().unwrap();
+    // Top speed of 60 RPM with no ramp, i.e. 4000 counts a second
+    drive.set_register(ModbusRegister::MotorAcceleration, 60_000);
+    controller.set_rpm(600).unwrap();
+
+    (controller, drive)
+}
+
+#[test]
+fn simulated_drive_moves_by_relative_target() {
+    let (mut controller, drive) = position_mode_controller();
+
+    controller.move_by_counts(4_000).unwrap();
+    assert_eq!(4_000, controller.get_target_position().unwrap());
+
+    drive.step(Duration::from_millis(500));
+    assert_eq!(2_000, controller.get_position().unwrap());
+
+    drive.step(Duration::from_secs(2));
+    assert_eq!(4_000, controller.get_position().unwrap());
+    assert_eq!(0, controller.get_rpm().unwrap());
+
+    controller.move_by_counts(-6_000).unwrap();
+    drive.step(Duration::from_secs(2));
+    assert_eq!(-2_000, controller.get_position().unwrap());
+}
+
+#[test]
+fn simulated_drive_moves_to_absolute_target() {
+    let (mut controller, drive) = position_mode_controller();
+
+    controller.move_to_counts(-3_000).unwrap();
+    drive.step(Duration::from_secs(1));
+    assert_eq!(-3_000, controller.get_position().unwrap());
+
+    // Repeating an absolute move goes nowhere
+    controller.move_to_counts(-3_000).unwrap();
+    drive.step(Duration::from_secs(1));
+    assert_eq!(-3_000, controller.get_position().unwrap());
+}
+
+#[test]
+fn move_to_zero_does_not_clear_the_count() {
+    let (mut controller, drive) = position_mode_controller();
+    // With the gear numerator at 0 the drive takes an absolute 0 as a clear
+    drive.set_register(ModbusRegister::MotorElectronicGearHigh, 0);
+    drive.set_position(8_000);
+
+    controller.move_to_counts(0).unwrap();
+    assert_eq!(8_000, controller.get_position().unwrap());
+
+    drive.step(Duration::from_secs(3));
+    assert_eq!(1, controller.get_position().unwrap());
+}
+
+#[test]
+fn writing_zero_position_clears_the_count() {
+    let (mut controller, drive) = position_mode_controller();
+    drive.set_register(ModbusRegister::MotorElectronicGearHigh, 0);
+    drive.set_position(8_000);
+
+    controller
+        .write_registers(ModbusRegister::MotorAbsolutePositionLow, &[0, 0])
+        .unwrap();
+    assert_eq!(0, controller.get_position().unwrap());
+}
+
+#[test]
+fn simulated_drive_moves_in_metres() {
+    let (mut controller, drive) = position_mode_controller();
+
+    // Half a wheel turn is half of 16 motor turns of 4000 counts
+    controller.move_to(MOTOR_WHEEL_LENGTH / 2.0).unwrap();
+    drive.step(Duration::from_secs(10));
+    assert_eq!(32_000, controller.get_position().unwrap());
+
+    controller.move_by(-MOTOR_WHEEL_LENGTH).unwrap();
+    drive.step(Duration::from_secs(20));
+    assert_eq!(-32_000, controller.get_position().unwrap());
+}
+
+#[test]
+fn set_position_fails_when_target_not_taken() {
+    let drive = SimulatedDrive::new(0x01);
+    let mut controller = MotorController::from_transport(drive, 0x01);
+
+    // Modbus isn't enabled, so the drive acknowledges but ignores the write
+    assert!(matches!(
+        controller.set_position(4_000),
+        Err(MotorControllerError::IncorrectPosition(4_000, 0))
+    ));
+}
+
+#[test]
+fn simulated_drive_moves_by_write_location() {
+    let (mut controller, drive) = position_mode_controller();
+
+    assert_eq!(0, controller.write_location(-2_000).unwrap());
+    assert_eq!(-2_000, controller.get_target_position().unwrap());
+
+    drive.step(Duration::from_secs(1));
+    assert_eq!(-2_000, controller.write_location(1_000).unwrap());
+
+    drive.step(Duration::from_secs(1));
+    assert_eq!(-1_000, controller.get_position().unwrap());
+}
+
+#[test]
+fn simulated_drive_changes_address() {
+    let (mut controller, drive) = simulated_controller(true);
+
+    controller.change_device_address(0x02, false).unwrap();
+    assert_eq!(0x02, controller.device_address());
+    assert_eq!(0x02, drive.register(ModbusRegister::DeviceAddress));
+    controller.get_rpm().unwrap();
+
+    // Not saved, so it comes back on the old address
+    drive.power_cycle();
+    assert!(controller.get_rpm().is_err());
+    assert_eq!(0x01, drive.register(ModbusRegister::DeviceAddress));
+}
+
+#[test]
+fn simulated_drive_keeps_saved_address() {
+    let (mut controller, drive) = simulated_controller(true);
+
+    controller.change_device_address(0x05, true).unwrap();
+    drive.power_cycle();
+
+    assert_eq!(0x05, drive.register(ModbusRegister::DeviceAddress));
+    controller.get_rpm().unwrap();
+}
+
+#[test]
+fn change_device_address_rejects_reserved_addresses() {
+    let (mut controller, drive) = simulated_controller(true);
+
+    for address in [0x00, 248, 0xff] {
+        assert!(matches!(
+            controller.change_device_address(address, false),
+            Err(MotorControllerError::InvalidDeviceAddress(a)) if a == address
+        ));
+    }
+
+    assert_eq!(0x01, controller.device_address());
+    assert_eq!(0x01, drive.register(ModbusRegister::DeviceAddress));
+}
+
+#[test]
+fn change_device_address_reverts_when_drive_ignores_it() {
+    let (mut controller, drive) = simulated_controller(false);
+
+    // Modbus isn't enabled, so the drive acknowledges but stays put
+    assert!(controller.change_device_address(0x02, false).is_err());
+    assert_eq!(0x01, controller.device_address());
+    assert_eq!(0x01, drive.register(ModbusRegister::DeviceAddress));
+}
+
+#[test]
+fn simulated_drive_refuses_missing_registers() {
+    let (mut controller, _drive) = simulated_controller(true);
+
+    // Only two registers left after 0x18
+    let read_past_the_end = ModbusRequest {
+        device_address: 0x01,
+        command: ModbusCommand::ReadRegister,
+        register: ModbusRegister::MotorSpeedFilterFrequency,
+        value: 3,
+    };
+
+    assert!(matches!(
+        controller.request(&read_past_the_end),
+        Err(MotorControllerError::ModbusException(
+            0x03,
+            ModbusException::IllegalDataAddress
+        ))
+    ));
+
+    // The drive is still there afterwards
+    controller.get_rpm().unwrap();
+}
+
+#[test]
+fn simulated_drive_refuses_unknown_functions() {
+    let mut drive = SimulatedDrive::new(0x01);
+
+    let mut request = vec![0x01, 0x2b, 0x0e, 0x01, 0x00];
+    let crc = crc16(&request);
+    request.extend([(crc >> 8) as u8, crc as u8]);
+    drive.write_all(&request).unwrap();
+
+    assert!(matches!(
+        ModbusResponse::from_reader(&mut drive).unwrap(),
+        ModbusResponse::ExceptionMessage {
+            device_address: 0x01,
+            function: 0x2b,
+            exception: ModbusException::IllegalFunction,
+        }
+    ));
+}
@@ -0,0 +1,82 @@
+use crate::ffi::*;
+use crate::message::ModbusRegister;
+use crate::motor_controller::error::MotorControllerError;
+use crate::motor_controller::motor_status::{MotorStatus, MotorStatusWarning};
+use crate::motor_controller::MotorController;
+use crate::simulator::SimulatedDrive;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+fn busy_drive() -> SimulatedDrive {
+    let drive = SimulatedDrive::new(0x01);
+    drive.set_supply_voltage(24.0);
+    drive.set_temperature(55);
+    drive.raise_alarm(MotorStatus::Warning(MotorStatusWarning::HighTemperature));
+    drive.set_register(ModbusRegister::MotorI, 3_000);
+    drive.set_register(ModbusRegister::MotorCurrentSpeed, (-1_200i16) as u16);
+    drive.set_register(ModbusRegister::SystemOutputPWM, (-16_384i16) as u16);
+
+    drive
+}
+
+#[test]
+fn telemetry_is_one_read() {
+    let drive = busy_drive();
+    let mut controller = MotorController::from_transport(drive.clone(), 0x01);
+    let frames = drive.frames_received();
+    let before = Instant::now();
+
+    let telemetry = controller.get_telemetry().unwrap();
+
+    assert_eq!(frames + 1, drive.frames_received());
+    assert_eq!(
+        MotorStatus::Warning(MotorStatusWarning::HighTemperature),
+        telemetry.status
+    );
+    assert_eq!(1.5, telemetry.current);
+    assert_eq!(-1_200, telemetry.rpm);
+    assert!((telemetry.voltage - 24.0).abs() < 0.01);
+    assert_eq!(55, telemetry.temperature);
+    assert_eq!(-50.0, telemetry.pwm);
+    assert!(telemetry.taken_at >= before && telemetry.taken_at <= Instant::now());
+}
+
+#[test]
+fn unknown_alarm_codes_are_an_error() {
+    let drive = busy_drive();
+    drive.set_register(ModbusRegister::MotorAlarmCode, 0x99);
+    let mut controller = MotorController::from_transport(drive, 0x01);
+
+    assert!(matches!(
+        controller.get_telemetry(),
+        Err(MotorControllerError::MotorStatusParseError(_))
+    ));
+}
+
+#[test]
+fn ffi_stamps_telemetry_with_the_wall_clock() {
+    let drive = busy_drive();
+    let ptr = Box::into_raw(Box::new(MotorController::from_transport(drive, 0x01)));
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs_f64();
+
+    unsafe {
+        let mut telemetry = std::mem::zeroed();
+        assert_eq!(
+            MotorControllerStatus::Ok,
+            motor_controller_get_telemetry(ptr, &mut telemetry)
+        );
+        assert_eq!(MotorAlarm::HighTemperature, telemetry.alarm);
+        assert_eq!(1.5, telemetry.current);
+        assert_eq!(55, telemetry.temperature);
+        assert!((telemetry.timestamp - now).abs() < 1.0);
+
+        assert_eq!(
+            MotorControllerStatus::NullPointer,
+            motor_controller_get_telemetry(ptr, std::ptr::null_mut())
+        );
+
+        motor_controller_free(ptr);
+    }
+}
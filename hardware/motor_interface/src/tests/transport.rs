@@ -0,0 +1,104 @@
+use crate::crc::crc16;
+use crate::motor_controller::MotorController;
+use crate::transport::{LoopbackTransport, Transport};
+use std::io::{ErrorKind, Read, Write};
+use std::thread;
+use std::time::Duration;
+
+#[cfg(unix)]
+use crate::transport::PtyTransport;
+
+const GET_RPM_FRAME: [u8; 8] = [0x01, 0x03, 0x00, 0x10, 0x00, 0x01, 0x85, 0xcf];
+
+fn with_crc(frame: &[u8]) -> Vec<u8> {
+    let crc = crc16(frame);
+    let mut message = frame.to_vec();
+    message.push((crc >> 8) as u8);
+    message.push(crc as u8);
+    message
+}
+
+// Plays the part of a drive that only knows how to answer one request. The
+// drive's end is handed back rather than dropped, as hanging up a pty would
+// discard the reply before the host gets to read it.
+fn answer_get_rpm<T: Transport + 'static>(mut drive: T, rpm: i16) -> thread::JoinHandle<T> {
+    thread::spawn(move || {
+        let mut request = [0u8; 8];
+        drive.read_exact(&mut request).unwrap();
+        assert_eq!(GET_RPM_FRAME, request);
+
+        let response = with_crc(&[0x01, 0x03, 0x02, (rpm >> 8) as u8, rpm as u8]);
+        drive.write_all(&response).unwrap();
+        drive.flush().unwrap();
+
+        drive
+    })
+}
+
+#[test]
+fn loopback_carries_bytes_both_ways() {
+    let (mut a, mut b) = LoopbackTransport::pair();
+
+    a.write_all(&[0xde, 0xad]).unwrap();
+    b.write_all(&[0xbe, 0xef]).unwrap();
+
+    let mut buf = [0u8; 2];
+    b.read_exact(&mut buf).unwrap();
+    assert_eq!([0xde, 0xad], buf);
+    a.read_exact(&mut buf).unwrap();
+    assert_eq!([0xbe, 0xef], buf);
+}
+
+#[test]
+fn loopback_times_out_when_nothing_arrives() {
+    let (mut a, _b) = LoopbackTransport::pair();
+    a.set_timeout(Duration::from_millis(10)).unwrap();
+
+    let mut buf = [0u8; 1];
+    let err = a.read(&mut buf).unwrap_err();
+    assert_eq!(ErrorKind::TimedOut, err.kind());
+}
+
+#[test]
+fn loopback_clear_buffers_drops_pending_input() {
+    let (mut a, mut b) = LoopbackTransport::pair();
+    a.set_timeout(Duration::from_millis(10)).unwrap();
+
+    b.write_all(&[0x01, 0x02, 0x03]).unwrap();
+    a.clear_buffers().unwrap();
+
+    let mut buf = [0u8; 1];
+    assert!(a.read(&mut buf).is_err());
+}
+
+#[test]
+fn controller_runs_over_loopback() {
+    let (host, drive) = LoopbackTransport::pair();
+    let drive = answer_get_rpm(drive, -1234);
+
+    let mut controller = MotorController::from_transport(host, 0x01);
+    assert_eq!(-1234, controller.get_rpm().unwrap());
+
+    drive.join().unwrap();
+}
+
+#[test]
+fn controller_reports_timeout_from_silent_drive() {
+    let (mut host, _drive) = LoopbackTransport::pair();
+    host.set_timeout(Duration::from_millis(10)).unwrap();
+
+    let mut controller = MotorController::from_transport(host, 0x01);
+    assert!(controller.get_rpm().is_err());
+}
+
+#[cfg(unix)]
+#[test]
+fn controller_runs_over_pty() {
+    let (host, drive) = PtyTransport::pair().expect("Failed to open a pseudo-terminal pair!");
+    let drive = answer_get_rpm(drive, 300);
+
+    let mut controller = MotorController::from_transport(host, 0x01);
+    assert_eq!(300, controller.get_rpm().unwrap());
+
+    drive.join().unwrap();
+}
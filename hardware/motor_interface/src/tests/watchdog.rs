@@ -0,0 +1,246 @@
+use super::simulated_controller;
+use crate::ffi::*;
+use crate::message::ModbusRegister;
+use crate::motor_controller::retry::RetryPolicy;
+use crate::motor_controller::watchdog::{WatchdogConfig, WatchdogTrip};
+use crate::motor_controller::MotorController;
+use crate::simulator::SimulatedDrive;
+use std::thread;
+use std::time::Duration;
+
+fn enabled_controller() -> (MotorController, SimulatedDrive) {
+    let (mut controller, drive) = simulated_controller(true);
+    controller.set_motor_enabled().unwrap();
+
+    (controller, drive)
+}
+
+fn target_speed(drive: &SimulatedDrive) -> i16 {
+    drive.register(ModbusRegister::MotorTargetSpeed) as i16
+}
+
+#[test]
+fn watchdog_ramps_down_when_commands_stop() {
+    let (mut controller, drive) = enabled_controller();
+    controller.set_watchdog(Some(WatchdogConfig {
+        timeout_ms: 30,
+        ramp_ms: 60,
+        disable_motor: true,
+    }));
+
+    controller.set_rpm(1_000).unwrap();
+    let frames = drive.frames_received();
+    thread::sleep(Duration::from_millis(300));
+
+    assert_eq!(0, target_speed(&drive));
+    assert_eq!(0, drive.register(ModbusRegister::EnableMotor));
+    // A few steps on the way down, then the disable
+    assert!(drive.frames_received() - frames >= 4);
+    assert_eq!(
+        Some(WatchdogTrip {
+            device_address: 0x01,
+            speed: 1_000,
+            stopped: true,
+            disabled: true,
+        }),
+        controller.take_watchdog_trip()
+    );
+    assert_eq!(None, controller.take_watchdog_trip());
+}
+
+#[test]
+fn watchdog_stays_quiet_while_fed() {
+    let (mut controller, drive) = enabled_controller();
+    controller.set_watchdog(Some(WatchdogConfig {
+        timeout_ms: 100,
+        ramp_ms: 0,
+        disable_motor: false,
+    }));
+
+    for _ in 0..10 {
+        controller.set_rpm(500).unwrap();
+        thread::sleep(Duration::from_millis(20));
+    }
+    assert_eq!(500, target_speed(&drive));
+
+    // Nothing to stop, so nothing to report
+    controller.set_rpm(0).unwrap();
+    thread::sleep(Duration::from_millis(200));
+    assert_eq!(None, controller.take_watchdog_trip());
+}
+
+#[test]
+fn watchdog_rearms_on_the_next_command() {
+    let (mut controller, drive) = enabled_controller();
+    controller.set_watchdog(Some(WatchdogConfig {
+        timeout_ms: 20,
+        ramp_ms: 0,
+        disable_motor: false,
+    }));
+
+    controller.set_rpm(-700).unwrap();
+    thread::sleep(Duration::from_millis(150));
+    assert_eq!(0, target_speed(&drive));
+    assert!(controller.take_watchdog_trip().is_some());
+
+    controller.set_rpm(300).unwrap();
+    assert_eq!(300, target_speed(&drive));
+    thread::sleep(Duration::from_millis(150));
+    assert_eq!(0, target_speed(&drive));
+    assert_eq!(300, controller.take_watchdog_trip().unwrap().speed);
+}
+
+#[test]
+fn watchdog_follows_an_address_change() {
+    let (mut controller, drive) = enabled_controller();
+    controller.set_watchdog(Some(WatchdogConfig {
+        timeout_ms: 30,
+        ramp_ms: 0,
+        disable_motor: true,
+    }));
+    controller.set_rpm(800).unwrap();
+    controller.change_device_address(0x05, false).unwrap();
+
+    thread::sleep(Duration::from_millis(200));
+
+    assert_eq!(0, target_speed(&drive));
+    assert_eq!(0, drive.register(ModbusRegister::EnableMotor));
+    assert_eq!(
+        Some(WatchdogTrip {
+            device_address: 0x05,
+            speed: 800,
+            stopped: true,
+            disabled: true,
+        }),
+        controller.take_watchdog_trip()
+    );
+}
+
+#[test]
+fn dropping_a_moving_controller_stops_it() {
+    let (mut controller, drive) = enabled_controller();
+    controller.set_rpm(800).unwrap();
+
+    drop(controller);
+
+    assert_eq!(0, target_speed(&drive));
+}
+
+#[test]
+fn dropping_after_a_raw_speed_write_stops_it() {
+    let (mut controller, drive) = enabled_controller();
+    controller
+        .write_register(ModbusRegister::MotorTargetSpeed, 800)
+        .unwrap();
+
+    drop(controller);
+
+    assert_eq!(0, target_speed(&drive));
+}
+
+#[test]
+fn dropping_after_a_block_write_with_speed_stops_it() {
+    let (mut controller, drive) = enabled_controller();
+    controller
+        .write_registers(ModbusRegister::EnableMotor, &[1, (-800i16) as u16])
+        .unwrap();
+    assert_eq!(-800, target_speed(&drive));
+
+    drop(controller);
+
+    assert_eq!(0, target_speed(&drive));
+}
+
+#[test]
+fn ffi_free_stops_a_raw_speed_write() {
+    let (controller, drive) = enabled_controller();
+    let ptr = Box::into_raw(Box::new(controller));
+
+    unsafe {
+        assert_eq!(
+            MotorControllerStatus::Ok,
+            motor_controller_write_register(ptr, ModbusRegister::MotorTargetSpeed as u16, 1_200)
+        );
+        assert_eq!(1_200, target_speed(&drive));
+
+        motor_controller_free(ptr);
+    }
+
+    assert_eq!(0, target_speed(&drive));
+}
+
+#[test]
+fn dropping_after_a_lost_stop_still_stops_it() {
+    let (mut controller, drive) = enabled_controller();
+    controller.set_retry_policy(RetryPolicy::never());
+    controller.set_rpm(800).unwrap();
+
+    drive.ignore_next_frames(1);
+    assert!(controller.set_rpm(0).is_err());
+    assert_eq!(800, target_speed(&drive));
+
+    drop(controller);
+
+    assert_eq!(0, target_speed(&drive));
+}
+
+#[test]
+fn watchdog_trips_after_a_lost_stop() {
+    let (mut controller, drive) = enabled_controller();
+    controller.set_retry_policy(RetryPolicy::never());
+    controller.set_watchdog(Some(WatchdogConfig {
+        timeout_ms: 20,
+        ramp_ms: 0,
+        disable_motor: false,
+    }));
+    controller.set_rpm(800).unwrap();
+
+    drive.ignore_next_frames(1);
+    assert!(controller.set_rpm(0).is_err());
+    thread::sleep(Duration::from_millis(150));
+
+    assert_eq!(0, target_speed(&drive));
+    assert_eq!(800, controller.take_watchdog_trip().unwrap().speed);
+}
+
+#[test]
+fn dropping_a_stopped_controller_sends_nothing() {
+    let (mut controller, drive) = enabled_controller();
+    controller.set_rpm(800).unwrap();
+    controller.set_rpm(0).unwrap();
+    let frames = drive.frames_received();
+
+    drop(controller);
+
+    assert_eq!(frames, drive.frames_received());
+}
+
+#[test]
+fn ffi_free_stops_the_motor() {
+    let (controller, drive) = enabled_controller();
+    let ptr = Box::into_raw(Box::new(controller));
+
+    unsafe {
+        let config = watchdog_config_default();
+        assert_eq!(
+            MotorControllerStatus::Ok,
+            motor_controller_set_watchdog(ptr, &config)
+        );
+        assert_eq!(
+            MotorControllerStatus::Ok,
+            motor_controller_set_rpm(ptr, 1_200, std::ptr::null_mut())
+        );
+
+        let mut trip = std::mem::zeroed();
+        let mut tripped = true;
+        assert_eq!(
+            MotorControllerStatus::Ok,
+            motor_controller_take_watchdog_trip(ptr, &mut trip, &mut tripped)
+        );
+        assert!(!tripped);
+
+        motor_controller_free(ptr);
+    }
+
+    assert_eq!(0, target_speed(&drive));
+}
@@ -0,0 +1,30 @@
+use std::io::{Read, Write};
+use std::time::Duration;
+
+mod loopback;
+#[cfg(unix)]
+mod pty;
+mod serial;
+
+pub use loopback::*;
+#[cfg(unix)]
+pub use pty::*;
+pub use serial::*;
+
+// A Transport is anything the motor controller can push Modbus frames down and
+// read responses back from. On the robot this is an RS-485 serial port, but the
+// driver does not care so long as bytes go in one end and come out the other.
+pub trait Transport: Read + Write + Send {
+    // How long a read will block before giving up with ErrorKind::TimedOut
+    fn timeout(&self) -> Duration;
+
+    fn set_timeout(&mut self, timeout: Duration) -> std::io::Result<()>;
+
+    fn baud_rate(&self) -> std::io::Result<u32>;
+
+    fn set_baud_rate(&mut self, baud_rate: u32) -> std::io::Result<()>;
+
+    // Throw away anything sitting in the input and output buffers, e.g. a late
+    // reply to a request we already gave up on
+    fn clear_buffers(&mut self) -> std::io::Result<()>;
+}
@@ -0,0 +1,115 @@
+use crate::transport::Transport;
+use std::collections::VecDeque;
+use std::io::{Read, Write};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+const LOOPBACK_DEFAULT_TIMEOUT: Duration = Duration::from_secs(1);
+const LOOPBACK_DEFAULT_BAUD_RATE: u32 = 19_200;
+
+#[derive(Default)]
+struct Channel {
+    bytes: Mutex<VecDeque<u8>>,
+    ready: Condvar,
+}
+
+// An in-memory cable. Bytes written to one end of the pair can be read from the
+// other, with reads blocking up to the timeout just like a serial port would.
+// The baud rate is only remembered, bytes get through whatever it is set to.
+pub struct LoopbackTransport {
+    rx: Arc<Channel>,
+    tx: Arc<Channel>,
+    timeout: Duration,
+    baud_rate: u32,
+}
+
+impl LoopbackTransport {
+    pub fn pair() -> (LoopbackTransport, LoopbackTransport) {
+        let a = Arc::new(Channel::default());
+        let b = Arc::new(Channel::default());
+
+        (
+            LoopbackTransport {
+                rx: a.clone(),
+                tx: b.clone(),
+                timeout: LOOPBACK_DEFAULT_TIMEOUT,
+                baud_rate: LOOPBACK_DEFAULT_BAUD_RATE,
+            },
+            LoopbackTransport {
+                rx: b,
+                tx: a,
+                timeout: LOOPBACK_DEFAULT_TIMEOUT,
+                baud_rate: LOOPBACK_DEFAULT_BAUD_RATE,
+            },
+        )
+    }
+}
+
+impl Read for LoopbackTransport {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        let deadline = Instant::now() + self.timeout;
+        let mut bytes = self.rx.bytes.lock().unwrap();
+
+        while bytes.is_empty() {
+            let now = Instant::now();
+            if now >= deadline {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::TimedOut,
+                    "Operation timed out",
+                ));
+            }
+
+            bytes = self.rx.ready.wait_timeout(bytes, deadline - now).unwrap().0;
+        }
+
+        let n = buf.len().min(bytes.len());
+        for (dst, src) in buf.iter_mut().zip(bytes.drain(..n)) {
+            *dst = src;
+        }
+
+        Ok(n)
+    }
+}
+
+impl Write for LoopbackTransport {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.tx.bytes.lock().unwrap().extend(buf);
+        self.tx.ready.notify_all();
+
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Transport for LoopbackTransport {
+    fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    fn set_timeout(&mut self, timeout: Duration) -> std::io::Result<()> {
+        self.timeout = timeout;
+        Ok(())
+    }
+
+    fn baud_rate(&self) -> std::io::Result<u32> {
+        Ok(self.baud_rate)
+    }
+
+    fn set_baud_rate(&mut self, baud_rate: u32) -> std::io::Result<()> {
+        self.baud_rate = baud_rate;
+        Ok(())
+    }
+
+    // Writes are delivered immediately, so only our input can have anything in it
+    fn clear_buffers(&mut self) -> std::io::Result<()> {
+        self.rx.bytes.lock().unwrap().clear();
+        Ok(())
+    }
+}
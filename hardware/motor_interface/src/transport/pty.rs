@@ -0,0 +1,65 @@
+use crate::transport::Transport;
+use serialport::{ClearBuffer, SerialPort, TTYPort};
+use std::io::{Read, Write};
+use std::time::Duration;
+
+// A pseudo-terminal pair. Each end behaves like a real tty, so this exercises
+// the same termios/timeout code paths as the USB adapter. The other end can
+// also be handed to an external program by its name (e.g. /dev/pts/3).
+pub struct PtyTransport {
+    port: TTYPort,
+}
+
+impl PtyTransport {
+    pub fn pair() -> Result<(PtyTransport, PtyTransport), serialport::Error> {
+        let (master, slave) = TTYPort::pair()?;
+
+        Ok((PtyTransport { port: master }, PtyTransport { port: slave }))
+    }
+
+    pub fn name(&self) -> Option<String> {
+        self.port.name()
+    }
+}
+
+impl Read for PtyTransport {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.port.read(buf)
+    }
+}
+
+impl Write for PtyTransport {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.port.write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.port.flush()
+    }
+}
+
+impl Transport for PtyTransport {
+    fn timeout(&self) -> Duration {
+        self.port.timeout()
+    }
+
+    fn set_timeout(&mut self, timeout: Duration) -> std::io::Result<()> {
+        self.port.set_timeout(timeout).map_err(std::io::Error::from)
+    }
+
+    fn baud_rate(&self) -> std::io::Result<u32> {
+        self.port.baud_rate().map_err(std::io::Error::from)
+    }
+
+    fn set_baud_rate(&mut self, baud_rate: u32) -> std::io::Result<()> {
+        self.port
+            .set_baud_rate(baud_rate)
+            .map_err(std::io::Error::from)
+    }
+
+    fn clear_buffers(&mut self) -> std::io::Result<()> {
+        self.port
+            .clear(ClearBuffer::All)
+            .map_err(std::io::Error::from)
+    }
+}
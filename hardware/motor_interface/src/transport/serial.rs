@@ -0,0 +1,80 @@
+use crate::transport::Transport;
+use serialport::{ClearBuffer, SerialPort};
+use std::io::{Read, Write};
+use std::time::Duration;
+
+// The motors talk Modbus RTU at 8N1 with no flow control
+pub struct SerialTransport {
+    port: Box<dyn SerialPort>,
+}
+
+impl SerialTransport {
+    pub fn open(
+        port_path: &str,
+        baud_rate: u32,
+        timeout: Duration,
+    ) -> Result<SerialTransport, serialport::Error> {
+        let port = serialport::new(port_path, baud_rate)
+            // Char size 8
+            .data_bits(serialport::DataBits::Eight)
+            // No parity bits
+            .parity(serialport::Parity::None)
+            // Stop bits
+            .stop_bits(serialport::StopBits::One)
+            // No hardware flow control
+            .flow_control(serialport::FlowControl::None)
+            // ---
+            .timeout(timeout)
+            .open()?;
+
+        Ok(SerialTransport { port })
+    }
+}
+
+impl From<Box<dyn SerialPort>> for SerialTransport {
+    fn from(port: Box<dyn SerialPort>) -> Self {
+        SerialTransport { port }
+    }
+}
+
+impl Read for SerialTransport {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.port.read(buf)
+    }
+}
+
+impl Write for SerialTransport {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.port.write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.port.flush()
+    }
+}
+
+impl Transport for SerialTransport {
+    fn timeout(&self) -> Duration {
+        self.port.timeout()
+    }
+
+    fn set_timeout(&mut self, timeout: Duration) -> std::io::Result<()> {
+        self.port.set_timeout(timeout).map_err(std::io::Error::from)
+    }
+
+    fn baud_rate(&self) -> std::io::Result<u32> {
+        self.port.baud_rate().map_err(std::io::Error::from)
+    }
+
+    fn set_baud_rate(&mut self, baud_rate: u32) -> std::io::Result<()> {
+        self.port
+            .set_baud_rate(baud_rate)
+            .map_err(std::io::Error::from)
+    }
+
+    fn clear_buffers(&mut self) -> std::io::Result<()> {
+        self.port
+            .clear(ClearBuffer::All)
+            .map_err(std::io::Error::from)
+    }
+}